@@ -0,0 +1,432 @@
+use super::price_provider::{normalize_contract_address, ETH_ADDRESS};
+use config::Config;
+use std::error;
+use std::io;
+
+pub const DEFAULT_CHAIN: &str = "ethereum";
+
+// Keys a chain missing from the built-in ones needs in Settings.toml
+const REQUIRED_KEYS: [&str; 6] = [
+    "chain_id",
+    "rpc_url",
+    "explorer_api",
+    "native_asset",
+    "native_coingecko_id",
+    "coingecko_platform",
+];
+
+// Etherscan compatible API listing the transactions of an account
+#[derive(Debug, Clone, PartialEq)]
+pub struct Explorer {
+    pub api: String,
+    pub key: String,
+}
+
+// EVM network with the endpoints and ids its balances and prices are read with,
+// chain names follow the ones of DefiLlama
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chain {
+    pub name: String,
+    pub chain_id: u64,
+    pub rpc_url: String,
+    pub explorer: Explorer,
+    pub native_asset: String,
+    pub native_coingecko_id: String,
+    pub coingecko_platform: String,
+    pub paraswap_network: Option<u64>,
+    pub usd_token: Option<String>,
+    pub eth_token: String,
+}
+
+impl Explorer {
+    pub fn etherscan(key: &str) -> Explorer {
+        Explorer {
+            api: "https://api.etherscan.io/api".to_string(),
+            key: key.to_string(),
+        }
+    }
+
+    // The api url can already carry a query, like the chainid of the Etherscan V2 API
+    pub fn get_url(&self, query: &str) -> String {
+        let separator = match self.api.contains('?') {
+            true => "&",
+            false => "?",
+        };
+
+        format!("{}{}{}&apikey={}", self.api, separator, query, self.key)
+    }
+}
+
+impl Default for Chain {
+    fn default() -> Chain {
+        Chain {
+            name: DEFAULT_CHAIN.to_string(),
+            chain_id: 1,
+            rpc_url: String::new(),
+            explorer: Explorer::etherscan(""),
+            native_asset: "ETH".to_string(),
+            native_coingecko_id: "ethereum".to_string(),
+            coingecko_platform: "ethereum".to_string(),
+            paraswap_network: Some(1),
+            usd_token: Some("0xdac17f958d2ee523a2206206994597c13d831ec7".to_string()),
+            eth_token: ETH_ADDRESS.to_string(),
+        }
+    }
+}
+
+impl Chain {
+    // Ethplorer, the Chainlink feeds and the Uniswap pools only cover Ethereum mainnet
+    pub fn is_ethereum(&self) -> bool {
+        self.chain_id == 1
+    }
+
+    pub fn get_coingecko_link(&self) -> String {
+        format!(
+            "https://coingecko.com/en/coins/{}",
+            self.native_coingecko_id
+        )
+    }
+
+    // OP Stack rollups charge an L1 data fee on top of the gas used on the L2
+    pub fn is_op_stack(&self) -> bool {
+        matches!(self.chain_id, 10 | 8453)
+    }
+
+    // Token prices are asked versus ETH, chains paying gas in another asset rebase them
+    pub fn is_eth_native(&self) -> bool {
+        self.native_asset == "ETH"
+    }
+}
+
+// Built-in chains read through Infura, and the Etherscan V2 API outside of mainnet
+pub fn default_chains(infura_key: &str, etherscan_key: &str) -> Vec<Chain> {
+    let ethereum = Chain {
+        rpc_url: format!("https://mainnet.infura.io/v3/{}", infura_key),
+        explorer: Explorer::etherscan(etherscan_key),
+        ..Default::default()
+    };
+
+    // Name, chain id, Infura network, native asset and its CoinGecko id, CoinGecko platform,
+    // USD stablecoin, bridged WETH
+    let networks = [
+        (
+            "polygon",
+            137,
+            "polygon-mainnet",
+            "POL",
+            "polygon-ecosystem-token",
+            "polygon-pos",
+            "0xc2132d05d31c914a87c6611c10748aeb04b58e8f",
+            "0x7ceb23fd6bc0add59e62ac25578270cff1b9f619",
+        ),
+        (
+            "arbitrum",
+            42161,
+            "arbitrum-mainnet",
+            "ETH",
+            "ethereum",
+            "arbitrum-one",
+            "0xfd086bc7cd5c481dcc9c85ebe478a1c0b69fcbb9",
+            ETH_ADDRESS,
+        ),
+        (
+            "optimism",
+            10,
+            "optimism-mainnet",
+            "ETH",
+            "ethereum",
+            "optimistic-ethereum",
+            "0x94b008aa00579c1307b0ef2c499ad98a8ce58e58",
+            ETH_ADDRESS,
+        ),
+        (
+            "base",
+            8453,
+            "base-mainnet",
+            "ETH",
+            "ethereum",
+            "base",
+            "0x833589fcd6edb6e08f4c7c32d4f71b54bda02913",
+            ETH_ADDRESS,
+        ),
+        (
+            "bsc",
+            56,
+            "bsc-mainnet",
+            "BNB",
+            "binancecoin",
+            "binance-smart-chain",
+            "0x55d398326f99059ff775485246999027b3197955",
+            "0x2170ed0880ac9a755fd29b2688956bd959f933f8",
+        ),
+    ];
+
+    let mut chains = vec![ethereum];
+
+    for (
+        name,
+        chain_id,
+        network,
+        native_asset,
+        native_coingecko_id,
+        coingecko_platform,
+        usd_token,
+        eth_token,
+    ) in networks
+    {
+        chains.push(Chain {
+            name: name.to_string(),
+            chain_id,
+            rpc_url: format!("https://{}.infura.io/v3/{}", network, infura_key),
+            explorer: Explorer {
+                api: format!("https://api.etherscan.io/v2/api?chainid={}", chain_id),
+                key: etherscan_key.to_string(),
+            },
+            native_asset: native_asset.to_string(),
+            native_coingecko_id: native_coingecko_id.to_string(),
+            coingecko_platform: coingecko_platform.to_string(),
+            paraswap_network: Some(chain_id),
+            usd_token: Some(usd_token.to_string()),
+            eth_token: eth_token.to_string(),
+        });
+    }
+
+    chains
+}
+
+// Built-in chains with the [chains.<name>] sections of Settings.toml, every key overrides the
+// built-in one and new chains need all of them but explorer_key, paraswap_network, usd_token
+// and eth_token:
+//
+// [chains.<name>]
+// chain_id = 100
+// rpc_url = "https://rpc.gnosischain.com"
+// explorer_api = "https://api.gnosisscan.io/api"
+// explorer_key = "<explorer-api-key>"
+// native_asset = "XDAI"
+// native_coingecko_id = "xdai"
+// coingecko_platform = "xdai"
+// paraswap_network = 100
+// usd_token = "<usd-stablecoin-address>"
+// eth_token = "<weth-address>"
+pub fn load_chains(settings: &Config) -> Result<Vec<Chain>, Box<dyn error::Error>> {
+    let infura_key = settings.get::<String>("infura")?;
+    let etherscan_key = settings.get::<String>("etherscan")?;
+
+    let mut chains = default_chains(&infura_key, &etherscan_key);
+
+    let table = match settings.get_table("chains") {
+        Ok(table) => table,
+        Err(_) => return Ok(chains),
+    };
+
+    let mut names: Vec<String> = table.keys().cloned().collect();
+    names.sort();
+
+    for name in names {
+        let definition = table[&name].clone().into_table()?;
+        let name = name.to_lowercase();
+
+        let get_string = |key: &str| -> Result<Option<String>, Box<dyn error::Error>> {
+            match definition.get(key) {
+                Some(value) => Ok(Some(value.clone().into_string()?)),
+                None => Ok(None),
+            }
+        };
+        let get_uint = |key: &str| -> Result<Option<u64>, Box<dyn error::Error>> {
+            match definition.get(key) {
+                Some(value) => Ok(Some(value.clone().into_uint()?)),
+                None => Ok(None),
+            }
+        };
+
+        let index = match chains.iter().position(|chain| chain.name == name) {
+            Some(index) => index,
+            None => {
+                for key in REQUIRED_KEYS {
+                    if !definition.contains_key(key) {
+                        return Err(Box::new(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!("Missing {} in chain {}", key, name),
+                        )));
+                    }
+                }
+                chains.push(Chain {
+                    name: name.to_string(),
+                    explorer: Explorer::etherscan(&etherscan_key),
+                    paraswap_network: None,
+                    usd_token: None,
+                    ..Default::default()
+                });
+                chains.len() - 1
+            }
+        };
+        let chain = &mut chains[index];
+
+        if let Some(chain_id) = get_uint("chain_id")? {
+            chain.chain_id = chain_id;
+        }
+        if let Some(rpc_url) = get_string("rpc_url")? {
+            chain.rpc_url = rpc_url;
+        }
+        if let Some(explorer_api) = get_string("explorer_api")? {
+            chain.explorer.api = explorer_api;
+        }
+        if let Some(explorer_key) = get_string("explorer_key")? {
+            chain.explorer.key = explorer_key;
+        }
+        if let Some(native_asset) = get_string("native_asset")? {
+            chain.native_asset = native_asset.to_uppercase();
+        }
+        if let Some(native_coingecko_id) = get_string("native_coingecko_id")? {
+            chain.native_coingecko_id = native_coingecko_id;
+        }
+        if let Some(coingecko_platform) = get_string("coingecko_platform")? {
+            chain.coingecko_platform = coingecko_platform;
+        }
+        if let Some(paraswap_network) = get_uint("paraswap_network")? {
+            chain.paraswap_network = Some(paraswap_network);
+        }
+        if let Some(usd_token) = get_string("usd_token")? {
+            chain.usd_token = Some(normalize_contract_address(&usd_token)?);
+        }
+        if let Some(eth_token) = get_string("eth_token")? {
+            chain.eth_token = normalize_contract_address(&eth_token)?;
+        }
+    }
+
+    Ok(chains)
+}
+
+pub fn load_chain(settings: &Config, name: &str) -> Result<Chain, Box<dyn error::Error>> {
+    let chains = load_chains(settings)?;
+
+    match chains
+        .iter()
+        .find(|chain| chain.name.eq_ignore_ascii_case(name))
+    {
+        Some(chain) => Ok(chain.clone()),
+        None => {
+            let names: Vec<&str> = chains.iter().map(|chain| chain.name.as_str()).collect();
+            Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Unknown chain {}, expected one of: {}",
+                    name,
+                    names.join(", ")
+                ),
+            )))
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+
+    fn get_settings(toml: &str) -> Config {
+        config::Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn get_url_success() {
+        let chains = default_chains("infura", "etherscan");
+
+        assert_eq!(
+            chains[0]
+                .explorer
+                .get_url("module=block&action=getblocknobytime"),
+            "https://api.etherscan.io/api?module=block&action=getblocknobytime&apikey=etherscan"
+        );
+        assert_eq!(
+            chains[1].explorer.get_url("module=account&action=txlist"),
+            "https://api.etherscan.io/v2/api?chainid=137&module=account&action=txlist&apikey=etherscan"
+        );
+        assert_eq!(
+            chains[1].rpc_url,
+            "https://polygon-mainnet.infura.io/v3/infura"
+        );
+    }
+
+    #[tokio::test]
+    async fn load_chains_success() {
+        let settings = get_settings(
+            r#"
+            infura = "infura"
+            etherscan = "etherscan"
+
+            [chains.polygon]
+            rpc_url = "https://polygon-rpc.com"
+            explorer_api = "https://api.polygonscan.com/api"
+            explorer_key = "polygonscan"
+
+            [chains.gnosis]
+            chain_id = 100
+            rpc_url = "https://rpc.gnosischain.com"
+            explorer_api = "https://api.gnosisscan.io/api"
+            native_asset = "xdai"
+            native_coingecko_id = "xdai"
+            coingecko_platform = "xdai"
+            "#,
+        );
+
+        let chains = load_chains(&settings).unwrap();
+        assert_eq!(chains.len(), 7);
+        assert!(chains[0].is_ethereum());
+
+        let polygon = load_chain(&settings, "Polygon").unwrap();
+        assert_eq!(polygon.rpc_url, "https://polygon-rpc.com");
+        assert_eq!(polygon.explorer.key, "polygonscan");
+        assert_eq!(polygon.coingecko_platform, "polygon-pos");
+
+        let gnosis = load_chain(&settings, "gnosis").unwrap();
+        assert_eq!(gnosis.native_asset, "XDAI");
+        assert_eq!(
+            gnosis.get_coingecko_link(),
+            "https://coingecko.com/en/coins/xdai"
+        );
+        assert!(!gnosis.is_eth_native());
+        assert_eq!(gnosis.explorer.key, "etherscan");
+        assert_eq!(gnosis.paraswap_network, None);
+    }
+
+    #[tokio::test]
+    async fn load_chains_missing_key_fail() {
+        let settings = get_settings(
+            r#"
+            infura = "infura"
+            etherscan = "etherscan"
+
+            [chains.gnosis]
+            chain_id = 100
+            "#,
+        );
+
+        let result = load_chains(&settings);
+        if let Result::Err(err) = result {
+            assert_eq!((*err).to_string(), "Missing rpc_url in chain gnosis");
+        }
+    }
+
+    #[tokio::test]
+    async fn load_chain_unknown_fail() {
+        let settings = get_settings(
+            r#"
+            infura = "infura"
+            etherscan = "etherscan"
+            "#,
+        );
+
+        let result = load_chain(&settings, "solana");
+        if let Result::Err(err) = result {
+            assert_eq!(
+                (*err).to_string(),
+                "Unknown chain solana, expected one of: ethereum, polygon, arbitrum, optimism, base, bsc"
+            );
+        }
+    }
+}
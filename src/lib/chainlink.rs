@@ -0,0 +1,367 @@
+use super::onchain;
+use super::price_provider::{normalize_contract_address, PriceProvider, ETH_ADDRESS};
+use async_trait::async_trait;
+use config::Config;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error;
+use std::io;
+use std::time::{SystemTime, UNIX_EPOCH};
+use web3::ethabi::ParamType;
+use web3::transports::Http;
+use web3::types::H160;
+use web3::Web3;
+
+// Longest heartbeat of the default feeds is 24 hours, allow one extra hour
+pub const DEFAULT_MAX_AGE: u64 = 25 * 60 * 60;
+
+// Token contract address, USD aggregator and ETH aggregator on mainnet
+const DEFAULT_FEEDS: [(&str, Option<&str>, Option<&str>); 7] = [
+    // ETH
+    (
+        ETH_ADDRESS,
+        Some("0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419"),
+        None,
+    ),
+    // WETH
+    (
+        "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2",
+        Some("0x5f4ec3df9cbd43714fe2740f5e3616155c5b8419"),
+        None,
+    ),
+    // WBTC
+    (
+        "0x2260fac5e5542a773aa44fbcfedf7c193bc2c599",
+        Some("0xf4030086522a5beea4988f8ca5b36dbc97bee88c"),
+        Some("0xdeb288f737066589598e9214e782fa5a8ed689e8"),
+    ),
+    // LINK
+    (
+        "0x514910771af9ca656af840dff83e8264ecf986ca",
+        Some("0x2c1d072e956affc0d435cb7ac38ef18d24d9127c"),
+        Some("0xdc530d9457755926550b59e8eccdae7624181557"),
+    ),
+    // USDC
+    (
+        "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48",
+        Some("0x8fffffd4afb6115b954bd326cbe7b4ba576818f6"),
+        Some("0x986b5e1e1755e3c2440e960477f25201b0a8bbd4"),
+    ),
+    // USDT
+    (
+        "0xdac17f958d2ee523a2206206994597c13d831ec7",
+        Some("0x3e7d1eab13ad0104d2750b8863b489d65364e32d"),
+        Some("0xee9f2375b4bdf6387aa8265dd4fb8f16512a1d46"),
+    ),
+    // DAI
+    (
+        "0x6b175474e89094c44da98b954eedeac495271d0f",
+        Some("0xaed0c38402a5d19df6e4c03f4e2dced6e29c1ee9"),
+        Some("0x773616e4d11a78f511299002da57a0a94577f1f4"),
+    ),
+];
+
+#[derive(Debug, Clone)]
+pub struct Feed {
+    pub usd: Option<H160>,
+    pub eth: Option<H160>,
+}
+
+pub type Feeds = HashMap<String, Feed>;
+
+pub struct Chainlink {
+    web3: Web3<Http>,
+    feeds: Feeds,
+    max_age: u64,
+}
+
+#[derive(Debug)]
+pub struct Round {
+    pub answer: f64,
+    pub updated_at: u64,
+}
+
+#[async_trait]
+impl PriceProvider for Chainlink {
+    fn get_name(&self) -> &str {
+        "chainlink"
+    }
+
+    async fn fetch<'a>(
+        &self,
+        url: &'a str,
+        _verbose: bool,
+    ) -> Result<Value, Box<dyn error::Error>> {
+        Err(Box::new(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!(
+                "Chainlink prices are read on-chain, could not fetch {}",
+                url
+            ),
+        )))
+    }
+
+    async fn get_token_id_from_contract_address<'a>(
+        &self,
+        contract_address: &'a str,
+        _verbose: bool,
+    ) -> Result<String, Box<dyn error::Error>> {
+        let token_id = normalize_contract_address(contract_address)?;
+
+        match self.feeds.contains_key(&token_id) {
+            true => Ok(token_id),
+            false => Err(Box::new(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No Chainlink feed configured for {}", contract_address),
+            ))),
+        }
+    }
+
+    async fn get_token_price<'a>(
+        &self,
+        token_id: &'a str,
+        versus_name: &'a str,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let feed = self.feeds.get(token_id).ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No Chainlink feed configured for {}", token_id),
+            )
+        })?;
+
+        let price = match (versus_name, feed.usd, feed.eth) {
+            ("usd", Some(usd), _) => self.get_answer(usd, verbose).await?,
+            ("usd", None, Some(eth)) => {
+                let eth_price = self.get_answer(eth, verbose).await?;
+                let eth_usd_price = self.get_eth_usd_price(verbose).await?;
+                eth_price * eth_usd_price
+            }
+            ("eth", _, Some(eth)) => self.get_answer(eth, verbose).await?,
+            ("eth", Some(usd), None) => {
+                let usd_price = self.get_answer(usd, verbose).await?;
+                let eth_usd_price = self.get_eth_usd_price(verbose).await?;
+                usd_price / eth_usd_price
+            }
+            _ => {
+                return Err(Box::new(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("Could not fetch token price versus {}", versus_name),
+                )))
+            }
+        };
+
+        Ok(price)
+    }
+}
+
+impl Chainlink {
+    pub fn new(web3: Web3<Http>, feeds: Feeds, max_age: u64) -> Chainlink {
+        Chainlink {
+            web3,
+            feeds,
+            max_age,
+        }
+    }
+
+    async fn get_eth_usd_price(&self, verbose: bool) -> Result<f64, Box<dyn error::Error>> {
+        match self.feeds.get(ETH_ADDRESS).and_then(|feed| feed.usd) {
+            Some(usd) => self.get_answer(usd, verbose).await,
+            None => Err("No Chainlink ETH/USD feed configured".into()),
+        }
+    }
+
+    async fn get_answer(
+        &self,
+        aggregator: H160,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let round = self.get_latest_round(aggregator).await?;
+        let age = now().saturating_sub(round.updated_at);
+
+        if verbose {
+            println!(
+                "Chainlink aggregator {:?} answer: {} updated {} seconds ago",
+                aggregator, round.answer, age
+            );
+        }
+
+        if age > self.max_age {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Stale Chainlink round for aggregator {:?}, updated {} seconds ago",
+                    aggregator, age
+                ),
+            )));
+        }
+
+        Ok(round.answer)
+    }
+
+    pub async fn get_latest_round(&self, aggregator: H160) -> Result<Round, Box<dyn error::Error>> {
+        let decimals = onchain::get_decimals(&self.web3, aggregator).await?;
+        let result = onchain::call(
+            &self.web3,
+            aggregator,
+            "latestRoundData()",
+            &[],
+            &[
+                ParamType::Uint(80),
+                ParamType::Int(256),
+                ParamType::Uint(256),
+                ParamType::Uint(256),
+                ParamType::Uint(80),
+            ],
+            None,
+        )
+        .await?;
+
+        let answer = onchain::get_int(&result, 1)?;
+        let updated_at = onchain::get_uint(&result, 3)?;
+
+        if answer.bit(255) || answer.is_zero() {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Invalid Chainlink answer for aggregator {:?}", aggregator),
+            )));
+        }
+
+        Ok(Round {
+            answer: onchain::scale(answer, decimals),
+            updated_at: updated_at.low_u64(),
+        })
+    }
+}
+
+pub fn default_feeds() -> Feeds {
+    DEFAULT_FEEDS
+        .iter()
+        .map(|(contract_address, usd, eth)| {
+            (
+                contract_address.to_string(),
+                Feed {
+                    usd: usd.and_then(|usd| usd.parse().ok()),
+                    eth: eth.and_then(|eth| eth.parse().ok()),
+                },
+            )
+        })
+        .collect()
+}
+
+// Reads the [pricing.chainlink.feeds] table of Settings.toml on top of the default feeds:
+// "<token-contract-address>" = { usd = "<aggregator>", eth = "<aggregator>" }
+pub fn load_feeds(settings: &Config) -> Result<Feeds, Box<dyn error::Error>> {
+    let mut feeds = default_feeds();
+
+    let table = match settings.get_table("pricing.chainlink.feeds") {
+        Ok(table) => table,
+        Err(_) => return Ok(feeds),
+    };
+
+    for (contract_address, value) in table {
+        let feed = value.into_table()?;
+        let usd = match feed.get("usd") {
+            Some(usd) => Some(usd.clone().into_string()?.parse::<H160>()?),
+            None => None,
+        };
+        let eth = match feed.get("eth") {
+            Some(eth) => Some(eth.clone().into_string()?.parse::<H160>()?),
+            None => None,
+        };
+
+        feeds.insert(
+            normalize_contract_address(&contract_address)?,
+            Feed { usd, eth },
+        );
+    }
+
+    Ok(feeds)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+
+    fn get_web3() -> Web3<Http> {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_infura_key = settings
+            .get::<String>("test_infura")
+            .unwrap_or_else(|_| panic!("test infura key is not set in Settings.toml, exit."));
+
+        let endpoint = format!("https://mainnet.infura.io/v3/{}", test_infura_key);
+        let transport = web3::transports::Http::new(&endpoint).unwrap();
+        web3::Web3::new(transport)
+    }
+
+    #[tokio::test]
+    async fn get_token_price_success() {
+        let chainlink = Chainlink::new(get_web3(), default_feeds(), DEFAULT_MAX_AGE);
+
+        let price = chainlink
+            .get_token_price(ETH_ADDRESS, "usd", false)
+            .await
+            .unwrap();
+        assert_ne!(price, 0.0);
+
+        // LINK token address
+        let link_price_eth = chainlink
+            .get_token_price("0x514910771af9ca656af840dff83e8264ecf986ca", "eth", false)
+            .await
+            .unwrap();
+        assert_ne!(link_price_eth, 0.0);
+    }
+
+    #[tokio::test]
+    async fn get_token_price_stale_fail() {
+        let chainlink = Chainlink::new(get_web3(), default_feeds(), 0);
+        let result = chainlink.get_token_price(ETH_ADDRESS, "usd", false).await;
+        if let Result::Err(err) = result {
+            assert!((*err).to_string().starts_with("Stale Chainlink round"));
+        }
+    }
+
+    #[tokio::test]
+    async fn get_token_id_fail() {
+        let chainlink = Chainlink::new(get_web3(), default_feeds(), DEFAULT_MAX_AGE);
+
+        // YFI token address, without a default feed
+        let result = chainlink
+            .get_token_id_from_contract_address("0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e", false)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn load_feeds_success() {
+        let settings = config::Config::builder()
+            .add_source(config::File::from_str(
+                r#"
+                [pricing.chainlink.feeds]
+                "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e" = { eth = "0x7c5d4f8345e66f68099581db340cd65b078c41f4" }
+                "#,
+                config::FileFormat::Toml,
+            ))
+            .build()
+            .unwrap();
+
+        let feeds = load_feeds(&settings).unwrap();
+        assert_eq!(feeds.len(), DEFAULT_FEEDS.len() + 1);
+
+        let feed = feeds
+            .get("0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e")
+            .unwrap();
+        assert!(feed.usd.is_none());
+        assert!(feed.eth.is_some());
+    }
+}
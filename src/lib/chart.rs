@@ -0,0 +1,252 @@
+use super::historical;
+
+pub const DEFAULT_WIDTH: usize = 72;
+pub const DEFAULT_HEIGHT: usize = 15;
+
+// Line chart of (timestamp, value) points, one text line per row plus the x axis
+pub fn render(points: &[(i64, f64)], width: usize, height: usize) -> Vec<String> {
+    if points.is_empty() || width < 2 || height < 2 {
+        return vec![];
+    }
+
+    let mut points = points.to_vec();
+    points.sort_by_key(|point| point.0);
+
+    let (mut min, mut max) = points
+        .iter()
+        .fold((f64::MAX, f64::MIN), |(min, max), (_, value)| {
+            (min.min(*value), max.max(*value))
+        });
+    if (max - min).abs() < f64::EPSILON {
+        let margin = match max.abs() > 0.0 {
+            true => max.abs() * 0.05,
+            false => 1.0,
+        };
+        min -= margin;
+        max += margin;
+    }
+
+    let get_row = |value: f64| -> usize {
+        ((max - value) / (max - min) * (height - 1) as f64).round() as usize
+    };
+
+    let values = resample(&points, width);
+    let mut grid = vec![vec![' '; width]; height];
+
+    for (column, value) in values.iter().enumerate() {
+        let row = get_row(*value);
+        let previous_row = match column {
+            0 => row,
+            _ => get_row(values[column - 1]),
+        };
+
+        if row == previous_row {
+            grid[row][column] = '─';
+            continue;
+        }
+
+        let (top, bottom) = (row.min(previous_row), row.max(previous_row));
+        for line in grid.iter_mut().take(bottom).skip(top + 1) {
+            line[column] = '│';
+        }
+
+        // Rows grow downwards, a smaller row means the value went up
+        match row < previous_row {
+            true => {
+                grid[previous_row][column] = '╯';
+                grid[row][column] = '╭';
+            }
+            false => {
+                grid[previous_row][column] = '╮';
+                grid[row][column] = '╰';
+            }
+        }
+    }
+
+    let labels: Vec<String> = (0..height)
+        .map(|row| {
+            format!(
+                "{:.2}",
+                max - (max - min) * row as f64 / (height - 1) as f64
+            )
+        })
+        .collect();
+    let label_width = labels.iter().map(String::len).max().unwrap_or(0);
+
+    let mut lines: Vec<String> = grid
+        .iter()
+        .zip(&labels)
+        .map(|(line, label)| {
+            format!(
+                "{:>width$} ┤{}",
+                label,
+                line.iter().collect::<String>(),
+                width = label_width
+            )
+        })
+        .collect();
+
+    lines.push(format!(
+        "{:>width$} └{}",
+        "",
+        "─".repeat(width),
+        width = label_width
+    ));
+
+    let first_date = get_date(points[0].0);
+    let last_date = get_date(points[points.len() - 1].0);
+    lines.push(format!(
+        "{:>label_width$}  {}{:>width$}",
+        "",
+        first_date,
+        last_date,
+        label_width = label_width,
+        width = width.saturating_sub(first_date.len())
+    ));
+
+    lines
+}
+
+// Value at each column, interpolated between the surrounding points
+fn resample(points: &[(i64, f64)], width: usize) -> Vec<f64> {
+    let start = points[0].0;
+    let end = points[points.len() - 1].0;
+
+    (0..width)
+        .map(|column| {
+            let timestamp =
+                start as f64 + (end - start) as f64 * column as f64 / (width - 1) as f64;
+
+            let next = points
+                .iter()
+                .position(|(point_timestamp, _)| *point_timestamp as f64 >= timestamp)
+                .unwrap_or(points.len() - 1);
+
+            match next {
+                0 => points[0].1,
+                _ => {
+                    let (previous_timestamp, previous_value) = points[next - 1];
+                    let (next_timestamp, next_value) = points[next];
+                    let span = (next_timestamp - previous_timestamp) as f64;
+                    match span > 0.0 {
+                        true => {
+                            previous_value
+                                + (next_value - previous_value)
+                                    * (timestamp - previous_timestamp as f64)
+                                    / span
+                        }
+                        false => next_value,
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+// Totals across series of the points falling on the same UTC day, each series only counting
+// its latest point of the day, e.g. the current price ending a CoinGecko market chart
+pub fn sum_by_day(series: &[Vec<(i64, f64)>]) -> Vec<(i64, f64)> {
+    let mut days: Vec<(i64, f64)> = vec![];
+
+    for points in series {
+        for (day, value) in get_last_by_day(points) {
+            match days.iter_mut().find(|(existing, _)| *existing == day) {
+                Some((_, total)) => *total += value,
+                None => days.push((day, value)),
+            }
+        }
+    }
+
+    days.sort_by_key(|(day, _)| *day);
+
+    days
+}
+
+fn get_last_by_day(points: &[(i64, f64)]) -> Vec<(i64, f64)> {
+    let mut last_points: Vec<(i64, i64, f64)> = vec![];
+
+    for (timestamp, value) in points {
+        let day = timestamp - timestamp.rem_euclid(86400);
+        match last_points
+            .iter_mut()
+            .find(|(existing, _, _)| *existing == day)
+        {
+            Some(last_point) if *timestamp >= last_point.1 => {
+                *last_point = (day, *timestamp, *value)
+            }
+            Some(_) => (),
+            None => last_points.push((day, *timestamp, *value)),
+        }
+    }
+
+    last_points
+        .into_iter()
+        .map(|(day, _, value)| (day, value))
+        .collect()
+}
+
+fn get_date(timestamp: i64) -> String {
+    historical::format_timestamp(timestamp)
+        .split(' ')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn render_success() {
+        let points = vec![
+            (1704067199, 100.0),
+            (1704153599, 200.0),
+            (1704239999, 150.0),
+        ];
+
+        let lines = render(&points, 20, 5);
+        assert_eq!(lines.len(), 7);
+        assert!(lines[0].starts_with("200.00 ┤"));
+        assert!(lines[4].starts_with("100.00 ┤─"));
+        assert!(lines[6].contains("2023-12-31"));
+        assert!(lines[6].ends_with("2024-01-02"));
+    }
+
+    #[tokio::test]
+    async fn render_flat_success() {
+        let lines = render(&[(1704067199, 100.0)], 10, 3);
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1], "100.00 ┤──────────");
+    }
+
+    #[tokio::test]
+    async fn render_empty_success() {
+        assert!(render(&[], 10, 3).is_empty());
+    }
+
+    #[tokio::test]
+    async fn resample_success() {
+        let points = vec![(0, 0.0), (10, 10.0)];
+        assert_eq!(resample(&points, 3), vec![0.0, 5.0, 10.0]);
+    }
+
+    #[tokio::test]
+    async fn sum_by_day_success() {
+        let series = vec![
+            vec![(86400 + 10, 1.0), (10, 2.0)],
+            vec![(86400 + 20, 3.0), (20, 4.0)],
+        ];
+        assert_eq!(sum_by_day(&series), vec![(0, 6.0), (86400, 4.0)]);
+    }
+
+    #[tokio::test]
+    async fn sum_by_day_same_day_success() {
+        // Daily price at midnight followed by the current price of the same day
+        let series = vec![
+            vec![(0, 1.0), (86400, 2.0), (86400 + 3600, 2.5)],
+            vec![(86400, 10.0)],
+        ];
+        assert_eq!(sum_by_day(&series), vec![(0, 1.0), (86400, 12.5)]);
+    }
+}
@@ -1,4 +1,6 @@
-use super::price_provider;
+use super::chain::Chain;
+use super::historical::Historical;
+use super::price_provider::{self, PriceProvider};
 use async_trait::async_trait;
 use serde_json::Value;
 use std::error;
@@ -6,10 +8,24 @@ use std::io;
 use std::thread::sleep;
 use std::time::Duration;
 
-pub struct Coingecko;
+// Contract addresses are looked up on the asset platform of a chain, like polygon-pos
+pub struct Coingecko {
+    platform: String,
+    native_id: String,
+}
+
+impl Default for Coingecko {
+    fn default() -> Coingecko {
+        Coingecko::new(&Chain::default())
+    }
+}
 
 #[async_trait]
-impl price_provider::PriceProvider for Coingecko {
+impl PriceProvider for Coingecko {
+    fn get_name(&self) -> &str {
+        "coingecko"
+    }
+
     async fn fetch<'a>(&self, url: &'a str, verbose: bool) -> Result<Value, Box<dyn error::Error>> {
         let mut retry: u32 = 0;
         let max_retries: u32 = 5;
@@ -46,9 +62,13 @@ impl price_provider::PriceProvider for Coingecko {
         contract_address: &'a str,
         verbose: bool,
     ) -> Result<String, Box<dyn error::Error>> {
+        if contract_address.eq_ignore_ascii_case(price_provider::ETH_ADDRESS) {
+            return Ok(self.native_id.to_string());
+        }
+
         let url = format!(
-            "https://api.coingecko.com/api/v3/coins/ethereum/contract/{}",
-            contract_address
+            "https://api.coingecko.com/api/v3/coins/{}/contract/{}",
+            self.platform, contract_address
         );
         let json = self.fetch(&url, verbose).await?;
 
@@ -78,16 +98,124 @@ impl price_provider::PriceProvider for Coingecko {
 
         Ok(value.as_f64().ok_or(0.0).unwrap_or(0.0))
     }
+
+    async fn get_historical_token_price<'a>(
+        &self,
+        token_id: &'a str,
+        versus_name: &'a str,
+        historical: &'a Historical,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let url = format!(
+            "https://api.coingecko.com/api/v3/coins/{}/history?date={}&localization=false",
+            token_id,
+            historical.get_date()
+        );
+        let json = self.fetch(&url, verbose).await?;
+
+        let selector = format!(r#""market_data"."current_price"."{}""#, versus_name);
+        let mix_selector = selector.as_str();
+
+        let value: Value = jql::walker(&json, mix_selector)?;
+
+        value.as_f64().ok_or_else(|| {
+            format!(
+                "Could not fetch {} price of {} on {}",
+                versus_name,
+                token_id,
+                historical.get_date()
+            )
+            .into()
+        })
+    }
+
+    fn get_token_link(&self, token_id: &str) -> Option<String> {
+        Some(format!("https://coingecko.com/en/coins/{}", token_id))
+    }
+
+    // vs_currencies accepts any fiat currency
+    fn supports_versus(&self, _versus_name: &str) -> bool {
+        true
+    }
+}
+
+impl Coingecko {
+    pub fn new(chain: &Chain) -> Coingecko {
+        Coingecko {
+            platform: chain.coingecko_platform.to_string(),
+            native_id: chain.native_coingecko_id.to_string(),
+        }
+    }
+
+    // Daily (timestamp, price) points of the last days, oldest first
+    pub async fn get_daily_prices(
+        &self,
+        token_id: &str,
+        versus_name: &str,
+        days: u32,
+        verbose: bool,
+    ) -> Result<Vec<(i64, f64)>, Box<dyn error::Error>> {
+        let url = format!(
+            "https://api.coingecko.com/api/v3/coins/{}/market_chart?vs_currency={}&days={}&interval=daily",
+            token_id, versus_name, days
+        );
+        let json = self.fetch(&url, verbose).await?;
+
+        let mix_selector = r#""prices""#;
+
+        let value: Value = jql::walker(&json, mix_selector)?;
+
+        let prices = value
+            .as_array()
+            .ok_or_else(|| format!("Could not fetch daily prices of {}", token_id))?
+            .iter()
+            .filter_map(|point| {
+                let timestamp = point.get(0)?.as_i64()? / 1000;
+                let price = point.get(1)?.as_f64()?;
+                Some((timestamp, price))
+            })
+            .collect();
+
+        Ok(prices)
+    }
+
+    // Floor price of the NFT collection at a contract address
+    pub async fn get_nft_floor_price(
+        &self,
+        contract_address: &str,
+        versus_name: &str,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let url = format!(
+            "https://api.coingecko.com/api/v3/nfts/{}/contract/{}",
+            self.platform, contract_address
+        );
+        let json = self.fetch(&url, verbose).await?;
+
+        let selector = format!(r#""floor_price"."{}""#, versus_name);
+        let mix_selector = selector.as_str();
+
+        let value: Value = jql::walker(&json, mix_selector)?;
+
+        value.as_f64().ok_or_else(|| {
+            format!(
+                "Could not fetch {} floor price of {}",
+                versus_name, contract_address
+            )
+            .into()
+        })
+    }
 }
 
 #[cfg(test)]
 mod test {
     use crate::lib::coingecko::Coingecko;
+    use crate::lib::historical::Historical;
     use crate::lib::price_provider::PriceProvider;
 
     #[tokio::test]
     async fn fetch_success() {
-        let coingecko = Coingecko;
+        let coingecko = Coingecko::default();
 
         // YFI token address
         let erc20_contract_address = "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e";
@@ -103,7 +231,7 @@ mod test {
 
     #[tokio::test]
     async fn fetch_non_existent_token_fail() {
-        let coingecko = Coingecko;
+        let coingecko = Coingecko::default();
 
         // non existent token address
         let erc20_contract_address = "0x0121212121212121212121212212121212121212";
@@ -122,7 +250,7 @@ mod test {
 
     #[tokio::test]
     async fn get_token_id_success() {
-        let coingecko = Coingecko;
+        let coingecko = Coingecko::default();
 
         // YFI token address
         let erc20_contract_address = "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e";
@@ -135,7 +263,7 @@ mod test {
 
     #[tokio::test]
     async fn get_token_id_fail() {
-        let coingecko = Coingecko;
+        let coingecko = Coingecko::default();
         // non existent token address
         let erc20_contract_address = "0x0121212121212121212121212212121212121212";
         let result = coingecko
@@ -151,7 +279,7 @@ mod test {
 
     #[tokio::test]
     async fn get_token_price_success() {
-        let coingecko = Coingecko;
+        let coingecko = Coingecko::default();
         let erc20_token_id = "yearn-finance";
         let price = coingecko
             .get_token_price(erc20_token_id, "usd", true)
@@ -165,9 +293,45 @@ mod test {
         assert_ne!(price_eth, 0.0);
     }
 
+    #[tokio::test]
+    async fn get_historical_token_price_success() {
+        let coingecko = Coingecko::default();
+        let historical = Historical {
+            block: 11766000,
+            timestamp: 1612137599,
+        };
+        let price = coingecko
+            .get_historical_token_price("yearn-finance", "usd", &historical, true)
+            .await
+            .unwrap();
+        assert_ne!(price, 0.0);
+    }
+
+    #[tokio::test]
+    async fn get_daily_prices_success() {
+        let coingecko = Coingecko::default();
+        let prices = coingecko
+            .get_daily_prices("yearn-finance", "usd", 7, true)
+            .await
+            .unwrap();
+        assert!(prices.len() >= 7);
+    }
+
+    #[tokio::test]
+    async fn get_nft_floor_price_success() {
+        let coingecko = Coingecko::default();
+
+        // Bored Ape Yacht Club
+        let price = coingecko
+            .get_nft_floor_price("0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d", "usd", true)
+            .await
+            .unwrap();
+        assert_ne!(price, 0.0);
+    }
+
     #[tokio::test]
     async fn get_token_price_fail() {
-        let coingecko = Coingecko;
+        let coingecko = Coingecko::default();
         let result = coingecko
             .get_token_price("nonexistingtoken", "usd", true)
             .await;
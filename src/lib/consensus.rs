@@ -0,0 +1,146 @@
+use std::cmp::Ordering::Equal;
+
+// Maximum deviation from the median, in percent, before a source is flagged
+pub const DEFAULT_MAX_DEVIATION: f64 = 5.0;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Consensus {
+    pub price: f64,
+    pub spread: Option<f64>,
+    pub sources: Vec<String>,
+    pub outliers: Vec<String>,
+}
+
+impl Consensus {
+    pub fn single(source: &str, price: f64) -> Consensus {
+        Consensus {
+            price,
+            spread: None,
+            sources: vec![source.to_string()],
+            outliers: vec![],
+        }
+    }
+
+    pub fn get_source(&self) -> String {
+        match (self.sources.is_empty(), self.outliers.is_empty()) {
+            (_, true) => self.sources.join(", "),
+            (true, false) => format!("no consensus; outlier: {}", self.outliers.join(", ")),
+            (false, false) => format!(
+                "{}; outlier: {}",
+                self.sources.join(", "),
+                self.outliers.join(", ")
+            ),
+        }
+    }
+}
+
+// Median of the prices within the maximum deviation from the median of all prices, the plain
+// median with every source flagged as an outlier when they all disagree with it
+pub fn get_consensus(quotes: &[(String, f64)], max_deviation: f64) -> Option<Consensus> {
+    let quotes: Vec<&(String, f64)> = quotes.iter().filter(|(_, price)| *price > 0.0).collect();
+    let prices: Vec<f64> = quotes.iter().map(|(_, price)| *price).collect();
+
+    let median = get_median(&prices)?;
+    let spread = get_spread(&prices, median);
+
+    let mut sources = vec![];
+    let mut outliers = vec![];
+    let mut inlier_prices = vec![];
+
+    for (source, price) in quotes {
+        match (price - median).abs() / median * 100.0 > max_deviation {
+            true => outliers.push(source.to_string()),
+            false => {
+                sources.push(source.to_string());
+                inlier_prices.push(*price);
+            }
+        }
+    }
+
+    Some(Consensus {
+        price: get_median(&inlier_prices).unwrap_or(median),
+        spread: Some(spread),
+        sources,
+        outliers,
+    })
+}
+
+fn get_median(prices: &[f64]) -> Option<f64> {
+    let mut prices = prices.to_vec();
+    prices.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Equal));
+
+    let middle = prices.len() / 2;
+    match prices.len() {
+        0 => None,
+        length if length % 2 == 0 => Some((prices[middle - 1] + prices[middle]) / 2.0),
+        _ => Some(prices[middle]),
+    }
+}
+
+// Gap between the highest and lowest price, in percent of the median
+fn get_spread(prices: &[f64], median: f64) -> f64 {
+    let highest = prices.iter().cloned().fold(f64::MIN, f64::max);
+    let lowest = prices.iter().cloned().fold(f64::MAX, f64::min);
+
+    (highest - lowest) / median * 100.0
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn get_consensus_success() {
+        let quotes = vec![
+            ("coingecko".to_string(), 100.0),
+            ("paraswap".to_string(), 150.0),
+            ("defillama".to_string(), 101.0),
+        ];
+
+        let consensus = get_consensus(&quotes, DEFAULT_MAX_DEVIATION).unwrap();
+        assert_eq!(consensus.price, 100.5);
+        assert_eq!(consensus.spread, Some(50.0 / 101.0 * 100.0));
+        assert_eq!(consensus.sources, vec!["coingecko", "defillama"]);
+        assert_eq!(consensus.outliers, vec!["paraswap"]);
+        assert_eq!(
+            consensus.get_source(),
+            "coingecko, defillama; outlier: paraswap"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_consensus_even_success() {
+        let quotes = vec![
+            ("coingecko".to_string(), 100.0),
+            ("paraswap".to_string(), 102.0),
+        ];
+
+        let consensus = get_consensus(&quotes, DEFAULT_MAX_DEVIATION).unwrap();
+        assert_eq!(consensus.price, 101.0);
+        assert!(consensus.outliers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn get_consensus_fail() {
+        let quotes = vec![("paraswap".to_string(), 0.0)];
+        assert_eq!(get_consensus(&quotes, DEFAULT_MAX_DEVIATION), None);
+    }
+
+    #[tokio::test]
+    async fn get_consensus_disagreeing_success() {
+        // Both sources are a third away from their median
+        let quotes = vec![
+            ("coingecko".to_string(), 100.0),
+            ("paraswap".to_string(), 200.0),
+        ];
+
+        let consensus = get_consensus(&quotes, DEFAULT_MAX_DEVIATION).unwrap();
+        assert_eq!(consensus.price, 150.0);
+        assert!(consensus.sources.is_empty());
+        assert_eq!(consensus.outliers, vec!["coingecko", "paraswap"]);
+        assert_eq!(
+            consensus.get_source(),
+            "no consensus; outlier: coingecko, paraswap"
+        );
+    }
+}
@@ -0,0 +1,328 @@
+use super::history::{Direction, Kind, Transfer};
+use std::cmp::Ordering::Equal;
+use std::collections::HashMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Dust left by float rounding when a lot is used up
+const EPSILON: f64 = 1e-12;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Method {
+    #[default]
+    Fifo,
+    Lifo,
+    Hifo,
+}
+
+// Tokens acquired in a single incoming transfer
+#[derive(Debug, Clone, PartialEq)]
+pub struct Lot {
+    pub timestamp: i64,
+    pub amount: f64,
+    pub usd_price: Option<f64>,
+}
+
+// Part of an outgoing transfer matched against a single lot,
+// without an acquisition date or cost when no lot was left to match
+#[derive(Debug, Clone, PartialEq)]
+pub struct Disposal {
+    pub hash: String,
+    pub acquired: Option<i64>,
+    pub disposed: i64,
+    pub amount: f64,
+    pub proceeds: Option<f64>,
+    pub cost: Option<f64>,
+    // Spent on gas rather than sold
+    pub fee: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct CostBasis {
+    pub symbol: String,
+    pub lots: Vec<Lot>,
+    pub disposals: Vec<Disposal>,
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Method::Fifo => write!(f, "fifo"),
+            Method::Lifo => write!(f, "lifo"),
+            Method::Hifo => write!(f, "hifo"),
+        }
+    }
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(method: &str) -> Result<Method, String> {
+        match method.to_lowercase().as_str() {
+            "fifo" => Ok(Method::Fifo),
+            "lifo" => Ok(Method::Lifo),
+            "hifo" => Ok(Method::Hifo),
+            _ => Err(format!(
+                "Unknown cost basis method {}, expected fifo, lifo or hifo",
+                method
+            )),
+        }
+    }
+}
+
+impl Method {
+    // Index of the lot the next disposal is taken from
+    fn pick(&self, lots: &[Lot]) -> Option<usize> {
+        match self {
+            Method::Fifo => (!lots.is_empty()).then_some(0),
+            Method::Lifo => lots.len().checked_sub(1),
+            Method::Hifo => lots
+                .iter()
+                .enumerate()
+                // Unpriced lots come last
+                .max_by(|(_, a), (_, b)| a.usd_price.partial_cmp(&b.usd_price).unwrap_or(Equal))
+                .map(|(index, _)| index),
+        }
+    }
+}
+
+impl Disposal {
+    // Unknown when either the proceeds or the cost is missing
+    pub fn get_gain(&self) -> Option<f64> {
+        Some(self.proceeds? - self.cost?)
+    }
+}
+
+impl CostBasis {
+    // Amount of the tokens still held
+    pub fn get_amount(&self) -> f64 {
+        self.lots.iter().map(|lot| lot.amount).sum()
+    }
+
+    // Cost of the tokens still held, unknown when a lot was acquired without a price
+    pub fn get_cost(&self) -> Option<f64> {
+        self.lots
+            .iter()
+            .map(|lot| lot.usd_price.map(|usd_price| lot.amount * usd_price))
+            .sum()
+    }
+
+    // Gains of the disposals with a known proceeds and cost
+    pub fn get_realized_pnl(&self) -> f64 {
+        self.disposals.iter().filter_map(Disposal::get_gain).sum()
+    }
+
+    // Disposals left out of the realized PnL
+    pub fn get_missing_basis_count(&self) -> usize {
+        self.disposals
+            .iter()
+            .filter(|disposal| disposal.get_gain().is_none())
+            .count()
+    }
+
+    fn acquire(&mut self, transfer: &Transfer) {
+        self.lots.push(Lot {
+            timestamp: transfer.timestamp,
+            amount: transfer.amount,
+            usd_price: transfer
+                .usd_value
+                .map(|usd_value| usd_value / transfer.amount),
+        });
+    }
+
+    fn dispose(&mut self, transfer: &Transfer, method: Method) {
+        let mut remaining = transfer.amount;
+
+        while remaining > EPSILON {
+            let index = match method.pick(&self.lots) {
+                Some(index) => index,
+                None => break,
+            };
+            let lot = &mut self.lots[index];
+            let amount = lot.amount.min(remaining);
+
+            self.disposals.push(get_disposal(
+                transfer,
+                Some(lot.timestamp),
+                amount,
+                lot.usd_price,
+            ));
+
+            lot.amount -= amount;
+            if lot.amount <= EPSILON {
+                self.lots.remove(index);
+            }
+            remaining -= amount;
+        }
+
+        // Tokens sent without a known acquisition, e.g. gas or untracked mints, have no cost basis
+        if remaining > EPSILON {
+            self.disposals
+                .push(get_disposal(transfer, None, remaining, None));
+        }
+    }
+}
+
+// Lots and disposals of every token by contract address, transfers need to be sorted oldest first
+pub fn compute(transfers: &[Transfer], method: Method) -> HashMap<String, CostBasis> {
+    let mut cost_bases: HashMap<String, CostBasis> = HashMap::new();
+
+    for transfer in transfers {
+        let cost_basis = cost_bases
+            .entry(transfer.contract_address.to_lowercase())
+            .or_insert_with(|| CostBasis {
+                symbol: transfer.symbol.to_string(),
+                ..Default::default()
+            });
+
+        match transfer.direction {
+            Direction::In => cost_basis.acquire(transfer),
+            Direction::Out => cost_basis.dispose(transfer, method),
+        }
+    }
+
+    cost_bases
+}
+
+fn get_disposal(
+    transfer: &Transfer,
+    acquired: Option<i64>,
+    amount: f64,
+    usd_price: Option<f64>,
+) -> Disposal {
+    let cost = usd_price.map(|usd_price| amount * usd_price);
+    let proceeds = transfer
+        .usd_value
+        .map(|usd_value| usd_value * amount / transfer.amount);
+
+    Disposal {
+        hash: transfer.hash.to_string(),
+        acquired,
+        disposed: transfer.timestamp,
+        amount,
+        proceeds,
+        cost,
+        fee: transfer.kind == Kind::Fee,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_transfer(direction: Direction, timestamp: i64, amount: f64, usd_price: f64) -> Transfer {
+        Transfer {
+            hash: "0x1".to_string(),
+            block: 18908894,
+            timestamp,
+            kind: Kind::Erc20,
+            direction,
+            counterparty: "0x0000000000000000000000000000000000000001".to_string(),
+            symbol: "YFI".to_string(),
+            contract_address: "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e".to_string(),
+            amount,
+            usd_value: Some(amount * usd_price),
+        }
+    }
+
+    fn get_transfers() -> Vec<Transfer> {
+        vec![
+            get_transfer(Direction::In, 1, 1.0, 100.0),
+            get_transfer(Direction::In, 2, 1.0, 300.0),
+            get_transfer(Direction::In, 3, 1.0, 200.0),
+            get_transfer(Direction::Out, 4, 1.5, 400.0),
+        ]
+    }
+
+    fn get_cost_basis(method: Method) -> CostBasis {
+        compute(&get_transfers(), method)
+            .remove("0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e")
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn compute_fifo_success() {
+        let cost_basis = get_cost_basis(Method::Fifo);
+        assert_eq!(cost_basis.symbol, "YFI");
+        assert_eq!(cost_basis.disposals.len(), 2);
+        assert_eq!(cost_basis.get_realized_pnl(), 600.0 - 250.0);
+        assert_eq!(cost_basis.get_cost(), Some(150.0 + 200.0));
+        assert_eq!(cost_basis.get_amount(), 1.5);
+    }
+
+    #[tokio::test]
+    async fn compute_lifo_success() {
+        let cost_basis = get_cost_basis(Method::Lifo);
+        assert_eq!(cost_basis.get_realized_pnl(), 600.0 - 350.0);
+        assert_eq!(cost_basis.get_cost(), Some(100.0 + 150.0));
+    }
+
+    #[tokio::test]
+    async fn compute_hifo_success() {
+        let cost_basis = get_cost_basis(Method::Hifo);
+        assert_eq!(cost_basis.disposals[0].acquired, Some(2));
+        assert_eq!(cost_basis.get_realized_pnl(), 600.0 - 400.0);
+        assert_eq!(cost_basis.get_cost(), Some(100.0 + 100.0));
+    }
+
+    #[tokio::test]
+    async fn compute_unknown_acquisition_success() {
+        let transfers = vec![
+            get_transfer(Direction::In, 1, 1.0, 100.0),
+            get_transfer(Direction::Out, 2, 2.0, 150.0),
+        ];
+
+        let cost_basis = compute(&transfers, Method::Fifo)
+            .remove("0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e")
+            .unwrap();
+        assert!(cost_basis.lots.is_empty());
+        assert_eq!(cost_basis.disposals.len(), 2);
+        assert_eq!(cost_basis.disposals[1].acquired, None);
+        assert_eq!(cost_basis.disposals[1].cost, None);
+        assert_eq!(cost_basis.get_realized_pnl(), 50.0);
+        assert_eq!(cost_basis.get_missing_basis_count(), 1);
+    }
+
+    #[tokio::test]
+    async fn compute_unpriced_success() {
+        let mut unpriced = get_transfer(Direction::In, 2, 1.0, 0.0);
+        unpriced.usd_value = None;
+        let transfers = vec![
+            get_transfer(Direction::In, 1, 1.0, 100.0),
+            unpriced,
+            get_transfer(Direction::Out, 3, 1.5, 200.0),
+        ];
+
+        let cost_basis = compute(&transfers, Method::Hifo)
+            .remove("0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e")
+            .unwrap();
+        assert_eq!(cost_basis.disposals[0].acquired, Some(1));
+        assert_eq!(cost_basis.disposals[1].cost, None);
+        assert_eq!(cost_basis.get_realized_pnl(), 100.0);
+        assert_eq!(cost_basis.get_missing_basis_count(), 1);
+        assert_eq!(cost_basis.get_cost(), None);
+        assert_eq!(cost_basis.get_amount(), 0.5);
+    }
+
+    #[tokio::test]
+    async fn compute_fee_success() {
+        let mut fee = get_transfer(Direction::Out, 2, 0.5, 200.0);
+        fee.kind = Kind::Fee;
+        let transfers = vec![get_transfer(Direction::In, 1, 1.0, 100.0), fee];
+
+        let cost_basis = compute(&transfers, Method::Fifo)
+            .remove("0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e")
+            .unwrap();
+        assert!(cost_basis.disposals[0].fee);
+        assert_eq!(cost_basis.get_amount(), 0.5);
+    }
+
+    #[tokio::test]
+    async fn method_from_str_fail() {
+        let result = "avco".parse::<Method>();
+        assert_eq!(
+            result,
+            Err("Unknown cost basis method avco, expected fifo, lifo or hifo".to_string())
+        );
+    }
+}
@@ -0,0 +1,340 @@
+use super::price_provider::{normalize_contract_address, PriceProvider};
+use async_trait::async_trait;
+use config::Config;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error;
+use std::io;
+use std::thread::sleep;
+use std::time::Duration;
+
+// Price provider declared in a [pricing.custom.<name>] section of Settings.toml:
+//
+// [pricing.custom.<name>]
+// url = "https://api.example.com/price?token={contract}&vs={vs}"
+// selector = '"data"."price"'
+// scale = 1.0
+// headers = { "X-Api-Key" = "<api-key>" }
+// ids = { "<token-contract-address>" = "<token-id>" }
+// versus = { usd = "USD", eth = "ETH" }
+//
+// {contract}, {id} and {vs} are replaced in both url and selector
+#[derive(Debug, Clone)]
+pub struct Custom {
+    pub name: String,
+    pub url: String,
+    pub selector: String,
+    pub scale: f64,
+    pub headers: Vec<(String, String)>,
+    pub ids: HashMap<String, String>,
+    pub versus: HashMap<String, String>,
+}
+
+#[async_trait]
+impl PriceProvider for Custom {
+    fn get_name(&self) -> &str {
+        &self.name
+    }
+
+    async fn fetch<'a>(&self, url: &'a str, verbose: bool) -> Result<Value, Box<dyn error::Error>> {
+        let mut retry: u32 = 0;
+        let max_retries: u32 = 5;
+        let client = reqwest::Client::new();
+
+        loop {
+            let mut request = client.get(url);
+            for (name, value) in &self.headers {
+                request = request.header(name, value);
+            }
+
+            let body = request.send().await?.text().await?;
+            let result = serde_json::from_str(&body);
+
+            match result {
+                Ok(json) => return Ok(json),
+                _ => {
+                    if retry > max_retries {
+                        return Err(Box::new(io::Error::new(
+                            io::ErrorKind::ConnectionRefused,
+                            format!(
+                                "Could not fetch from {}: response body: {:?}",
+                                self.name, &body
+                            ),
+                        )));
+                    } else {
+                        retry += 1;
+                        if verbose {
+                            println!(
+                                "Failed to fetch from {}, retry up to {}, retry number: {}",
+                                self.name, max_retries, retry
+                            );
+                        }
+                        sleep(Duration::from_millis((2_u32.pow(retry) * 1000).into()));
+                    }
+                }
+            }
+        }
+    }
+
+    async fn get_token_id_from_contract_address<'a>(
+        &self,
+        contract_address: &'a str,
+        _verbose: bool,
+    ) -> Result<String, Box<dyn error::Error>> {
+        normalize_contract_address(contract_address)
+    }
+
+    async fn get_token_price<'a>(
+        &self,
+        token_id: &'a str,
+        versus_name: &'a str,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let url = self.render(&self.url, token_id, versus_name);
+        let json = self.fetch(&url, verbose).await?;
+
+        let selector = self.render(&self.selector, token_id, versus_name);
+        let value: Value = jql::walker(&json, &selector)?;
+
+        let price = match &value {
+            Value::Number(number) => number.as_f64(),
+            Value::String(string) => string.parse::<f64>().ok(),
+            _ => None,
+        };
+
+        match price {
+            Some(price) if price > 0.0 => Ok(price * self.scale),
+            _ => Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Could not read {} price for {} versus {}: {}",
+                    self.name, token_id, versus_name, value
+                ),
+            ))),
+        }
+    }
+
+    // Without {vs} in the url or selector, the price is assumed to be in usd
+    fn supports_versus(&self, versus_name: &str) -> bool {
+        self.url.contains("{vs}")
+            || self.selector.contains("{vs}")
+            || self.versus.contains_key(versus_name)
+            || versus_name == "usd"
+    }
+}
+
+impl Custom {
+    fn render(&self, template: &str, contract_address: &str, versus_name: &str) -> String {
+        let id = self
+            .ids
+            .get(contract_address)
+            .map(String::as_str)
+            .unwrap_or(contract_address);
+        let versus = self
+            .versus
+            .get(versus_name)
+            .map(String::as_str)
+            .unwrap_or(versus_name);
+
+        template
+            .replace("{contract}", contract_address)
+            .replace("{id}", id)
+            .replace("{vs}", versus)
+    }
+}
+
+pub fn load_custom_providers(settings: &Config) -> Result<Vec<Custom>, Box<dyn error::Error>> {
+    let mut custom_providers = vec![];
+
+    let table = match settings.get_table("pricing.custom") {
+        Ok(table) => table,
+        Err(_) => return Ok(custom_providers),
+    };
+
+    for (name, value) in table {
+        let definition = value.into_table()?;
+
+        let get_string = |key: &str| -> Result<String, Box<dyn error::Error>> {
+            match definition.get(key) {
+                Some(value) => Ok(value.clone().into_string()?),
+                None => Err(Box::new(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Missing {} in custom price provider {}", key, name),
+                ))),
+            }
+        };
+        let get_strings = |key: &str| -> Result<Vec<(String, String)>, Box<dyn error::Error>> {
+            let mut strings = vec![];
+            if let Some(value) = definition.get(key) {
+                for (key, value) in value.clone().into_table()? {
+                    strings.push((key, value.into_string()?));
+                }
+            }
+            Ok(strings)
+        };
+
+        let scale = match definition.get("scale") {
+            Some(scale) => scale.clone().into_float()?,
+            None => 1.0,
+        };
+
+        let mut ids = HashMap::new();
+        for (contract_address, id) in get_strings("ids")? {
+            ids.insert(normalize_contract_address(&contract_address)?, id);
+        }
+
+        custom_providers.push(Custom {
+            name: name.to_lowercase(),
+            url: get_string("url")?,
+            selector: get_string("selector")?,
+            scale,
+            headers: get_strings("headers")?,
+            ids,
+            versus: get_strings("versus")?.into_iter().collect(),
+        });
+    }
+
+    custom_providers.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(custom_providers)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+
+    fn get_settings(toml: &str) -> Config {
+        config::Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn load_custom_providers_success() {
+        let settings = get_settings(
+            r#"
+            [pricing.custom.llama]
+            url = "https://coins.llama.fi/prices/current/ethereum:{contract}"
+            selector = '"coins"."ethereum:{contract}"."price"'
+            scale = 0.5
+            headers = { "Accept" = "application/json" }
+            ids = { "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e" = "yearn-finance" }
+            "#,
+        );
+
+        let custom_providers = load_custom_providers(&settings).unwrap();
+        assert_eq!(custom_providers.len(), 1);
+
+        let custom = &custom_providers[0];
+        assert_eq!(custom.get_name(), "llama");
+        assert_eq!(custom.scale, 0.5);
+        assert_eq!(
+            custom.headers,
+            vec![("accept".to_string(), "application/json".to_string())]
+        );
+        assert_eq!(
+            custom
+                .ids
+                .get("0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e")
+                .unwrap(),
+            "yearn-finance"
+        );
+    }
+
+    #[tokio::test]
+    async fn load_custom_providers_missing_url_fail() {
+        let settings = get_settings(
+            r#"
+            [pricing.custom.llama]
+            selector = '"price"'
+            "#,
+        );
+
+        let result = load_custom_providers(&settings);
+        if let Result::Err(err) = result {
+            assert_eq!(
+                (*err).to_string(),
+                "Missing url in custom price provider llama"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn render_success() {
+        let mut custom = Custom {
+            name: "example".to_string(),
+            url: "https://example.com/{id}?vs={vs}&address={contract}".to_string(),
+            selector: r#""{id}"."{vs}""#.to_string(),
+            scale: 1.0,
+            headers: vec![],
+            ids: HashMap::new(),
+            versus: HashMap::new(),
+        };
+        custom.ids.insert(
+            "0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e".to_string(),
+            "yearn-finance".to_string(),
+        );
+        custom.versus.insert("usd".to_string(), "USD".to_string());
+
+        assert_eq!(
+            custom.render(&custom.url, "0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e", "usd"),
+            "https://example.com/yearn-finance?vs=USD&address=0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e"
+        );
+        assert_eq!(
+            custom.render(
+                &custom.selector,
+                "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9",
+                "eth"
+            ),
+            r#""0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9"."eth""#
+        );
+    }
+
+    #[tokio::test]
+    async fn supports_versus_success() {
+        let mut custom = Custom {
+            name: "example".to_string(),
+            url: "https://example.com/{id}".to_string(),
+            selector: r#""{id}"."price""#.to_string(),
+            scale: 1.0,
+            headers: vec![],
+            ids: HashMap::new(),
+            versus: HashMap::new(),
+        };
+        assert!(custom.supports_versus("usd"));
+        assert!(!custom.supports_versus("eth"));
+
+        custom.versus.insert("eth".to_string(), "ETH".to_string());
+        assert!(custom.supports_versus("eth"));
+        assert!(!custom.supports_versus("btc"));
+
+        custom.selector = r#""{id}"."{vs}""#.to_string();
+        assert!(custom.supports_versus("btc"));
+    }
+
+    #[tokio::test]
+    async fn get_token_price_success() {
+        let settings = get_settings(
+            r#"
+            [pricing.custom.llama]
+            url = "https://coins.llama.fi/prices/current/ethereum:{contract}"
+            selector = '"coins"."ethereum:{contract}"."price"'
+            "#,
+        );
+        let custom = load_custom_providers(&settings).unwrap().remove(0);
+
+        // AAVE token address
+        let contract_address = "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9";
+        let token_id = custom
+            .get_token_id_from_contract_address(contract_address, false)
+            .await
+            .unwrap();
+        let price = custom
+            .get_token_price(&token_id, "usd", true)
+            .await
+            .unwrap();
+        assert_ne!(price, 0.0);
+    }
+}
@@ -0,0 +1,284 @@
+use super::chain::Chain;
+use super::price_provider::{normalize_contract_address, PriceProvider, ETH_ADDRESS};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error;
+use std::io;
+use std::sync::Mutex;
+use std::thread::sleep;
+use std::time::Duration;
+
+const ETH_COIN_ID: &str = "coingecko:ethereum";
+const COINS_PER_REQUEST: usize = 50;
+
+pub const DEFAULT_MIN_CONFIDENCE: f64 = 0.8;
+
+pub struct DefiLlama {
+    min_confidence: f64,
+    chain: String,
+    native_coin_id: String,
+    coins: Mutex<HashMap<String, Coin>>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Coin {
+    pub price: f64,
+    pub confidence: Option<f64>,
+}
+
+#[async_trait]
+impl PriceProvider for DefiLlama {
+    fn get_name(&self) -> &str {
+        "defillama"
+    }
+
+    async fn fetch<'a>(&self, url: &'a str, verbose: bool) -> Result<Value, Box<dyn error::Error>> {
+        let mut retry: u32 = 0;
+        let max_retries: u32 = 5;
+
+        loop {
+            let body = reqwest::get(url).await?.text().await?;
+            let result = serde_json::from_str(&body);
+
+            match result {
+                Ok(json) => return Ok(json),
+                _ => {
+                    if retry > max_retries {
+                        return Err(Box::new(io::Error::new(
+                            io::ErrorKind::ConnectionRefused,
+                            format!("Could not fetch from defillama: response body: {:?}", &body),
+                        )));
+                    } else {
+                        retry += 1;
+                        if verbose {
+                            println!(
+                                "Failed to fetch from defillama, retry up to {}, retry number: {}",
+                                max_retries, retry
+                            );
+                        }
+                        sleep(Duration::from_millis((2_u32.pow(retry) * 1000).into()));
+                    }
+                }
+            }
+        }
+    }
+
+    async fn get_token_id_from_contract_address<'a>(
+        &self,
+        contract_address: &'a str,
+        _verbose: bool,
+    ) -> Result<String, Box<dyn error::Error>> {
+        normalize_contract_address(contract_address)
+    }
+
+    async fn get_token_price<'a>(
+        &self,
+        token_id: &'a str,
+        versus_name: &'a str,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let coin = self.get_coin(&self.get_coin_id(token_id), verbose).await?;
+
+        match versus_name {
+            "usd" => Ok(coin.price),
+            "eth" => {
+                let eth_coin = self.get_coin(ETH_COIN_ID, verbose).await?;
+                Ok(coin.price / eth_coin.price)
+            }
+            _ => Err(Box::new(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("Could not fetch token price versus {}", versus_name),
+            ))),
+        }
+    }
+
+    async fn prefetch<'a>(
+        &self,
+        contract_addresses: &'a [String],
+        verbose: bool,
+    ) -> Result<(), Box<dyn error::Error>> {
+        let mut coin_ids = vec![ETH_COIN_ID.to_string(), self.native_coin_id.to_string()];
+        for contract_address in contract_addresses {
+            match normalize_contract_address(contract_address) {
+                Ok(contract_address) => coin_ids.push(self.get_coin_id(&contract_address)),
+                Err(err) => {
+                    if verbose {
+                        println!(
+                            "Skipping {} in the defillama prefetch: {}",
+                            contract_address, err
+                        );
+                    }
+                }
+            }
+        }
+        coin_ids.sort();
+        coin_ids.dedup();
+
+        self.get_coins(&coin_ids, verbose).await?;
+
+        Ok(())
+    }
+}
+
+impl DefiLlama {
+    pub fn new(min_confidence: f64, chain: &Chain) -> DefiLlama {
+        DefiLlama {
+            min_confidence,
+            chain: chain.name.to_string(),
+            native_coin_id: format!("coingecko:{}", chain.native_coingecko_id),
+            coins: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn get_coins(
+        &self,
+        coin_ids: &[String],
+        verbose: bool,
+    ) -> Result<HashMap<String, Coin>, Box<dyn error::Error>> {
+        let mut coins = HashMap::new();
+
+        for chunk in coin_ids.chunks(COINS_PER_REQUEST) {
+            let url = format!("https://coins.llama.fi/prices/current/{}", chunk.join(","));
+            let json = self.fetch(&url, verbose).await?;
+
+            let mix_selector = r#""coins""#;
+
+            let value: Value = jql::walker(&json, mix_selector)?;
+
+            if let Value::Object(entries) = value {
+                for (coin_id, entry) in entries {
+                    let price = match entry.get("price").and_then(Value::as_f64) {
+                        Some(price) => price,
+                        None => continue,
+                    };
+                    let confidence = entry.get("confidence").and_then(Value::as_f64);
+
+                    coins.insert(coin_id.to_lowercase(), Coin { price, confidence });
+                }
+            }
+        }
+
+        if let Ok(mut cache) = self.coins.lock() {
+            cache.extend(coins.clone());
+        }
+
+        Ok(coins)
+    }
+
+    async fn get_coin(&self, coin_id: &str, verbose: bool) -> Result<Coin, Box<dyn error::Error>> {
+        let cached = match self.coins.lock() {
+            Ok(cache) => cache.get(coin_id).cloned(),
+            Err(_) => None,
+        };
+
+        let coin = match cached {
+            Some(coin) => coin,
+            None => self
+                .get_coins(&[coin_id.to_string()], verbose)
+                .await?
+                .remove(coin_id)
+                .ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        format!("Coin {} not found on defillama", coin_id),
+                    )
+                })?,
+        };
+
+        if let Some(confidence) = coin.confidence {
+            if confidence < self.min_confidence {
+                return Err(Box::new(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "Confidence {} of defillama price for {} is below {}",
+                        confidence, coin_id, self.min_confidence
+                    ),
+                )));
+            }
+        }
+
+        Ok(coin)
+    }
+
+    fn get_coin_id(&self, contract_address: &str) -> String {
+        if contract_address.eq_ignore_ascii_case(ETH_ADDRESS) {
+            return self.native_coin_id.to_string();
+        }
+
+        format!("{}:{}", self.chain, contract_address.to_lowercase())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::lib::chain::Chain;
+    use crate::lib::defillama::{DefiLlama, DEFAULT_MIN_CONFIDENCE};
+    use crate::lib::price_provider::PriceProvider;
+
+    #[tokio::test]
+    async fn get_token_price_success() {
+        let defillama = DefiLlama::new(DEFAULT_MIN_CONFIDENCE, &Chain::default());
+
+        // AAVE token address
+        let contract_address = "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9";
+        let price = defillama
+            .get_token_price(contract_address, "usd", true)
+            .await
+            .unwrap();
+        let price_eth = defillama
+            .get_token_price(contract_address, "eth", true)
+            .await
+            .unwrap();
+        assert_ne!(price, 0.0);
+        assert_ne!(price_eth, 0.0);
+    }
+
+    #[tokio::test]
+    async fn prefetch_success() {
+        let defillama = DefiLlama::new(DEFAULT_MIN_CONFIDENCE, &Chain::default());
+
+        // AAVE and YFI token addresses
+        let contract_addresses = vec![
+            "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9".to_string(),
+            "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e".to_string(),
+        ];
+        defillama
+            .prefetch(&contract_addresses, false)
+            .await
+            .unwrap();
+
+        assert_eq!(defillama.coins.lock().unwrap().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn get_token_price_low_confidence_fail() {
+        let defillama = DefiLlama::new(1.01, &Chain::default());
+
+        // AAVE token address
+        let contract_address = "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9";
+        let result = defillama
+            .get_token_price(contract_address, "usd", false)
+            .await;
+        if let Result::Err(err) = result {
+            assert!((*err).to_string().starts_with("Confidence"));
+        }
+    }
+
+    #[tokio::test]
+    async fn get_token_price_fail() {
+        let defillama = DefiLlama::new(DEFAULT_MIN_CONFIDENCE, &Chain::default());
+
+        // non existent token address
+        let contract_address = "0x0121212121212121212121212212121212121212";
+        let result = defillama
+            .get_token_price(contract_address, "usd", false)
+            .await;
+        if let Result::Err(err) = result {
+            assert_eq!(
+                (*err).to_string(),
+                "Coin ethereum:0x0121212121212121212121212212121212121212 not found on defillama"
+            );
+        }
+    }
+}
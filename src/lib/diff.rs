@@ -0,0 +1,170 @@
+use super::snapshot::{Position, Snapshot};
+use std::cmp::Ordering::Equal;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Change {
+    Added,
+    Removed,
+    #[default]
+    Changed,
+}
+
+// Value change of a token, split so that price_effect + quantity_effect == value delta
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PositionDiff {
+    pub symbol: String,
+    pub contract_address: String,
+    pub change: Change,
+    pub from_balance: f64,
+    pub to_balance: f64,
+    pub from_usd_value: f64,
+    pub to_usd_value: f64,
+    pub price_effect: f64,
+    pub quantity_effect: f64,
+}
+
+impl PositionDiff {
+    fn new(from: Option<&Position>, to: Option<&Position>) -> PositionDiff {
+        let (symbol, contract_address) = match to.or(from) {
+            Some(position) => (&position.symbol, &position.contract_address),
+            None => return PositionDiff::default(),
+        };
+        let from_balance = from.map(|from| from.balance).unwrap_or(0.0);
+        let to_balance = to.map(|to| to.balance).unwrap_or(0.0);
+        let from_price = from.map(|from| from.usd_price);
+        let to_price = to.map(|to| to.usd_price);
+
+        // Quantity change valued at the old price, price change applied to the new quantity
+        let (change, price_effect, quantity_effect) = match (from_price, to_price) {
+            (Some(from_price), Some(to_price)) => (
+                Change::Changed,
+                to_balance * (to_price - from_price),
+                (to_balance - from_balance) * from_price,
+            ),
+            (None, Some(to_price)) => (Change::Added, 0.0, to_balance * to_price),
+            (Some(from_price), None) => (Change::Removed, 0.0, -from_balance * from_price),
+            (None, None) => (Change::Changed, 0.0, 0.0),
+        };
+
+        PositionDiff {
+            symbol: symbol.to_string(),
+            contract_address: contract_address.to_string(),
+            change,
+            from_balance,
+            to_balance,
+            from_usd_value: from.map(Position::get_usd_balance).unwrap_or(0.0),
+            to_usd_value: to.map(Position::get_usd_balance).unwrap_or(0.0),
+            price_effect,
+            quantity_effect,
+        }
+    }
+
+    pub fn get_balance_delta(&self) -> f64 {
+        self.to_balance - self.from_balance
+    }
+
+    pub fn get_usd_delta(&self) -> f64 {
+        self.to_usd_value - self.from_usd_value
+    }
+
+    pub fn get_usd_delta_percent(&self) -> Option<f64> {
+        match self.from_usd_value > 0.0 {
+            true => Some(self.get_usd_delta() / self.from_usd_value * 100.0),
+            false => None,
+        }
+    }
+}
+
+// Largest value changes first, tokens without any change are left out
+pub fn diff_snapshots(from: &Snapshot, to: &Snapshot) -> Vec<PositionDiff> {
+    let mut diffs: Vec<PositionDiff> = from
+        .positions
+        .iter()
+        .map(|from_position| {
+            let to_position = find_position(to, &from_position.contract_address);
+            PositionDiff::new(Some(from_position), to_position)
+        })
+        .collect();
+
+    for to_position in &to.positions {
+        if find_position(from, &to_position.contract_address).is_none() {
+            diffs.push(PositionDiff::new(None, Some(to_position)));
+        }
+    }
+
+    diffs.retain(|diff| diff.get_balance_delta() != 0.0 || diff.get_usd_delta() != 0.0);
+    diffs.sort_by(|a, b| {
+        b.get_usd_delta()
+            .abs()
+            .partial_cmp(&a.get_usd_delta().abs())
+            .unwrap_or(Equal)
+    });
+
+    diffs
+}
+
+fn find_position<'a>(snapshot: &'a Snapshot, contract_address: &str) -> Option<&'a Position> {
+    snapshot.positions.iter().find(|position| {
+        position
+            .contract_address
+            .eq_ignore_ascii_case(contract_address)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_position(symbol: &str, balance: f64, usd_price: f64) -> Position {
+        Position {
+            symbol: symbol.to_string(),
+            contract_address: format!("0x{}", symbol.to_lowercase()),
+            balance,
+            usd_price,
+            eth_price: 0.0,
+            price_source: "coingecko".to_string(),
+        }
+    }
+
+    fn get_snapshot(positions: Vec<Position>) -> Snapshot {
+        Snapshot {
+            id: 1,
+            timestamp: 1704067199,
+            block: 18908894,
+            address: "0x000000000000000000000000000000000000dead".to_string(),
+            positions,
+        }
+    }
+
+    #[tokio::test]
+    async fn diff_snapshots_success() {
+        let from = get_snapshot(vec![
+            get_position("ETH", 2.0, 2000.0),
+            get_position("YFI", 1.0, 8000.0),
+            get_position("DAI", 100.0, 1.0),
+        ]);
+        let to = get_snapshot(vec![
+            get_position("ETH", 3.0, 2500.0),
+            get_position("AAVE", 10.0, 100.0),
+            get_position("DAI", 100.0, 1.0),
+        ]);
+
+        let diffs = diff_snapshots(&from, &to);
+        assert_eq!(diffs.len(), 3);
+
+        assert_eq!(diffs[0].symbol, "YFI");
+        assert_eq!(diffs[0].change, Change::Removed);
+        assert_eq!(diffs[0].quantity_effect, -8000.0);
+
+        assert_eq!(diffs[1].symbol, "ETH");
+        assert_eq!(diffs[1].change, Change::Changed);
+        assert_eq!(diffs[1].get_usd_delta(), 3500.0);
+        assert_eq!(diffs[1].quantity_effect, 2000.0);
+        assert_eq!(diffs[1].price_effect, 1500.0);
+        assert_eq!(diffs[1].get_usd_delta_percent(), Some(87.5));
+
+        assert_eq!(diffs[2].symbol, "AAVE");
+        assert_eq!(diffs[2].change, Change::Added);
+        assert_eq!(diffs[2].get_usd_delta_percent(), None);
+    }
+}
@@ -0,0 +1,319 @@
+use super::coingecko::Coingecko;
+use super::historical::Historical;
+use super::onchain;
+use std::collections::HashMap;
+use std::error;
+use web3::ethabi::{self, ParamType, Token};
+use web3::signing::keccak256;
+use web3::transports::Http;
+use web3::types::{BlockNumber, Filter, FilterBuilder, Log, H160, H256, U256};
+use web3::Web3;
+
+const TRANSFER_SINGLE: &str = "TransferSingle(address,address,address,uint256,uint256)";
+const TRANSFER_BATCH: &str = "TransferBatch(address,address,address,uint256[],uint256[])";
+
+// Balance of a single id of an ERC1155 contract held by the account
+#[derive(Debug, Clone, PartialEq)]
+pub struct MultiToken {
+    pub collection: String,
+    pub contract_address: String,
+    pub token_id: U256,
+    pub balance: U256,
+    pub floor_usd: Option<f64>,
+}
+
+impl MultiToken {
+    pub fn get_usd_value(&self) -> Option<f64> {
+        self.floor_usd
+            .map(|floor_usd| floor_usd * onchain::u256_to_f64(self.balance))
+    }
+}
+
+// Ids ever received by the account, kept when balanceOfBatch still reports a balance
+pub async fn list_erc1155_for_account(
+    web3: &Web3<Http>,
+    account_address: H160,
+    historical: Option<&Historical>,
+    verbose: bool,
+) -> Result<Vec<MultiToken>, Box<dyn error::Error>> {
+    let to_block = match historical {
+        Some(historical) => historical.block,
+        None => web3.eth().block_number().await?.as_u64(),
+    };
+
+    let logs = get_transfer_logs(web3, account_address, to_block, verbose).await?;
+    if verbose {
+        println!("Fetched {} ERC1155 transfer events", logs.len());
+    }
+
+    let block = historical.map(Historical::get_block_id);
+    let mut multi_tokens = vec![];
+
+    for (contract_address, token_ids) in get_received_ids(&logs) {
+        let result = onchain::call(
+            web3,
+            contract_address,
+            "balanceOfBatch(address[],uint256[])",
+            &[
+                Token::Array(vec![Token::Address(account_address); token_ids.len()]),
+                Token::Array(token_ids.iter().copied().map(Token::Uint).collect()),
+            ],
+            &[ParamType::Array(Box::new(ParamType::Uint(256)))],
+            block,
+        )
+        .await;
+        let balances = match result.map(|result| result.into_iter().next()) {
+            Ok(Some(Token::Array(balances))) => balances,
+            _ => {
+                if verbose {
+                    println!("Failed to read ERC1155 balances of {:?}", contract_address);
+                }
+                continue;
+            }
+        };
+
+        // name() is optional in ERC1155
+        let collection = onchain::call(
+            web3,
+            contract_address,
+            "name()",
+            &[],
+            &[ParamType::String],
+            None,
+        )
+        .await
+        .and_then(|result| onchain::get_string(&result, 0))
+        .unwrap_or_else(|_| format!("{:?}", contract_address));
+
+        for (token_id, balance) in token_ids.into_iter().zip(balances) {
+            match balance.into_uint() {
+                Some(balance) if !balance.is_zero() => multi_tokens.push(MultiToken {
+                    collection: collection.to_string(),
+                    contract_address: format!("{:?}", contract_address),
+                    token_id,
+                    balance,
+                    floor_usd: None,
+                }),
+                _ => continue,
+            }
+        }
+    }
+
+    Ok(multi_tokens)
+}
+
+// Floor prices are looked up once per contract, most ERC1155 contracts have none
+pub async fn price_multi_tokens(
+    multi_tokens: &mut [MultiToken],
+    coingecko: &Coingecko,
+    verbose: bool,
+) {
+    let mut floor_prices: HashMap<String, Option<f64>> = HashMap::new();
+
+    for multi_token in multi_tokens {
+        let floor_usd = match floor_prices.get(&multi_token.contract_address) {
+            Some(floor_usd) => *floor_usd,
+            None => {
+                let floor_usd = coingecko
+                    .get_nft_floor_price(&multi_token.contract_address, "usd", verbose)
+                    .await
+                    .ok();
+                floor_prices.insert(multi_token.contract_address.to_string(), floor_usd);
+                floor_usd
+            }
+        };
+
+        multi_token.floor_usd = floor_usd;
+    }
+}
+
+pub fn get_usd_total(multi_tokens: &[MultiToken]) -> f64 {
+    multi_tokens
+        .iter()
+        .filter_map(MultiToken::get_usd_value)
+        .fold(0.0, |total, usd_value| total + usd_value)
+}
+
+// Nodes like Infura reject eth_getLogs over the whole chain when it returns too many
+// events, the block range is then split in halves until each part is accepted
+async fn get_transfer_logs(
+    web3: &Web3<Http>,
+    account_address: H160,
+    to_block: u64,
+    verbose: bool,
+) -> Result<Vec<Log>, Box<dyn error::Error>> {
+    let mut logs = vec![];
+    let mut ranges = vec![(0, to_block)];
+
+    while let Some((from_block, to_block)) = ranges.pop() {
+        match web3
+            .eth()
+            .logs(get_transfer_filter(account_address, from_block, to_block))
+            .await
+        {
+            Ok(range_logs) => logs.extend(range_logs),
+            Err(err) if from_block < to_block => {
+                if verbose {
+                    println!(
+                        "Failed to fetch ERC1155 transfer events of blocks {} to {}, splitting: {}",
+                        from_block, to_block, err
+                    );
+                }
+                let middle = from_block + (to_block - from_block) / 2;
+                ranges.push((middle + 1, to_block));
+                ranges.push((from_block, middle));
+            }
+            Err(err) => return Err(err.into()),
+        }
+    }
+
+    Ok(logs)
+}
+
+fn get_transfer_filter(account_address: H160, from_block: u64, to_block: u64) -> Filter {
+    FilterBuilder::default()
+        .from_block(BlockNumber::Number(from_block.into()))
+        .to_block(BlockNumber::Number(to_block.into()))
+        .topics(
+            Some(vec![
+                H256::from(keccak256(TRANSFER_SINGLE.as_bytes())),
+                H256::from(keccak256(TRANSFER_BATCH.as_bytes())),
+            ]),
+            None,
+            None,
+            Some(vec![H256::from(account_address)]),
+        )
+        .build()
+}
+
+// Distinct ids by contract, in the order they were first received
+fn get_received_ids(logs: &[Log]) -> Vec<(H160, Vec<U256>)> {
+    let mut received: Vec<(H160, Vec<U256>)> = vec![];
+
+    for log in logs {
+        let index = match received
+            .iter()
+            .position(|(contract_address, _)| *contract_address == log.address)
+        {
+            Some(index) => index,
+            None => {
+                received.push((log.address, vec![]));
+                received.len() - 1
+            }
+        };
+
+        for token_id in decode_ids(log) {
+            if !received[index].1.contains(&token_id) {
+                received[index].1.push(token_id);
+            }
+        }
+    }
+
+    received
+}
+
+fn decode_ids(log: &Log) -> Vec<U256> {
+    let is_batch = log.topics.first() == Some(&H256::from(keccak256(TRANSFER_BATCH.as_bytes())));
+
+    let outputs = match is_batch {
+        true => vec![
+            ParamType::Array(Box::new(ParamType::Uint(256))),
+            ParamType::Array(Box::new(ParamType::Uint(256))),
+        ],
+        false => vec![ParamType::Uint(256), ParamType::Uint(256)],
+    };
+
+    match ethabi::decode(&outputs, &log.data.0).map(|tokens| tokens.into_iter().next()) {
+        Ok(Some(Token::Array(ids))) => ids.into_iter().filter_map(Token::into_uint).collect(),
+        Ok(Some(Token::Uint(id))) => vec![id],
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+    use serde_json::json;
+    use web3::types::Bytes;
+
+    const CONTRACT: &str = "0x76be3b62873462d2142405439777e971754e8e77";
+
+    fn get_log(signature: &str, data: Vec<Token>) -> Log {
+        let mut log: Log = serde_json::from_value(json!({
+            "address": CONTRACT,
+            "topics": [],
+            "data": "0x"
+        }))
+        .unwrap();
+        log.topics = vec![H256::from(keccak256(signature.as_bytes()))];
+        log.data = Bytes(ethabi::encode(&data));
+        log
+    }
+
+    #[tokio::test]
+    async fn get_received_ids_success() {
+        let logs = vec![
+            get_log(
+                TRANSFER_SINGLE,
+                vec![Token::Uint(U256::from(7)), Token::Uint(U256::from(1))],
+            ),
+            get_log(
+                TRANSFER_BATCH,
+                vec![
+                    Token::Array(vec![Token::Uint(U256::from(7)), Token::Uint(U256::from(9))]),
+                    Token::Array(vec![Token::Uint(U256::from(2)), Token::Uint(U256::from(3))]),
+                ],
+            ),
+        ];
+
+        let received = get_received_ids(&logs);
+        assert_eq!(received.len(), 1);
+        assert_eq!(received[0].0, CONTRACT.parse::<H160>().unwrap());
+        assert_eq!(received[0].1, vec![U256::from(7), U256::from(9)]);
+    }
+
+    #[tokio::test]
+    async fn get_usd_value_success() {
+        let multi_token = MultiToken {
+            collection: "parallel".to_string(),
+            contract_address: CONTRACT.to_string(),
+            token_id: U256::from(7),
+            balance: U256::from(3),
+            floor_usd: Some(2.5),
+        };
+        let unpriced = MultiToken {
+            floor_usd: None,
+            ..multi_token.clone()
+        };
+
+        assert_eq!(multi_token.get_usd_value(), Some(7.5));
+        assert_eq!(unpriced.get_usd_value(), None);
+        assert_eq!(get_usd_total(&[multi_token, unpriced]), 7.5);
+    }
+
+    #[tokio::test]
+    async fn list_erc1155_for_account_success() {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_infura_key = settings
+            .get::<String>("test_infura")
+            .unwrap_or_else(|_| panic!("test infura key is not set in Settings.toml, exit."));
+
+        let endpoint = format!("https://mainnet.infura.io/v3/{}", test_infura_key);
+        let web3 = Web3::new(Http::new(&endpoint).unwrap());
+
+        // Vitalik's address
+        let account_address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045"
+            .parse::<H160>()
+            .unwrap();
+
+        let multi_tokens = list_erc1155_for_account(&web3, account_address, None, false)
+            .await
+            .unwrap();
+        assert!(multi_tokens
+            .iter()
+            .all(|multi_token| !multi_token.balance.is_zero()));
+    }
+}
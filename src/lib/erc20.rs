@@ -1,6 +1,13 @@
-use crate::coingecko::Coingecko;
-use crate::lib::price_provider::PriceProvider;
-use crate::paraswap::Paraswap;
+use crate::lib::chain::Explorer;
+use crate::lib::consensus::{self, Consensus};
+use crate::lib::cost_basis::CostBasis;
+use crate::lib::fiat::{self, Currencies};
+use crate::lib::historical::Historical;
+use crate::lib::onchain;
+use crate::lib::paraswap::Paraswap;
+use crate::lib::price_provider::{PriceProvider, ETH_ADDRESS};
+use crate::lib::pricing::{self, PriceProviders, PricingConfig};
+use crate::lib::staking;
 use governor::{Quota, RateLimiter};
 use indicatif::ProgressBar;
 use nonzero_ext::*;
@@ -11,7 +18,13 @@ use std::error;
 use std::io::{self, Write};
 use std::thread::sleep;
 use std::time::Duration;
+use web3::ethabi::{ParamType, Token};
+use web3::transports::Http;
 use web3::types::H160;
+use web3::Web3;
+
+// Difference left by float rounding between the cost basis lots and the balance
+const DUST: f64 = 1e-6;
 
 #[derive(Debug)]
 pub struct TokenInfo {
@@ -22,18 +35,32 @@ pub struct TokenInfo {
     pub usd_balance: f64,
     pub eth_balance: f64,
     pub coingecko_link: String,
+    pub liquidation_usd: Option<f64>,
+    pub price_source: String,
+    pub price_spread: Option<f64>,
+    pub fiat_balances: Vec<(String, f64)>,
+    pub cost_basis: Option<f64>,
+    // Amount of tokens the cost basis lots add up to
+    pub cost_basis_amount: Option<f64>,
+    pub realized_pnl: Option<f64>,
 }
 
 #[derive(Debug)]
 pub struct ListConfig {
-    pub startblock: i32,
-    pub endblock: i32,
+    pub startblock: u64,
+    pub endblock: u64,
     pub show_progress_bar: bool,
     pub verbose: bool,
+    pub liquidation: bool,
+    pub endpoint: Option<String>,
+    pub pricing: PricingConfig,
+    pub consensus: bool,
+    pub currencies: Currencies,
+    pub historical: Option<Historical>,
 }
 
 impl<'a> TokenInfo {
-    fn new(
+    pub fn new(
         contract_address: &'a str,
         balance: &'a f64,
         usd_price: &'a f64,
@@ -48,14 +75,48 @@ impl<'a> TokenInfo {
             usd_balance: balance * usd_price,
             eth_balance: balance * eth_price,
             coingecko_link: coingecko_link.to_string(),
+            liquidation_usd: None,
+            price_source: String::new(),
+            price_spread: None,
+            fiat_balances: vec![(fiat::DEFAULT_CURRENCY.to_string(), balance * usd_price)],
+            cost_basis: None,
+            cost_basis_amount: None,
+            realized_pnl: None,
         }
     }
+
+    pub fn set_cost_basis(&mut self, cost_basis: Option<&CostBasis>) {
+        self.cost_basis = cost_basis.and_then(CostBasis::get_cost);
+        self.cost_basis_amount = cost_basis.map(CostBasis::get_amount);
+        self.realized_pnl = cost_basis.map(CostBasis::get_realized_pnl);
+    }
+
+    // Value of the lots over their cost, unknown when the lots do not add up to the balance,
+    // e.g. after a rebase or transfers missing from the history
+    pub fn get_unrealized_pnl(&self) -> Option<f64> {
+        let cost_basis = self.cost_basis?;
+        let amount = self.cost_basis_amount?;
+
+        match (amount - self.balance).abs() > DUST * self.balance.max(1.0) {
+            true => None,
+            false => Some(amount * self.usd_price - cost_basis),
+        }
+    }
+
+    // Prices the token in the native asset of a chain not paying gas in ETH
+    pub fn set_native_price(&mut self, native_usd_price: f64) {
+        self.eth_price = match native_usd_price > 0.0 {
+            true => self.usd_price / native_usd_price,
+            false => 0.0,
+        };
+        self.eth_balance = self.balance * self.eth_price;
+    }
 }
 
 impl ListConfig {
     pub fn new(
-        startblock: Option<i32>,
-        endblock: Option<i32>,
+        startblock: Option<u64>,
+        endblock: Option<u64>,
         show_progress_bar: bool,
         verbose: bool,
     ) -> ListConfig {
@@ -74,6 +135,12 @@ impl ListConfig {
             endblock: endblock_number,
             show_progress_bar,
             verbose,
+            liquidation: false,
+            endpoint: None,
+            pricing: PricingConfig::default(),
+            consensus: false,
+            currencies: Currencies::default(),
+            historical: None,
         }
     }
 }
@@ -110,11 +177,14 @@ pub async fn get_token_decimal(
 
 pub async fn get_erc20_balance_for_account(
     account_address: H160,
-    etherscan_api_key: &str,
+    explorer: &Explorer,
     ethplorer_api_key: &str,
     contract_address: &str,
 ) -> Result<f64, Box<dyn error::Error>> {
-    let url = format!("https://api.etherscan.io/api?module=account&action=tokenbalance&contractaddress={}&address={:?}&tag=latest&apikey={}", contract_address, account_address, etherscan_api_key);
+    let url = explorer.get_url(&format!(
+        "module=account&action=tokenbalance&contractaddress={}&address={:?}&tag=latest",
+        contract_address, account_address
+    ));
     let body = reqwest::get(&url).await?.text().await?;
     let json: Value = serde_json::from_str(&body)?;
     let mix_selector = r#""result""#;
@@ -143,16 +213,54 @@ pub async fn get_erc20_balance_for_account(
     }
 }
 
+// Latest balance, or the one at the historical block which needs an archive RPC endpoint
+// for blocks older than the last 128
+pub async fn get_erc20_balance_on_chain(
+    web3: &Web3<Http>,
+    account_address: H160,
+    contract_address: &str,
+    historical: Option<&Historical>,
+) -> Result<f64, Box<dyn error::Error>> {
+    let contract_address = contract_address.parse::<H160>()?;
+
+    let result = onchain::call(
+        web3,
+        contract_address,
+        "balanceOf(address)",
+        &[Token::Address(account_address)],
+        &[ParamType::Uint(256)],
+        historical.map(Historical::get_block_id),
+    )
+    .await?;
+    let decimals = onchain::get_decimals(web3, contract_address).await?;
+
+    Ok(onchain::scale(onchain::get_uint(&result, 0)?, decimals))
+}
+
 pub async fn list_erc20_for_account(
     account_address: H160,
-    etherscan_api_key: &str,
+    explorer: &Explorer,
     ethplorer_api_key: &str,
     list_config: ListConfig,
 ) -> Result<Tokens, Box<dyn error::Error>> {
-    let price_providers: Vec<Box<dyn PriceProvider>> =
-        vec![Box::new(Coingecko), Box::new(Paraswap)];
-    let url =
-        format!("http://api.etherscan.io/api?module=account&action=tokentx&address={:?}&startblock={}&endblock={}&sort=asc&apikey={}", account_address, list_config.startblock, list_config.endblock, etherscan_api_key);
+    let price_providers =
+        PriceProviders::new(&list_config.pricing, list_config.endpoint.as_deref())?;
+    // Ethplorer only knows the decimals of mainnet tokens, balances elsewhere are read on-chain
+    let on_chain = list_config.historical.is_some() || !list_config.pricing.chain.is_ethereum();
+    let web3 = match &list_config.endpoint {
+        Some(endpoint) => Some(Web3::new(Http::new(endpoint)?)),
+        None => None,
+    };
+    if on_chain && web3.is_none() {
+        return Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "Historical or non-mainnet ERC20 balances need an RPC endpoint",
+        )));
+    }
+    let url = explorer.get_url(&format!(
+        "module=account&action=tokentx&address={:?}&startblock={}&endblock={}&sort=asc",
+        account_address, list_config.startblock, list_config.endblock
+    ));
     let body = reqwest::get(&url).await?.text().await?;
     let json: Value = serde_json::from_str(&body)?;
     let mix_selector = r#""result"|{"tokenSymbol", "tokenName", "contractAddress"}"#;
@@ -174,7 +282,29 @@ pub async fn list_erc20_for_account(
 
     match results {
         Value::Array(value) => {
+            let contract_addresses: Vec<String> = value
+                .iter()
+                .filter_map(|entry| entry.get("contractAddress")?.as_str())
+                .map(str::to_string)
+                .collect();
+            // Prefetched prices are current ones, useless at a past block
+            let prefetch_providers = match list_config.historical {
+                Some(_) => vec![],
+                None => price_providers.get_all(),
+            };
+            for price_provider in prefetch_providers {
+                if let Err(err) = price_provider
+                    .prefetch(&contract_addresses, list_config.verbose)
+                    .await
+                {
+                    if list_config.verbose {
+                        println!("Failed to prefetch token prices: {}", err);
+                    }
+                }
+            }
+
             let mut tokens = Tokens::new();
+            let mut eth_usd_consensus: Option<Consensus> = None;
             let mut pb: Option<ProgressBar> = None;
             if list_config.show_progress_bar {
                 pb = Some(ProgressBar::new(value.len().try_into()?));
@@ -198,42 +328,102 @@ pub async fn list_erc20_for_account(
                             .as_str()
                             .ok_or("contractAddress invalid")?;
 
-                        let token_id_result = match get_token_id_from_contract_address(
-                            &price_providers,
+                        let token_providers = price_providers.get_chain(contract_address);
+
+                        let token_ids = get_token_ids_from_contract_address(
+                            &token_providers,
                             contract_address,
                             &list_config,
                         )
-                        .await
-                        {
+                        .await;
+
+                        if token_ids.iter().all(Option::is_none) {
+                            continue;
+                        }
+
+                        let balance: f64 = match (&web3, on_chain) {
+                            (Some(web3), true) => {
+                                get_erc20_balance_on_chain(
+                                    web3,
+                                    account_address,
+                                    contract_address,
+                                    list_config.historical.as_ref(),
+                                )
+                                .await?
+                            }
+                            _ => {
+                                get_erc20_balance_for_account(
+                                    account_address,
+                                    explorer,
+                                    ethplorer_api_key,
+                                    contract_address,
+                                )
+                                .await?
+                            }
+                        };
+
+                        let rate_prices = match &web3 {
+                            Some(web3) => {
+                                fetch_eth_backed_prices(
+                                    web3,
+                                    contract_address,
+                                    &price_providers,
+                                    &mut eth_usd_consensus,
+                                    &list_config,
+                                    &limiter,
+                                )
+                                .await
+                            }
+                            None => None,
+                        };
+                        let eth_backed = rate_prices.is_some();
+
+                        let prices = match rate_prices {
+                            Some(prices) => Some(prices),
+                            None => {
+                                fetch_prices(&token_providers, &token_ids, &list_config, &limiter)
+                                    .await
+                            }
+                        };
+                        let (usd_consensus, eth_consensus) = match prices {
                             Some(value) => value,
                             None => continue,
                         };
 
-                        let token_id = token_id_result?;
-
-                        let balance: f64 = get_erc20_balance_for_account(
-                            account_address,
-                            etherscan_api_key,
-                            ethplorer_api_key,
-                            contract_address,
-                        )
-                        .await?;
-
-                        let (usd_price, eth_price) =
-                            match fetch_prices(&price_providers, &token_id, &list_config, &limiter)
-                                .await
-                            {
-                                Some(value) => value,
-                                None => continue,
-                            };
+                        let token_link = get_token_link(&token_providers, &token_ids);
 
-                        let token_info: TokenInfo = TokenInfo::new(
+                        let mut token_info: TokenInfo = TokenInfo::new(
                             contract_address,
                             &balance,
-                            &usd_price,
-                            &eth_price,
-                            &format!("https://coingecko.com/en/coins/{}", token_id),
+                            &usd_consensus.price,
+                            &eth_consensus.price,
+                            &token_link,
                         );
+                        token_info.price_source = usd_consensus.get_source();
+                        token_info.price_spread = usd_consensus.spread;
+                        // Exchange rate valued tokens are converted from USD
+                        token_info.fiat_balances = match eth_backed {
+                            true => {
+                                fetch_fiat_balances(&[], &[], &token_info, &list_config, &limiter)
+                                    .await
+                            }
+                            false => {
+                                fetch_fiat_balances(
+                                    &token_providers,
+                                    &token_ids,
+                                    &token_info,
+                                    &list_config,
+                                    &limiter,
+                                )
+                                .await
+                            }
+                        };
+
+                        if list_config.liquidation {
+                            token_info.liquidation_usd =
+                                fetch_liquidation_value(contract_address, balance, &list_config)
+                                    .await;
+                        }
 
                         tokens.insert(token_symbol, Some(token_info));
                     }
@@ -253,8 +443,32 @@ pub async fn list_erc20_for_account(
 }
 
 async fn fetch_prices(
-    price_providers: &Vec<Box<dyn PriceProvider>>,
-    token_id: &String,
+    price_providers: &[&dyn PriceProvider],
+    token_ids: &[Option<String>],
+    list_config: &ListConfig,
+    limiter: &RateLimiter<
+        governor::state::NotKeyed,
+        governor::state::InMemoryState,
+        governor::clock::QuantaClock,
+        governor::middleware::NoOpMiddleware<governor::clock::QuantaInstant>,
+    >,
+) -> Option<(Consensus, Consensus)> {
+    let usd_consensus =
+        fetch_price(price_providers, token_ids, "usd", list_config, limiter).await?;
+
+    let eth_consensus =
+        fetch_price(price_providers, token_ids, "eth", list_config, limiter).await?;
+
+    Some((usd_consensus, eth_consensus))
+}
+
+// Wrapped and staked ETH on mainnet are worth their on-chain exchange rate times the ETH price,
+// which is fetched once
+async fn fetch_eth_backed_prices(
+    web3: &Web3<Http>,
+    contract_address: &str,
+    price_providers: &PriceProviders,
+    eth_usd_consensus: &mut Option<Consensus>,
     list_config: &ListConfig,
     limiter: &RateLimiter<
         governor::state::NotKeyed,
@@ -262,25 +476,55 @@ async fn fetch_prices(
         governor::clock::QuantaClock,
         governor::middleware::NoOpMiddleware<governor::clock::QuantaInstant>,
     >,
-) -> Option<(f64, f64)> {
-    let usd_price = match fetch_price(price_providers, token_id, "usd", list_config, limiter).await
+) -> Option<(Consensus, Consensus)> {
+    if !list_config.pricing.chain.is_ethereum() || !staking::is_eth_backed(contract_address) {
+        return None;
+    }
+
+    let eth_rate = match staking::get_eth_rate(
+        web3,
+        contract_address,
+        list_config.historical.as_ref(),
+    )
+    .await
     {
-        Ok(value) => value,
-        Err(value) => return value,
+        Ok(eth_rate) => eth_rate,
+        Err(err) => {
+            if list_config.verbose {
+                println!(
+                    "Failed to read the ETH exchange rate of {}: {}",
+                    contract_address, err
+                );
+            }
+            return None;
+        }
     };
 
-    let eth_price = match fetch_price(price_providers, token_id, "eth", list_config, limiter).await
-    {
-        Ok(value) => value,
-        Err(value) => return value,
+    if eth_usd_consensus.is_none() {
+        let eth_providers = price_providers.get_chain(ETH_ADDRESS);
+        let eth_token_ids =
+            get_token_ids_from_contract_address(&eth_providers, ETH_ADDRESS, list_config).await;
+        *eth_usd_consensus =
+            fetch_price(&eth_providers, &eth_token_ids, "usd", list_config, limiter).await;
+    }
+    let eth_usd_consensus = eth_usd_consensus.as_ref()?;
+
+    let usd_consensus = Consensus {
+        price: eth_rate * eth_usd_consensus.price,
+        spread: eth_usd_consensus.spread,
+        sources: vec![format!(
+            "exchange rate x {}",
+            eth_usd_consensus.get_source()
+        )],
+        outliers: vec![],
     };
 
-    Some((usd_price, eth_price))
+    Some((usd_consensus, Consensus::single("exchange rate", eth_rate)))
 }
 
 async fn fetch_price(
-    price_providers: &Vec<Box<dyn PriceProvider>>,
-    token_id: &String,
+    price_providers: &[&dyn PriceProvider],
+    token_ids: &[Option<String>],
     versus_name: &str,
     list_config: &ListConfig,
     limiter: &RateLimiter<
@@ -289,39 +533,154 @@ async fn fetch_price(
         governor::clock::QuantaClock,
         governor::middleware::NoOpMiddleware<governor::clock::QuantaInstant>,
     >,
-) -> Result<f64, Option<(f64, f64)>> {
-    for price_provider in price_providers {
-        let token_price_future =
-            price_provider.get_token_price(token_id, versus_name, list_config.verbose);
+) -> Option<Consensus> {
+    let mut quotes: Vec<(String, f64)> = vec![];
+
+    for (price_provider, token_id) in price_providers.iter().zip(token_ids) {
+        let token_id = match token_id {
+            Some(token_id) => token_id,
+            None => continue,
+        };
+
+        let token_price_future = pricing::get_price(
+            *price_provider,
+            token_id,
+            versus_name,
+            list_config.historical.as_ref(),
+            list_config.verbose,
+        );
         match limiter.check() {
             Ok(()) => (),
             _ => sleep(Duration::from_millis(2000)),
         };
 
         if let Ok(v) = token_price_future.await {
-            return Ok(v);
+            if !list_config.consensus {
+                return Some(Consensus::single(price_provider.get_name(), v));
+            }
+            quotes.push((price_provider.get_name().to_string(), v));
         }
     }
 
-    Err(None)
+    let consensus = consensus::get_consensus(&quotes, list_config.pricing.max_deviation)?;
+
+    if list_config.verbose && consensus.sources.is_empty() {
+        println!(
+            "No price sources within {}% of each other, using their median {} {}: {}",
+            list_config.pricing.max_deviation,
+            consensus.price,
+            versus_name,
+            consensus.outliers.join(", ")
+        );
+    } else if list_config.verbose && !consensus.outliers.is_empty() {
+        println!(
+            "Price sources deviating more than {}% from the median, left out of the {} {} consensus: {}",
+            list_config.pricing.max_deviation,
+            consensus.price,
+            versus_name,
+            consensus.outliers.join(", ")
+        );
+    }
+
+    Some(consensus)
 }
 
-async fn get_token_id_from_contract_address(
-    price_providers: &Vec<Box<dyn PriceProvider>>,
+async fn fetch_fiat_balances(
+    price_providers: &[&dyn PriceProvider],
+    token_ids: &[Option<String>],
+    token_info: &TokenInfo,
+    list_config: &ListConfig,
+    limiter: &RateLimiter<
+        governor::state::NotKeyed,
+        governor::state::InMemoryState,
+        governor::clock::QuantaClock,
+        governor::middleware::NoOpMiddleware<governor::clock::QuantaInstant>,
+    >,
+) -> Vec<(String, f64)> {
+    let mut fiat_balances = vec![];
+
+    for currency in &list_config.currencies.names {
+        if currency == fiat::DEFAULT_CURRENCY {
+            fiat_balances.push((currency.to_string(), token_info.usd_balance));
+            continue;
+        }
+
+        // Ask the providers quoting this currency directly, otherwise convert from USD
+        let (fiat_providers, fiat_token_ids): (Vec<&dyn PriceProvider>, Vec<Option<String>>) =
+            price_providers
+                .iter()
+                .zip(token_ids)
+                .filter(|(price_provider, _)| price_provider.supports_versus(currency))
+                .map(|(price_provider, token_id)| (*price_provider, token_id.clone()))
+                .unzip();
+
+        let fiat_balance = match fetch_price(
+            &fiat_providers,
+            &fiat_token_ids,
+            currency,
+            list_config,
+            limiter,
+        )
+        .await
+        {
+            Some(consensus) => Some(token_info.balance * consensus.price),
+            None => list_config
+                .currencies
+                .convert(currency, token_info.usd_balance),
+        };
+
+        if let Some(fiat_balance) = fiat_balance {
+            fiat_balances.push((currency.to_string(), fiat_balance));
+        }
+    }
+
+    fiat_balances
+}
+
+async fn fetch_liquidation_value(
+    contract_address: &str,
+    balance: f64,
+    list_config: &ListConfig,
+) -> Option<f64> {
+    if balance == 0.0 {
+        return Some(0.0);
+    }
+
+    Paraswap::new(&list_config.pricing.chain)?
+        .get_liquidation_value(contract_address, balance, "usd", list_config.verbose)
+        .await
+        .ok()
+}
+
+async fn get_token_ids_from_contract_address(
+    price_providers: &[&dyn PriceProvider],
     contract_address: &str,
     list_config: &ListConfig,
-) -> Option<Result<String, Box<dyn error::Error>>> {
+) -> Vec<Option<String>> {
+    let mut token_ids = Vec::with_capacity(price_providers.len());
+
     for price_provider in price_providers {
-        let token_id_result = price_provider
+        let token_id = price_provider
             .get_token_id_from_contract_address(contract_address, list_config.verbose)
-            .await;
-        if let Result::Err(_err) = token_id_result {
-            continue;
-        }
-        return Some(token_id_result);
+            .await
+            .ok()
+            .filter(|token_id| !token_id.is_empty());
+        token_ids.push(token_id);
     }
 
-    None
+    token_ids
+}
+
+fn get_token_link(price_providers: &[&dyn PriceProvider], token_ids: &[Option<String>]) -> String {
+    price_providers
+        .iter()
+        .zip(token_ids)
+        .find_map(|(price_provider, token_id)| {
+            token_id
+                .as_ref()
+                .and_then(|token_id| price_provider.get_token_link(token_id))
+        })
+        .unwrap_or_default()
 }
 
 #[cfg(test)]
@@ -382,7 +741,7 @@ mod test {
             .unwrap_or_else(|_| panic!("test ethplorer key is not set in Settings.toml, exit."));
         let balance = get_erc20_balance_for_account(
             test_account_address,
-            &test_etherscan_api_key,
+            &Explorer::etherscan(&test_etherscan_api_key),
             &test_ethplorer_api_key,
             test_contract_address,
         )
@@ -407,7 +766,7 @@ mod test {
             .unwrap_or_else(|_| panic!("test ethplorer key is not set in Settings.toml, exit."));
         let balance = get_erc20_balance_for_account(
             test_account_address,
-            &test_etherscan_api_key,
+            &Explorer::etherscan(&test_etherscan_api_key),
             &test_ethplorer_api_key,
             test_contract_address,
         )
@@ -438,7 +797,7 @@ mod test {
 
         let list_erc20 = list_erc20_for_account(
             test_account_address,
-            &test_etherscan_api_key,
+            &Explorer::etherscan(&test_etherscan_api_key),
             &test_ethplorer_api_key,
             list_config,
         )
@@ -448,6 +807,37 @@ mod test {
         assert_eq!(list_erc20.len(), 1);
     }
 
+    #[tokio::test]
+    async fn list_erc20_for_account_with_liquidation_success() {
+        let test_account_address: H160 =
+            "000000000000000000000000000000000000dead".parse().unwrap();
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_etherscan_api_key = settings
+            .get::<String>("test_etherscan")
+            .unwrap_or_else(|_| panic!("test etherscan key is not set in Settings.toml, exit."));
+        let test_ethplorer_api_key = settings
+            .get::<String>("test_ethplorer")
+            .unwrap_or_else(|_| panic!("test ethplorer key is not set in Settings.toml, exit."));
+
+        let mut list_config = ListConfig::new(Some(11855520), Some(11855590), false, false);
+        list_config.liquidation = true;
+
+        let list_erc20 = list_erc20_for_account(
+            test_account_address,
+            &Explorer::etherscan(&test_etherscan_api_key),
+            &test_ethplorer_api_key,
+            list_config,
+        )
+        .await
+        .unwrap();
+
+        for token_info in list_erc20.values().flatten() {
+            assert!(token_info.liquidation_usd.is_some());
+        }
+    }
+
     #[tokio::test]
     async fn list_erc20_for_account_fail() {
         let test_account_address: H160 = "0x0121212121212121212121212212121212121212"
@@ -467,7 +857,7 @@ mod test {
 
         let list_erc20 = list_erc20_for_account(
             test_account_address,
-            &test_etherscan_api_key,
+            &Explorer::etherscan(&test_etherscan_api_key),
             &test_ethplorer_api_key,
             list_config,
         )
@@ -480,4 +870,28 @@ mod test {
             );
         }
     }
+
+    #[tokio::test]
+    async fn set_native_price_success() {
+        let mut token_info = TokenInfo::new("", &10.0, &1.0, &0.0005, "");
+        token_info.set_native_price(0.25);
+
+        assert_eq!(token_info.eth_price, 4.0);
+        assert_eq!(token_info.eth_balance, 40.0);
+        assert_eq!(token_info.usd_balance, 10.0);
+    }
+
+    #[tokio::test]
+    async fn get_unrealized_pnl_success() {
+        let mut token_info = TokenInfo::new("", &10.0, &3.0, &0.0015, "");
+        assert_eq!(token_info.get_unrealized_pnl(), None);
+
+        token_info.cost_basis = Some(20.0);
+        token_info.cost_basis_amount = Some(10.0 - 1e-12);
+        assert!((token_info.get_unrealized_pnl().unwrap() - 10.0).abs() < 1e-9);
+
+        // Lots missing part of the balance
+        token_info.cost_basis_amount = Some(4.0);
+        assert_eq!(token_info.get_unrealized_pnl(), None);
+    }
 }
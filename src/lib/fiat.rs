@@ -0,0 +1,151 @@
+use super::coingecko::Coingecko;
+use super::price_provider::PriceProvider;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error;
+use std::io;
+
+pub const DEFAULT_CURRENCY: &str = "usd";
+
+// Fiat currencies to report in and the USD -> fiat rate of each one
+#[derive(Debug, Clone)]
+pub struct Currencies {
+    pub names: Vec<String>,
+    pub fx_rates: HashMap<String, f64>,
+}
+
+impl Default for Currencies {
+    fn default() -> Currencies {
+        Currencies {
+            names: vec![DEFAULT_CURRENCY.to_string()],
+            fx_rates: HashMap::from([(DEFAULT_CURRENCY.to_string(), 1.0)]),
+        }
+    }
+}
+
+impl Currencies {
+    pub async fn load(
+        names: &[String],
+        verbose: bool,
+    ) -> Result<Currencies, Box<dyn error::Error>> {
+        let mut currencies = Currencies {
+            names: vec![],
+            fx_rates: HashMap::from([(DEFAULT_CURRENCY.to_string(), 1.0)]),
+        };
+
+        for name in names {
+            let name = name.to_lowercase();
+            if !currencies.names.contains(&name) {
+                currencies.names.push(name);
+            }
+        }
+
+        if currencies.names.is_empty() {
+            return Ok(Currencies::default());
+        }
+
+        if currencies.names.iter().any(|name| name != DEFAULT_CURRENCY) {
+            let fx_rates = get_fx_rates(verbose).await?;
+
+            for name in &currencies.names {
+                let fx_rate = fx_rates.get(name).ok_or_else(|| {
+                    io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Unknown fiat currency {}", name),
+                    )
+                })?;
+                currencies.fx_rates.insert(name.to_string(), *fx_rate);
+            }
+        }
+
+        Ok(currencies)
+    }
+
+    // None for a currency without a loaded rate
+    pub fn convert(&self, currency: &str, usd_value: f64) -> Option<f64> {
+        Some(usd_value * self.fx_rates.get(currency)?)
+    }
+}
+
+// CoinGecko publishes BTC based rates, USD -> fiat is the ratio of both
+pub async fn get_fx_rates(verbose: bool) -> Result<HashMap<String, f64>, Box<dyn error::Error>> {
+    let url = "https://api.coingecko.com/api/v3/exchange_rates";
+    let json = Coingecko::default().fetch(url, verbose).await?;
+
+    let mix_selector = r#""rates""#;
+
+    let value: Value = jql::walker(&json, mix_selector)?;
+
+    let rates = match value {
+        Value::Object(rates) => rates,
+        _ => {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "Could not fetch exchange rates from coingecko",
+            )))
+        }
+    };
+
+    let usd_rate = rates
+        .get(DEFAULT_CURRENCY)
+        .and_then(|rate| rate.get("value")?.as_f64())
+        .filter(|usd_rate| *usd_rate > 0.0)
+        .ok_or("Could not fetch USD exchange rate from coingecko")?;
+
+    let fx_rates = rates
+        .iter()
+        .filter(|(_, rate)| rate.get("type").and_then(Value::as_str) == Some("fiat"))
+        .filter_map(|(name, rate)| {
+            Some((name.to_string(), rate.get("value")?.as_f64()? / usd_rate))
+        })
+        .collect();
+
+    Ok(fx_rates)
+}
+
+pub fn get_symbol(currency: &str) -> String {
+    match currency {
+        "usd" => "US$".to_string(),
+        "eur" => "€".to_string(),
+        "gbp" => "£".to_string(),
+        "jpy" => "¥".to_string(),
+        _ => currency.to_uppercase(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn load_default_success() {
+        let currencies = Currencies::load(&["USD".to_string()], false).await.unwrap();
+        assert_eq!(currencies.names, vec!["usd"]);
+        assert_eq!(currencies.convert("usd", 10.0), Some(10.0));
+        assert_eq!(currencies.convert("eur", 10.0), None);
+    }
+
+    #[tokio::test]
+    async fn load_success() {
+        let currencies = Currencies::load(&["eur".to_string(), "chf".to_string()], false)
+            .await
+            .unwrap();
+        assert_eq!(currencies.names, vec!["eur", "chf"]);
+        assert!(currencies.convert("eur", 10.0).is_some_and(|eur| eur > 0.0));
+        assert!(currencies.convert("chf", 10.0).is_some_and(|chf| chf > 0.0));
+    }
+
+    #[tokio::test]
+    async fn load_unknown_currency_fail() {
+        let result = Currencies::load(&["btc".to_string()], false).await;
+        if let Result::Err(err) = result {
+            assert_eq!((*err).to_string(), "Unknown fiat currency btc");
+        }
+    }
+
+    #[tokio::test]
+    async fn get_symbol_success() {
+        assert_eq!(get_symbol("eur"), "€");
+        assert_eq!(get_symbol("chf"), "CHF");
+    }
+}
@@ -0,0 +1,347 @@
+use super::chain::Explorer;
+use super::erc20::ListConfig;
+use super::historical::{self, Historical};
+use super::history;
+use super::price_provider::ETH_ADDRESS;
+use super::pricing::{self, PriceProviders};
+use serde_json::Value;
+use std::cmp::Ordering::Equal;
+use std::collections::HashMap;
+use std::error;
+use web3::transports::Http;
+use web3::types::H160;
+use web3::{Transport, Web3};
+
+// Fee paid by the account for a transaction it sent
+#[derive(Debug, Clone, PartialEq)]
+pub struct GasSpend {
+    pub hash: String,
+    pub block: u64,
+    pub timestamp: i64,
+    pub contract_address: String,
+    pub eth: f64,
+    pub usd_value: Option<f64>,
+}
+
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct GasTotal {
+    pub key: String,
+    pub transactions: usize,
+    pub eth: f64,
+    // Unknown as soon as one gas spend of the group has no price
+    pub usd_value: Option<f64>,
+}
+
+impl GasSpend {
+    fn get_month(&self) -> String {
+        historical::format_timestamp(self.timestamp)
+            .get(..7)
+            .unwrap_or_default()
+            .to_string()
+    }
+}
+
+// Failed transactions are included, their gas is spent all the same
+pub async fn list_gas_spend_for_account(
+    account_address: H160,
+    explorer: &Explorer,
+    list_config: &ListConfig,
+) -> Result<Vec<GasSpend>, Box<dyn error::Error>> {
+    let account = format!("{:?}", account_address);
+
+    let entries = history::fetch_entries(
+        &account,
+        "txlist",
+        list_config.startblock,
+        list_config.endblock,
+        explorer,
+    )
+    .await?;
+
+    if list_config.verbose {
+        println!("Fetched {} transactions", entries.len());
+    }
+
+    Ok(entries
+        .iter()
+        .filter_map(|entry| parse_gas_spend(entry, &account))
+        .collect())
+}
+
+// The explorer gas price only covers the L2 execution, the L1 data fee is in the receipt
+pub async fn add_l1_fees(gas_spends: &mut [GasSpend], web3: &Web3<Http>, verbose: bool) {
+    for gas_spend in gas_spends {
+        let receipt = web3
+            .transport()
+            .execute(
+                "eth_getTransactionReceipt",
+                vec![Value::String(gas_spend.hash.to_string())],
+            )
+            .await;
+
+        match receipt.ok().as_ref().and_then(parse_l1_fee) {
+            Some(l1_fee) => gas_spend.eth += l1_fee,
+            None => {
+                if verbose {
+                    println!(
+                        "No L1 fee read for {}, its fee is understated",
+                        gas_spend.hash
+                    );
+                }
+            }
+        }
+    }
+}
+
+// ETH prices are looked up once per day
+pub async fn price_gas_spends(
+    gas_spends: &mut [GasSpend],
+    price_providers: &PriceProviders,
+    verbose: bool,
+) {
+    let mut prices: HashMap<String, Option<f64>> = HashMap::new();
+    let eth_providers = price_providers.get_chain(ETH_ADDRESS);
+
+    for gas_spend in gas_spends {
+        let historical = Historical {
+            block: gas_spend.block,
+            timestamp: gas_spend.timestamp,
+        };
+
+        let price = match prices.get(&historical.get_date()) {
+            Some(price) => *price,
+            None => {
+                let price = pricing::get_first_price(
+                    &eth_providers,
+                    ETH_ADDRESS,
+                    "usd",
+                    Some(&historical),
+                    verbose,
+                )
+                .await
+                .map(|(_, price)| price);
+                prices.insert(historical.get_date(), price);
+                price
+            }
+        };
+
+        gas_spend.usd_value = price.map(|price| price * gas_spend.eth);
+    }
+}
+
+// Oldest month first
+pub fn group_by_month(gas_spends: &[GasSpend]) -> Vec<GasTotal> {
+    let mut totals = group_by(gas_spends, GasSpend::get_month);
+    totals.sort_by(|a, b| a.key.cmp(&b.key));
+    totals
+}
+
+// Most expensive contract first
+pub fn group_by_contract(gas_spends: &[GasSpend]) -> Vec<GasTotal> {
+    let mut totals = group_by(gas_spends, |gas_spend| {
+        gas_spend.contract_address.to_string()
+    });
+    totals.sort_by(|a, b| b.eth.partial_cmp(&a.eth).unwrap_or(Equal));
+    totals
+}
+
+pub fn get_total(gas_spends: &[GasSpend]) -> GasTotal {
+    group_by(gas_spends, |_| "TOTAL".to_string())
+        .pop()
+        .unwrap_or_else(|| GasTotal {
+            key: "TOTAL".to_string(),
+            usd_value: Some(0.0),
+            ..Default::default()
+        })
+}
+
+fn group_by<F>(gas_spends: &[GasSpend], get_key: F) -> Vec<GasTotal>
+where
+    F: Fn(&GasSpend) -> String,
+{
+    let mut totals: HashMap<String, GasTotal> = HashMap::new();
+
+    for gas_spend in gas_spends {
+        let key = get_key(gas_spend);
+        let total = totals.entry(key.to_string()).or_insert_with(|| GasTotal {
+            key,
+            usd_value: Some(0.0),
+            ..Default::default()
+        });
+        total.transactions += 1;
+        total.eth += gas_spend.eth;
+        total.usd_value = total
+            .usd_value
+            .zip(gas_spend.usd_value)
+            .map(|(total_usd_value, usd_value)| total_usd_value + usd_value);
+    }
+
+    totals.into_values().collect()
+}
+
+pub fn parse_gas_spend(entry: &Value, account: &str) -> Option<GasSpend> {
+    let get = |key: &str| entry.get(key).and_then(Value::as_str);
+
+    if get("from")?.to_lowercase() != account {
+        return None;
+    }
+
+    // Contract creations have no recipient but the address of the new contract
+    let contract_address = match get("to")? {
+        "" => get("contractAddress")?.to_lowercase(),
+        to => to.to_lowercase(),
+    };
+
+    let gas_used = get("gasUsed")?.parse::<f64>().ok()?;
+    let gas_price = get("gasPrice")?.parse::<f64>().ok()?;
+
+    Some(GasSpend {
+        hash: get("hash")?.to_string(),
+        block: get("blockNumber")?.parse().ok()?,
+        timestamp: get("timeStamp")?.parse().ok()?,
+        contract_address,
+        eth: gas_used * gas_price / 1e18,
+        usd_value: None,
+    })
+}
+
+fn parse_l1_fee(receipt: &Value) -> Option<f64> {
+    let l1_fee = receipt.get("l1Fee")?.as_str()?;
+    let l1_fee = u128::from_str_radix(l1_fee.trim_start_matches("0x"), 16).ok()?;
+
+    Some(l1_fee as f64 / 1e18)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+    use serde_json::json;
+
+    const ACCOUNT: &str = "0x000000000000000000000000000000000000dead";
+
+    fn get_gas_spend(timestamp: i64, contract_address: &str, eth: f64) -> GasSpend {
+        GasSpend {
+            hash: "0xabc".to_string(),
+            block: 18908894,
+            timestamp,
+            contract_address: contract_address.to_string(),
+            eth,
+            usd_value: Some(eth * 2000.0),
+        }
+    }
+
+    fn get_gas_spends() -> Vec<GasSpend> {
+        vec![
+            get_gas_spend(1704067199, "0x1", 0.125),
+            get_gas_spend(1704067200, "0x2", 0.25),
+            get_gas_spend(1706745599, "0x2", 0.5),
+            get_gas_spend(1706745600, "0x1", 1.0),
+        ]
+    }
+
+    #[tokio::test]
+    async fn parse_gas_spend_success() {
+        let entry = json!({
+            "blockNumber": "18908894",
+            "timeStamp": "1704067199",
+            "hash": "0xabc",
+            "from": ACCOUNT,
+            "to": "0x7a250d5630B4cF539739dF2C5dAcb4c659F2488D",
+            "contractAddress": "",
+            "gasUsed": "100000",
+            "gasPrice": "20000000000",
+            "isError": "1"
+        });
+
+        let gas_spend = parse_gas_spend(&entry, ACCOUNT).unwrap();
+        assert_eq!(
+            gas_spend.contract_address,
+            "0x7a250d5630b4cf539739df2c5dacb4c659f2488d"
+        );
+        assert_eq!(gas_spend.eth, 0.002);
+    }
+
+    #[tokio::test]
+    async fn parse_gas_spend_incoming_success() {
+        let entry = json!({
+            "blockNumber": "18908894",
+            "timeStamp": "1704067199",
+            "hash": "0xabc",
+            "from": "0x0000000000000000000000000000000000000001",
+            "to": ACCOUNT,
+            "contractAddress": "",
+            "gasUsed": "21000",
+            "gasPrice": "20000000000"
+        });
+
+        assert_eq!(parse_gas_spend(&entry, ACCOUNT), None);
+    }
+
+    #[tokio::test]
+    async fn group_by_month_success() {
+        let totals = group_by_month(&get_gas_spends());
+        assert_eq!(totals.len(), 3);
+        assert_eq!(totals[0].key, "2023-12");
+        assert_eq!(totals[1].key, "2024-01");
+        assert_eq!(totals[1].transactions, 2);
+        assert_eq!(totals[1].eth, 0.75);
+        assert_eq!(totals[2].key, "2024-02");
+    }
+
+    #[tokio::test]
+    async fn group_by_contract_success() {
+        let totals = group_by_contract(&get_gas_spends());
+        assert_eq!(totals.len(), 2);
+        assert_eq!(totals[0].key, "0x1");
+        assert_eq!(totals[0].usd_value, Some(2250.0));
+        assert_eq!(get_total(&get_gas_spends()).transactions, 4);
+        assert_eq!(get_total(&[]).eth, 0.0);
+        assert_eq!(get_total(&[]).usd_value, Some(0.0));
+    }
+
+    #[tokio::test]
+    async fn group_by_unpriced_success() {
+        let mut gas_spends = get_gas_spends();
+        gas_spends[1].usd_value = None;
+
+        let totals = group_by_contract(&gas_spends);
+        assert_eq!(totals[0].usd_value, Some(2250.0));
+        assert_eq!(totals[1].usd_value, None);
+        assert_eq!(get_total(&gas_spends).usd_value, None);
+    }
+
+    #[tokio::test]
+    async fn parse_l1_fee_success() {
+        let receipt = json!({
+            "transactionHash": "0xabc",
+            "gasUsed": "0x5208",
+            "l1Fee": "0x38d7ea4c68000"
+        });
+        assert_eq!(parse_l1_fee(&receipt), Some(0.001));
+        assert_eq!(parse_l1_fee(&json!({ "gasUsed": "0x5208" })), None);
+    }
+
+    #[tokio::test]
+    async fn list_gas_spend_for_account_success() {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_etherscan_api_key = settings
+            .get::<String>("test_etherscan")
+            .unwrap_or_else(|_| panic!("test etherscan key is not set in Settings.toml, exit."));
+
+        // Vitalik's address
+        let account_address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045"
+            .parse::<H160>()
+            .unwrap();
+        let explorer = Explorer::etherscan(&test_etherscan_api_key);
+        let list_config = ListConfig::new(Some(18000000), Some(18100000), false, false);
+
+        let gas_spends = list_gas_spend_for_account(account_address, &explorer, &list_config)
+            .await
+            .unwrap();
+        assert!(!gas_spends.is_empty());
+        assert!(gas_spends.iter().all(|gas_spend| gas_spend.eth > 0.0));
+    }
+}
@@ -0,0 +1,230 @@
+use super::chain::Explorer;
+use chrono::{DateTime, NaiveDate, Utc};
+use serde_json::Value;
+use std::error;
+use std::io;
+use web3::transports::Http;
+use web3::types::{BlockId, BlockNumber};
+use web3::Web3;
+
+// Past block the portfolio is valued at, with its timestamp for historical prices
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Historical {
+    pub block: u64,
+    pub timestamp: i64,
+}
+
+impl Historical {
+    // Accepts a block number or a YYYY-MM-DD date, a date means the last block of that day in UTC
+    pub async fn resolve(
+        at: &str,
+        web3: &Web3<Http>,
+        explorer: &Explorer,
+        verbose: bool,
+    ) -> Result<Historical, Box<dyn error::Error>> {
+        let historical = match at.parse::<u64>() {
+            Ok(block) => Historical {
+                block,
+                timestamp: get_block_timestamp(web3, block).await?,
+            },
+            Err(_) => {
+                let timestamp = parse_date(at)?;
+                if timestamp > Utc::now().timestamp() {
+                    return Err(Box::new(io::Error::new(
+                        io::ErrorKind::InvalidInput,
+                        format!("Date {} is not over yet", at),
+                    )));
+                }
+                Historical {
+                    block: get_block_by_timestamp(explorer, timestamp).await?,
+                    timestamp,
+                }
+            }
+        };
+
+        if verbose {
+            println!(
+                "Valuing portfolio at block {} ({})",
+                historical.block,
+                historical.get_datetime()
+            );
+        }
+
+        Ok(historical)
+    }
+
+    // Same moment on another chain, its last block before the timestamp
+    pub async fn on_chain(&self, explorer: &Explorer) -> Result<Historical, Box<dyn error::Error>> {
+        Ok(Historical {
+            block: get_block_by_timestamp(explorer, self.timestamp).await?,
+            timestamp: self.timestamp,
+        })
+    }
+
+    pub fn get_block_number(&self) -> BlockNumber {
+        BlockNumber::Number(self.block.into())
+    }
+
+    pub fn get_block_id(&self) -> BlockId {
+        BlockId::Number(self.get_block_number())
+    }
+
+    // Date format of the CoinGecko history endpoint, which prices at 00:00 UTC,
+    // so the day closest to the timestamp (the next one for end of day dates)
+    pub fn get_date(&self) -> String {
+        match DateTime::from_timestamp(self.timestamp + 43200, 0) {
+            Some(datetime) => datetime.format("%d-%m-%Y").to_string(),
+            None => String::new(),
+        }
+    }
+
+    pub fn get_datetime(&self) -> String {
+        format_timestamp(self.timestamp)
+    }
+}
+
+pub fn format_timestamp(timestamp: i64) -> String {
+    match DateTime::from_timestamp(timestamp, 0) {
+        Some(datetime) => datetime.format("%Y-%m-%d %H:%M:%S UTC").to_string(),
+        None => String::new(),
+    }
+}
+
+// Start of a range like 30d, 12w, 6m or 1y before now, or a YYYY-MM-DD date
+pub fn parse_since(since: &str, now: i64) -> Result<i64, Box<dyn error::Error>> {
+    let (amount, unit) = match since.char_indices().last() {
+        Some((index, unit)) => (since[..index].parse::<i64>(), unit),
+        None => return parse_date(since),
+    };
+
+    let days = match (amount, unit) {
+        (Ok(amount), 'd') => amount,
+        (Ok(amount), 'w') => amount * 7,
+        (Ok(amount), 'm') => amount * 30,
+        (Ok(amount), 'y') => amount * 365,
+        _ => return Ok(parse_date(since)? - 86399),
+    };
+
+    Ok(now - days * 86400)
+}
+
+// Last second of a YYYY-MM-DD day in UTC
+pub fn parse_date(date: &str) -> Result<i64, Box<dyn error::Error>> {
+    let end_of_day = NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| date.and_hms_opt(23, 59, 59))
+        .ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Error at specified block or date: {}, expected a block number or YYYY-MM-DD",
+                    date
+                ),
+            )
+        })?;
+
+    Ok(end_of_day.and_utc().timestamp())
+}
+
+async fn get_block_timestamp(web3: &Web3<Http>, block: u64) -> Result<i64, Box<dyn error::Error>> {
+    let block_id = BlockId::Number(BlockNumber::Number(block.into()));
+
+    match web3.eth().block(block_id).await? {
+        Some(block) => Ok(block.timestamp.low_u64() as i64),
+        None => Err(Box::new(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Block {} not found", block),
+        ))),
+    }
+}
+
+async fn get_block_by_timestamp(
+    explorer: &Explorer,
+    timestamp: i64,
+) -> Result<u64, Box<dyn error::Error>> {
+    let url = explorer.get_url(&format!(
+        "module=block&action=getblocknobytime&timestamp={}&closest=before",
+        timestamp
+    ));
+    let body = reqwest::get(&url).await?.text().await?;
+    let json: Value = serde_json::from_str(&body)?;
+    let mix_selector = r#""result""#;
+
+    let results = jql::walker(&json, mix_selector)?;
+
+    match results {
+        Value::String(value) if value.parse::<u64>().is_ok() => Ok(value.parse::<u64>()?),
+        _ => Err(Box::new(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("Error on fetching block at timestamp {}", timestamp),
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+
+    #[tokio::test]
+    async fn parse_date_success() {
+        assert_eq!(parse_date("2021-01-31").unwrap(), 1612137599);
+    }
+
+    #[tokio::test]
+    async fn parse_date_fail() {
+        let result = parse_date("31/01/2021");
+        if let Result::Err(err) = result {
+            assert_eq!(
+                (*err).to_string(),
+                "Error at specified block or date: 31/01/2021, expected a block number or YYYY-MM-DD"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_since_success() {
+        let now = 1612137599;
+        assert_eq!(parse_since("30d", now).unwrap(), now - 30 * 86400);
+        assert_eq!(parse_since("2w", now).unwrap(), now - 14 * 86400);
+        assert_eq!(parse_since("1y", now).unwrap(), now - 365 * 86400);
+        assert_eq!(parse_since("2021-01-31", now).unwrap(), 1612051200);
+        assert!(parse_since("", now).is_err());
+    }
+
+    #[tokio::test]
+    async fn get_date_success() {
+        let historical = Historical {
+            block: 11766000,
+            timestamp: 1612137599,
+        };
+        assert_eq!(historical.get_date(), "01-02-2021");
+        assert_eq!(historical.get_datetime(), "2021-01-31 23:59:59 UTC");
+
+        let historical = Historical {
+            block: 11762000,
+            timestamp: 1612090800,
+        };
+        assert_eq!(historical.get_date(), "31-01-2021");
+        assert_eq!(historical.get_datetime(), "2021-01-31 11:00:00 UTC");
+    }
+
+    #[tokio::test]
+    async fn resolve_date_success() {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_etherscan_api_key = settings
+            .get::<String>("test_etherscan")
+            .unwrap_or_else(|_| panic!("test etherscan key is not set in Settings.toml, exit."));
+        let web3 = Web3::new(Http::new("http://localhost:8545").unwrap());
+
+        let explorer = Explorer::etherscan(&test_etherscan_api_key);
+
+        let historical = Historical::resolve("2021-01-31", &web3, &explorer, false)
+            .await
+            .unwrap();
+        assert_eq!(historical.timestamp, 1612137599);
+        assert_ne!(historical.block, 0);
+    }
+}
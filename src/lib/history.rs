@@ -0,0 +1,548 @@
+use super::chain::{Chain, Explorer};
+use super::gas::{self, GasSpend};
+use super::historical::Historical;
+use super::price_provider::ETH_ADDRESS;
+use super::pricing::{self, PriceProviders};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use web3::types::H160;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Kind {
+    Eth,
+    Internal,
+    Erc20,
+    Fee,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Direction {
+    In,
+    Out,
+}
+
+// Balance change of the account in a single transaction
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub hash: String,
+    pub block: u64,
+    pub timestamp: i64,
+    pub kind: Kind,
+    pub direction: Direction,
+    pub counterparty: String,
+    pub symbol: String,
+    pub contract_address: String,
+    pub amount: f64,
+    pub usd_value: Option<f64>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    pub token: Option<String>,
+    pub direction: Option<Direction>,
+    pub since: Option<i64>,
+    pub until: Option<i64>,
+}
+
+impl Kind {
+    fn get_action(&self) -> &'static str {
+        match self {
+            Kind::Eth | Kind::Fee => "txlist",
+            Kind::Internal => "txlistinternal",
+            Kind::Erc20 => "tokentx",
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kind::Eth => write!(f, "ETH"),
+            Kind::Internal => write!(f, "Internal"),
+            Kind::Erc20 => write!(f, "ERC20"),
+            Kind::Fee => write!(f, "Fee"),
+        }
+    }
+}
+
+impl fmt::Display for Direction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Direction::In => write!(f, "in"),
+            Direction::Out => write!(f, "out"),
+        }
+    }
+}
+
+impl FromStr for Direction {
+    type Err = String;
+
+    fn from_str(direction: &str) -> Result<Direction, String> {
+        match direction.to_lowercase().as_str() {
+            "in" => Ok(Direction::In),
+            "out" => Ok(Direction::Out),
+            _ => Err(format!(
+                "Unknown direction {}, expected in or out",
+                direction
+            )),
+        }
+    }
+}
+
+impl Transfer {
+    pub fn get_historical(&self) -> Historical {
+        Historical {
+            block: self.block,
+            timestamp: self.timestamp,
+        }
+    }
+
+    // Token given by symbol or contract address
+    pub fn matches(&self, token: &str) -> bool {
+        self.symbol.eq_ignore_ascii_case(token) || self.contract_address.eq_ignore_ascii_case(token)
+    }
+
+    // Gas paid leaves the account as the native asset sent to the contract called
+    fn from_gas_spend(gas_spend: GasSpend, native_asset: &str) -> Transfer {
+        Transfer {
+            hash: gas_spend.hash,
+            block: gas_spend.block,
+            timestamp: gas_spend.timestamp,
+            kind: Kind::Fee,
+            direction: Direction::Out,
+            counterparty: gas_spend.contract_address,
+            symbol: native_asset.to_string(),
+            contract_address: ETH_ADDRESS.to_string(),
+            amount: gas_spend.eth,
+            usd_value: None,
+        }
+    }
+}
+
+impl Filter {
+    pub fn matches(&self, transfer: &Transfer) -> bool {
+        self.token
+            .as_deref()
+            .is_none_or(|token| transfer.matches(token))
+            && self
+                .direction
+                .is_none_or(|direction| transfer.direction == direction)
+            && self.since.is_none_or(|since| transfer.timestamp >= since)
+            && self.until.is_none_or(|until| transfer.timestamp <= until)
+    }
+}
+
+// Native, internal and ERC20 transfers and gas fees of the account on a chain, oldest first
+pub async fn list_transfers_for_account(
+    account_address: H160,
+    chain: &Chain,
+    verbose: bool,
+) -> Result<Vec<Transfer>, Box<dyn error::Error>> {
+    let account = format!("{:?}", account_address);
+    let mut transfers = vec![];
+
+    for kind in [Kind::Eth, Kind::Internal, Kind::Erc20] {
+        let entries =
+            fetch_entries(&account, kind.get_action(), 0, 999999999, &chain.explorer).await?;
+        if verbose {
+            println!("Fetched {} {} transactions", entries.len(), kind);
+        }
+
+        transfers.extend(
+            entries
+                .iter()
+                .filter_map(|entry| parse_transfer(entry, &account, kind, &chain.native_asset)),
+        );
+
+        if kind == Kind::Eth {
+            transfers.extend(
+                entries
+                    .iter()
+                    .filter_map(|entry| gas::parse_gas_spend(entry, &account))
+                    .filter(|gas_spend| gas_spend.eth > 0.0)
+                    .map(|gas_spend| Transfer::from_gas_spend(gas_spend, &chain.native_asset)),
+            );
+        }
+    }
+
+    transfers.sort_by_key(|transfer| transfer.timestamp);
+
+    Ok(transfers)
+}
+
+// Net amount of a token received, matches its balance when every transfer is known
+pub fn get_ledger_balance(transfers: &[Transfer], contract_address: &str) -> f64 {
+    transfers
+        .iter()
+        .filter(|transfer| {
+            transfer
+                .contract_address
+                .eq_ignore_ascii_case(contract_address)
+        })
+        .map(|transfer| match transfer.direction {
+            Direction::In => transfer.amount,
+            Direction::Out => -transfer.amount,
+        })
+        .fold(0.0, |total, amount| total + amount)
+}
+
+// Prices are looked up once per token and day
+pub async fn price_transfers(
+    transfers: &mut [Transfer],
+    price_providers: &PriceProviders,
+    verbose: bool,
+) {
+    let mut prices: HashMap<(String, String), Option<f64>> = HashMap::new();
+
+    for transfer in transfers {
+        let historical = transfer.get_historical();
+        let key = (transfer.contract_address.clone(), historical.get_date());
+
+        let price = match prices.get(&key) {
+            Some(price) => *price,
+            None => {
+                let price = pricing::get_first_price(
+                    &price_providers.get_chain(&transfer.contract_address),
+                    &transfer.contract_address,
+                    "usd",
+                    Some(&historical),
+                    verbose,
+                )
+                .await
+                .map(|(_, price)| price);
+                if price.is_none() && verbose {
+                    println!(
+                        "No price of {} on {}",
+                        transfer.symbol,
+                        historical.get_date()
+                    );
+                }
+                prices.insert(key, price);
+                price
+            }
+        };
+
+        transfer.usd_value = price.map(|price| price * transfer.amount);
+    }
+}
+
+// Most rows an Etherscan account action returns for a block range
+const MAX_ENTRIES: usize = 10000;
+
+// Transactions listed by an Etherscan account action, like txlist or tokentx, fetched again
+// from the last block returned as long as a capped page comes back
+pub async fn fetch_entries(
+    account: &str,
+    action: &str,
+    startblock: u64,
+    endblock: u64,
+    explorer: &Explorer,
+) -> Result<Vec<Value>, Box<dyn error::Error>> {
+    let mut entries = vec![];
+    let mut startblock = startblock;
+
+    loop {
+        let mut page = fetch_page(account, action, startblock, endblock, explorer).await?;
+        if page.len() < MAX_ENTRIES {
+            entries.extend(page);
+            return Ok(entries);
+        }
+
+        // The last block may be cut off, its rows come back with the next page
+        let last_block = get_block(page.last());
+        page.retain(|entry| get_block(Some(entry)) != last_block);
+        match last_block {
+            Some(last_block) if !page.is_empty() => {
+                entries.extend(page);
+                startblock = last_block;
+            }
+            _ => {
+                return Err(Box::new(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!(
+                        "More than {} {} entries of {} in block {}",
+                        MAX_ENTRIES, action, account, startblock
+                    ),
+                )))
+            }
+        }
+    }
+}
+
+async fn fetch_page(
+    account: &str,
+    action: &str,
+    startblock: u64,
+    endblock: u64,
+    explorer: &Explorer,
+) -> Result<Vec<Value>, Box<dyn error::Error>> {
+    let url = explorer.get_url(&format!(
+        "module=account&action={}&address={}&startblock={}&endblock={}&sort=asc",
+        action, account, startblock, endblock
+    ));
+    let body = reqwest::get(&url).await?.text().await?;
+    let json: Value = serde_json::from_str(&body)?;
+    let mix_selector = r#""result""#;
+
+    let results = jql::walker(&json, mix_selector)?;
+
+    // An account without transactions gets an empty result with a NOTOK status
+    match results {
+        Value::Array(entries) => Ok(entries),
+        _ => Err(Box::new(io::Error::new(
+            io::ErrorKind::ConnectionRefused,
+            format!("Error on fetching the {} list of {}", action, account),
+        ))),
+    }
+}
+
+fn get_block(entry: Option<&Value>) -> Option<u64> {
+    entry?.get("blockNumber")?.as_str()?.parse().ok()
+}
+
+fn parse_transfer(
+    entry: &Value,
+    account: &str,
+    kind: Kind,
+    native_asset: &str,
+) -> Option<Transfer> {
+    let get = |key: &str| entry.get(key).and_then(Value::as_str);
+
+    if get("isError") == Some("1") {
+        return None;
+    }
+
+    let from = get("from")?.to_lowercase();
+    let to = get("to")?.to_lowercase();
+
+    // Sending to oneself leaves the balance unchanged
+    let (direction, counterparty) = match (from == account, to == account) {
+        (true, true) => return None,
+        (false, true) => (Direction::In, from),
+        (true, false) => (Direction::Out, to),
+        (false, false) => return None,
+    };
+
+    let (symbol, contract_address, decimals) = match kind {
+        Kind::Erc20 => (
+            get("tokenSymbol")?.to_string(),
+            get("contractAddress")?.to_lowercase(),
+            get("tokenDecimal")?.parse::<i32>().ok()?,
+        ),
+        _ => (native_asset.to_string(), ETH_ADDRESS.to_string(), 18),
+    };
+
+    let amount = get("value")?.parse::<f64>().ok()? / 10_f64.powi(decimals);
+    if amount == 0.0 {
+        return None;
+    }
+
+    Some(Transfer {
+        hash: get("hash")?.to_string(),
+        block: get("blockNumber")?.parse().ok()?,
+        timestamp: get("timeStamp")?.parse().ok()?,
+        kind,
+        direction,
+        counterparty,
+        symbol,
+        contract_address,
+        amount,
+        usd_value: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+    use serde_json::json;
+
+    const ACCOUNT: &str = "0x000000000000000000000000000000000000dead";
+
+    fn get_transfer(symbol: &str, direction: Direction, timestamp: i64) -> Transfer {
+        Transfer {
+            hash: "0x1".to_string(),
+            block: 18908894,
+            timestamp,
+            kind: Kind::Erc20,
+            direction,
+            counterparty: "0x0000000000000000000000000000000000000001".to_string(),
+            symbol: symbol.to_string(),
+            contract_address: format!("0x{}", symbol.to_lowercase()),
+            amount: 1.0,
+            usd_value: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn parse_transfer_erc20_success() {
+        let entry = json!({
+            "blockNumber": "18908894",
+            "timeStamp": "1704067199",
+            "hash": "0xabc",
+            "from": "0x0000000000000000000000000000000000000001",
+            "to": "0x000000000000000000000000000000000000dEaD",
+            "contractAddress": "0x6B175474E89094C44Da98b954EedeAC495271d0F",
+            "value": "1500000000000000000",
+            "tokenSymbol": "DAI",
+            "tokenDecimal": "18"
+        });
+
+        let transfer = parse_transfer(&entry, ACCOUNT, Kind::Erc20, "ETH").unwrap();
+        assert_eq!(transfer.direction, Direction::In);
+        assert_eq!(
+            transfer.counterparty,
+            "0x0000000000000000000000000000000000000001"
+        );
+        assert_eq!(
+            transfer.contract_address,
+            "0x6b175474e89094c44da98b954eedeac495271d0f"
+        );
+        assert_eq!(transfer.amount, 1.5);
+        assert_eq!(transfer.timestamp, 1704067199);
+    }
+
+    #[tokio::test]
+    async fn parse_transfer_eth_success() {
+        let entry = json!({
+            "blockNumber": "18908894",
+            "timeStamp": "1704067199",
+            "hash": "0xabc",
+            "from": ACCOUNT,
+            "to": "0x0000000000000000000000000000000000000001",
+            "value": "2000000000000000000",
+            "isError": "0"
+        });
+
+        let transfer = parse_transfer(&entry, ACCOUNT, Kind::Eth, "POL").unwrap();
+        assert_eq!(transfer.direction, Direction::Out);
+        assert_eq!(transfer.symbol, "POL");
+        assert_eq!(transfer.contract_address, ETH_ADDRESS);
+        assert_eq!(transfer.amount, 2.0);
+    }
+
+    #[tokio::test]
+    async fn parse_transfer_skipped_success() {
+        let failed = json!({
+            "blockNumber": "18908894",
+            "timeStamp": "1704067199",
+            "hash": "0xabc",
+            "from": ACCOUNT,
+            "to": "0x0000000000000000000000000000000000000001",
+            "value": "2000000000000000000",
+            "isError": "1"
+        });
+        assert_eq!(parse_transfer(&failed, ACCOUNT, Kind::Eth, "ETH"), None);
+
+        let contract_call = json!({
+            "blockNumber": "18908894",
+            "timeStamp": "1704067199",
+            "hash": "0xabc",
+            "from": ACCOUNT,
+            "to": "0x0000000000000000000000000000000000000001",
+            "value": "0",
+            "isError": "0"
+        });
+        assert_eq!(
+            parse_transfer(&contract_call, ACCOUNT, Kind::Eth, "ETH"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn from_gas_spend_success() {
+        let entry = json!({
+            "blockNumber": "18908894",
+            "timeStamp": "1704067199",
+            "hash": "0xabc",
+            "from": ACCOUNT,
+            "to": "0x0000000000000000000000000000000000000001",
+            "contractAddress": "",
+            "value": "0",
+            "gasUsed": "21000",
+            "gasPrice": "20000000000",
+            "isError": "1"
+        });
+
+        let transfer =
+            Transfer::from_gas_spend(gas::parse_gas_spend(&entry, ACCOUNT).unwrap(), "POL");
+        assert_eq!(transfer.kind, Kind::Fee);
+        assert_eq!(transfer.symbol, "POL");
+        assert_eq!(transfer.direction, Direction::Out);
+        assert_eq!(transfer.contract_address, ETH_ADDRESS);
+        assert_eq!(transfer.amount, 0.00042);
+    }
+
+    #[tokio::test]
+    async fn get_ledger_balance_success() {
+        let mut eth_in = get_transfer("ETH", Direction::In, 1);
+        eth_in.contract_address = ETH_ADDRESS.to_string();
+        eth_in.amount = 2.0;
+        let mut fee = get_transfer("ETH", Direction::Out, 2);
+        fee.kind = Kind::Fee;
+        fee.contract_address = ETH_ADDRESS.to_string();
+        fee.amount = 0.5;
+        let dai_in = get_transfer("DAI", Direction::In, 3);
+
+        let transfers = vec![eth_in, fee, dai_in];
+        assert_eq!(get_ledger_balance(&transfers, ETH_ADDRESS), 1.5);
+        assert_eq!(get_ledger_balance(&transfers, "0xDAI"), 1.0);
+    }
+
+    #[tokio::test]
+    async fn filter_success() {
+        let filter = Filter {
+            token: Some("dai".to_string()),
+            direction: Some(Direction::In),
+            since: Some(1704067199),
+            until: None,
+        };
+
+        assert!(filter.matches(&get_transfer("DAI", Direction::In, 1704067199)));
+        assert!(!filter.matches(&get_transfer("DAI", Direction::Out, 1704067199)));
+        assert!(!filter.matches(&get_transfer("DAI", Direction::In, 1704067198)));
+        assert!(!filter.matches(&get_transfer("YFI", Direction::In, 1704067199)));
+        assert!(Filter::default().matches(&get_transfer("YFI", Direction::Out, 0)));
+    }
+
+    #[tokio::test]
+    async fn direction_from_str_fail() {
+        let result = "sideways".parse::<Direction>();
+        assert_eq!(
+            result,
+            Err("Unknown direction sideways, expected in or out".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn list_transfers_for_account_success() {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_etherscan_api_key = settings
+            .get::<String>("test_etherscan")
+            .unwrap_or_else(|_| panic!("test etherscan key is not set in Settings.toml, exit."));
+
+        // Vitalik's address
+        let account_address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045"
+            .parse::<H160>()
+            .unwrap();
+
+        let chain = Chain {
+            explorer: Explorer::etherscan(&test_etherscan_api_key),
+            ..Default::default()
+        };
+
+        let transfers = list_transfers_for_account(account_address, &chain, false)
+            .await
+            .unwrap();
+        assert!(!transfers.is_empty());
+        assert!(transfers
+            .windows(2)
+            .all(|pair| pair[0].timestamp <= pair[1].timestamp));
+    }
+}
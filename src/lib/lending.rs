@@ -0,0 +1,651 @@
+use super::historical::Historical;
+use super::onchain;
+use super::price_provider::ETH_ADDRESS;
+use super::pricing::{self, PriceProviders};
+use std::error;
+use web3::ethabi::{ParamType, Token};
+use web3::transports::Http;
+use web3::types::{BlockId, H160, U256};
+use web3::Web3;
+
+const AAVE_V3_POOL: &str = "0x87870bca3f3fd6335c3f4ce8392d69350b4fa4e2";
+const COMPOUND_V2_COMPTROLLER: &str = "0x3d9819210a31b4961b30ef54be2aed79b9c9cd3b";
+const COMPOUND_V2_CETH: &str = "0x4ddc2d193948926d02f9b1fe9e1daa0718270ed5";
+// Compound V3 markets of USDC, WETH and USDT, each with its own collaterals
+const COMPOUND_V3_COMETS: [&str; 3] = [
+    "0xc3d688b66703497daa19211eedff47f25384cdc3",
+    "0xa17581a9e3356d9a858b789d68b4d866e593ae94",
+    "0x3afdc9bca9213a35503b077a6072f3d0d5ab0840",
+];
+
+// Asset supplied to or borrowed from a lending protocol
+#[derive(Debug, Clone, PartialEq)]
+pub struct LendingPosition {
+    pub symbol: String,
+    pub contract_address: String,
+    pub supplied: f64,
+    pub borrowed: f64,
+    pub usd_price: Option<f64>,
+}
+
+// Positions of the account in a protocol, it can be liquidated once the health factor is below 1
+#[derive(Debug, Clone, PartialEq)]
+pub struct Protocol {
+    pub name: String,
+    pub positions: Vec<LendingPosition>,
+    pub health_factor: Option<f64>,
+    // aTokens, debt tokens and cTokens the positions are held as
+    pub receipt_tokens: Vec<String>,
+}
+
+impl LendingPosition {
+    pub fn get_supplied_usd(&self) -> Option<f64> {
+        self.usd_price.map(|usd_price| usd_price * self.supplied)
+    }
+
+    pub fn get_borrowed_usd(&self) -> Option<f64> {
+        self.usd_price.map(|usd_price| usd_price * self.borrowed)
+    }
+}
+
+// Totals are unknown as soon as one supply or debt has no price, an unpriced debt would
+// overstate the net worth otherwise
+impl Protocol {
+    pub fn get_supplied_usd(&self) -> Option<f64> {
+        self.positions
+            .iter()
+            .filter(|position| position.supplied > 0.0)
+            .try_fold(0.0, |total, position| {
+                Some(total + position.get_supplied_usd()?)
+            })
+    }
+
+    pub fn get_borrowed_usd(&self) -> Option<f64> {
+        self.positions
+            .iter()
+            .filter(|position| position.borrowed > 0.0)
+            .try_fold(0.0, |total, position| {
+                Some(total + position.get_borrowed_usd()?)
+            })
+    }
+
+    pub fn get_net_usd(&self) -> Option<f64> {
+        Some(self.get_supplied_usd()? - self.get_borrowed_usd()?)
+    }
+}
+
+// Aave V3, Compound V2 and Compound V3 positions on Ethereum mainnet, a protocol failing to
+// answer, like one not deployed yet at the historical block, is skipped
+pub async fn list_lending_for_account(
+    web3: &Web3<Http>,
+    account_address: H160,
+    historical: Option<&Historical>,
+    verbose: bool,
+) -> Vec<Protocol> {
+    let block = historical.map(Historical::get_block_id);
+    let mut results = vec![
+        (
+            "Aave V3".to_string(),
+            list_aave_v3(web3, account_address, block).await,
+        ),
+        (
+            "Compound V2".to_string(),
+            list_compound_v2(web3, account_address, block).await,
+        ),
+    ];
+    for comet in COMPOUND_V3_COMETS {
+        results.push((
+            format!("Compound V3 {}", comet),
+            list_compound_v3(web3, comet, account_address, block).await,
+        ));
+    }
+
+    let mut protocols = vec![];
+
+    for (name, result) in results {
+        match result {
+            Ok(Some(protocol)) => protocols.push(protocol),
+            Ok(None) => (),
+            Err(err) => {
+                if verbose {
+                    println!("Failed to read {} positions: {}", name, err);
+                }
+            }
+        }
+    }
+
+    protocols
+}
+
+// US$ prices of the underlying assets, at the historical block when given
+pub async fn price_protocols(
+    protocols: &mut [Protocol],
+    price_providers: &PriceProviders,
+    historical: Option<&Historical>,
+    verbose: bool,
+) {
+    for protocol in protocols {
+        for position in &mut protocol.positions {
+            position.usd_price = pricing::get_first_price(
+                &price_providers.get_chain(&position.contract_address),
+                &position.contract_address,
+                "usd",
+                historical,
+                verbose,
+            )
+            .await
+            .map(|(_, usd_price)| usd_price);
+        }
+    }
+}
+
+pub fn get_net_usd_total(protocols: &[Protocol]) -> Option<f64> {
+    protocols
+        .iter()
+        .try_fold(0.0, |total, protocol| Some(total + protocol.get_net_usd()?))
+}
+
+// Collateral weighted by its liquidation threshold over the debt, none without debt
+pub fn get_health_factor(weighted_collateral: f64, debt: f64) -> Option<f64> {
+    match debt > 0.0 {
+        true => Some(weighted_collateral / debt),
+        false => None,
+    }
+}
+
+// Reserves are only read when the account has collateral or debt, supplies not used as
+// collateral are missed otherwise
+async fn list_aave_v3(
+    web3: &Web3<Http>,
+    account_address: H160,
+    block: Option<BlockId>,
+) -> Result<Option<Protocol>, Box<dyn error::Error>> {
+    let pool = AAVE_V3_POOL.parse::<H160>()?;
+
+    // Collateral, debt, available borrows, liquidation threshold, ltv and health factor
+    let account_data = onchain::call(
+        web3,
+        pool,
+        "getUserAccountData(address)",
+        &[Token::Address(account_address)],
+        &vec![ParamType::Uint(256); 6],
+        block,
+    )
+    .await?;
+    let total_collateral = onchain::get_uint(&account_data, 0)?;
+    let total_debt = onchain::get_uint(&account_data, 1)?;
+    if total_collateral.is_zero() && total_debt.is_zero() {
+        return Ok(None);
+    }
+
+    let reserves = onchain::call(
+        web3,
+        pool,
+        "getReservesList()",
+        &[],
+        &[ParamType::Array(Box::new(ParamType::Address))],
+        block,
+    )
+    .await?;
+    let reserves = match reserves.into_iter().next() {
+        Some(Token::Array(reserves)) => reserves,
+        _ => return Err("Could not read the Aave V3 reserves".into()),
+    };
+
+    // ReserveData fields up to the aToken, stable debt token and variable debt token addresses
+    let mut reserve_data_outputs = vec![ParamType::Uint(256); 8];
+    reserve_data_outputs.extend(vec![ParamType::Address; 4]);
+    reserve_data_outputs.extend(vec![ParamType::Uint(128); 3]);
+
+    let mut protocol = Protocol {
+        name: "Aave V3".to_string(),
+        positions: vec![],
+        health_factor: match total_debt.is_zero() {
+            true => None,
+            false => Some(onchain::scale(onchain::get_uint(&account_data, 5)?, 18)),
+        },
+        receipt_tokens: vec![],
+    };
+
+    for asset in reserves.into_iter().filter_map(Token::into_address) {
+        // A reserve failing to answer, like one listed after the historical block, is skipped
+        let reserve_data = match onchain::call(
+            web3,
+            pool,
+            "getReserveData(address)",
+            &[Token::Address(asset)],
+            &reserve_data_outputs,
+            block,
+        )
+        .await
+        {
+            Ok(reserve_data) => reserve_data,
+            Err(_) => continue,
+        };
+        let a_token = onchain::get_address(&reserve_data, 8)?;
+        let variable_debt_token = onchain::get_address(&reserve_data, 10)?;
+        protocol.receipt_tokens.push(format!("{:?}", a_token));
+        protocol
+            .receipt_tokens
+            .push(format!("{:?}", variable_debt_token));
+
+        // Stable rate borrowing is disabled on Aave V3
+        let supplied = get_balance(web3, a_token, account_address, block).await?;
+        let borrowed = get_balance(web3, variable_debt_token, account_address, block).await?;
+        if supplied.is_zero() && borrowed.is_zero() {
+            continue;
+        }
+
+        let (symbol, decimals) = get_token(web3, asset).await?;
+        protocol.positions.push(LendingPosition {
+            symbol,
+            contract_address: format!("{:?}", asset),
+            supplied: onchain::scale(supplied, decimals),
+            borrowed: onchain::scale(borrowed, decimals),
+            usd_price: None,
+        });
+    }
+
+    Ok(Some(protocol))
+}
+
+// Supplies are cToken balances times their exchange rate, the health factor is worked out from
+// the account liquidity and the debt valued by the oracle of the comptroller
+async fn list_compound_v2(
+    web3: &Web3<Http>,
+    account_address: H160,
+    block: Option<BlockId>,
+) -> Result<Option<Protocol>, Box<dyn error::Error>> {
+    let comptroller = COMPOUND_V2_COMPTROLLER.parse::<H160>()?;
+
+    let markets = onchain::call(
+        web3,
+        comptroller,
+        "getAllMarkets()",
+        &[],
+        &[ParamType::Array(Box::new(ParamType::Address))],
+        block,
+    )
+    .await?;
+    let markets = match markets.into_iter().next() {
+        Some(Token::Array(markets)) => markets,
+        _ => return Err("Could not read the Compound V2 markets".into()),
+    };
+
+    let oracle = onchain::call(
+        web3,
+        comptroller,
+        "oracle()",
+        &[],
+        &[ParamType::Address],
+        block,
+    )
+    .await?;
+    let oracle = onchain::get_address(&oracle, 0)?;
+
+    let mut protocol = Protocol {
+        name: "Compound V2".to_string(),
+        positions: vec![],
+        health_factor: None,
+        receipt_tokens: vec![],
+    };
+    let mut borrowed_usd = 0.0;
+
+    for c_token in markets.into_iter().filter_map(Token::into_address) {
+        protocol.receipt_tokens.push(format!("{:?}", c_token));
+
+        // Error code, cToken balance, borrow balance and exchange rate scaled by 1e18
+        let snapshot = onchain::call(
+            web3,
+            c_token,
+            "getAccountSnapshot(address)",
+            &[Token::Address(account_address)],
+            &vec![ParamType::Uint(256); 4],
+            block,
+        )
+        .await?;
+        let c_token_balance = onchain::get_uint(&snapshot, 1)?;
+        let borrowed = onchain::get_uint(&snapshot, 2)?;
+        if c_token_balance.is_zero() && borrowed.is_zero() {
+            continue;
+        }
+        let supplied = onchain::u256_to_f64(c_token_balance)
+            * onchain::scale(onchain::get_uint(&snapshot, 3)?, 18);
+
+        let (symbol, contract_address, decimals) =
+            match format!("{:?}", c_token) == COMPOUND_V2_CETH {
+                true => ("ETH".to_string(), ETH_ADDRESS.to_string(), 18),
+                false => {
+                    let underlying = onchain::call(
+                        web3,
+                        c_token,
+                        "underlying()",
+                        &[],
+                        &[ParamType::Address],
+                        block,
+                    )
+                    .await?;
+                    let underlying = onchain::get_address(&underlying, 0)?;
+                    let (symbol, decimals) = get_token(web3, underlying).await?;
+                    (symbol, format!("{:?}", underlying), decimals)
+                }
+            };
+
+        if !borrowed.is_zero() {
+            // Underlying price scaled by 1e36 over the underlying decimals
+            let underlying_price = onchain::call(
+                web3,
+                oracle,
+                "getUnderlyingPrice(address)",
+                &[Token::Address(c_token)],
+                &[ParamType::Uint(256)],
+                block,
+            )
+            .await?;
+            borrowed_usd += onchain::u256_to_f64(borrowed)
+                * onchain::scale(onchain::get_uint(&underlying_price, 0)?, 36);
+        }
+
+        protocol.positions.push(LendingPosition {
+            symbol,
+            contract_address,
+            supplied: supplied / 10_f64.powi(decimals as i32),
+            borrowed: onchain::scale(borrowed, decimals),
+            usd_price: None,
+        });
+    }
+
+    if protocol.positions.is_empty() {
+        return Ok(None);
+    }
+
+    if borrowed_usd > 0.0 {
+        // Error code, liquidity and shortfall in US$ scaled by 1e18
+        let liquidity = onchain::call(
+            web3,
+            comptroller,
+            "getAccountLiquidity(address)",
+            &[Token::Address(account_address)],
+            &vec![ParamType::Uint(256); 3],
+            block,
+        )
+        .await?;
+        let weighted_collateral = borrowed_usd
+            + onchain::scale(onchain::get_uint(&liquidity, 1)?, 18)
+            - onchain::scale(onchain::get_uint(&liquidity, 2)?, 18);
+        protocol.health_factor = get_health_factor(weighted_collateral, borrowed_usd);
+    }
+
+    Ok(Some(protocol))
+}
+
+// The base asset is either supplied or borrowed, collaterals only back the borrow
+async fn list_compound_v3(
+    web3: &Web3<Http>,
+    comet: &str,
+    account_address: H160,
+    block: Option<BlockId>,
+) -> Result<Option<Protocol>, Box<dyn error::Error>> {
+    let comet = comet.parse::<H160>()?;
+
+    let base_token = onchain::call(
+        web3,
+        comet,
+        "baseToken()",
+        &[],
+        &[ParamType::Address],
+        block,
+    )
+    .await?;
+    let base_token = onchain::get_address(&base_token, 0)?;
+    let (base_symbol, base_decimals) = get_token(web3, base_token).await?;
+
+    let supplied = get_balance(web3, comet, account_address, block).await?;
+    let borrowed = onchain::call(
+        web3,
+        comet,
+        "borrowBalanceOf(address)",
+        &[Token::Address(account_address)],
+        &[ParamType::Uint(256)],
+        block,
+    )
+    .await?;
+    let borrowed = onchain::scale(onchain::get_uint(&borrowed, 0)?, base_decimals);
+
+    let mut protocol = Protocol {
+        name: format!("Compound V3 {}", base_symbol),
+        positions: vec![],
+        health_factor: None,
+        receipt_tokens: vec![format!("{:?}", comet)],
+    };
+    if !supplied.is_zero() || borrowed > 0.0 {
+        protocol.positions.push(LendingPosition {
+            symbol: base_symbol,
+            contract_address: format!("{:?}", base_token),
+            supplied: onchain::scale(supplied, base_decimals),
+            borrowed,
+            usd_price: None,
+        });
+    }
+
+    let num_assets = onchain::call(
+        web3,
+        comet,
+        "numAssets()",
+        &[],
+        &[ParamType::Uint(8)],
+        block,
+    )
+    .await?;
+    let mut weighted_collateral = 0.0;
+
+    for index in 0..onchain::get_uint(&num_assets, 0)?.low_u32() {
+        // Offset, asset, price feed, scale, borrow, liquidate and liquidation factors, supply cap
+        let asset_info = onchain::call(
+            web3,
+            comet,
+            "getAssetInfo(uint8)",
+            &[Token::Uint(U256::from(index))],
+            &[
+                ParamType::Uint(8),
+                ParamType::Address,
+                ParamType::Address,
+                ParamType::Uint(64),
+                ParamType::Uint(64),
+                ParamType::Uint(64),
+                ParamType::Uint(64),
+                ParamType::Uint(128),
+            ],
+            block,
+        )
+        .await?;
+        let asset = onchain::get_address(&asset_info, 1)?;
+
+        let collateral = onchain::call(
+            web3,
+            comet,
+            "collateralBalanceOf(address,address)",
+            &[Token::Address(account_address), Token::Address(asset)],
+            &[ParamType::Uint(128)],
+            block,
+        )
+        .await?;
+        let collateral = onchain::get_uint(&collateral, 0)?;
+        if collateral.is_zero() {
+            continue;
+        }
+        let collateral = onchain::u256_to_f64(collateral)
+            / onchain::u256_to_f64(onchain::get_uint(&asset_info, 3)?);
+
+        if borrowed > 0.0 {
+            let price =
+                get_comet_price(web3, comet, onchain::get_address(&asset_info, 2)?, block).await?;
+            weighted_collateral +=
+                collateral * price * onchain::scale(onchain::get_uint(&asset_info, 5)?, 18);
+        }
+
+        let (symbol, _) = get_token(web3, asset).await?;
+        protocol.positions.push(LendingPosition {
+            symbol,
+            contract_address: format!("{:?}", asset),
+            supplied: collateral,
+            borrowed: 0.0,
+            usd_price: None,
+        });
+    }
+
+    if protocol.positions.is_empty() {
+        return Ok(None);
+    }
+
+    if borrowed > 0.0 {
+        let base_price_feed = onchain::call(
+            web3,
+            comet,
+            "baseTokenPriceFeed()",
+            &[],
+            &[ParamType::Address],
+            block,
+        )
+        .await?;
+        let base_price = get_comet_price(
+            web3,
+            comet,
+            onchain::get_address(&base_price_feed, 0)?,
+            block,
+        )
+        .await?;
+        protocol.health_factor = get_health_factor(weighted_collateral, borrowed * base_price);
+    }
+
+    Ok(Some(protocol))
+}
+
+// Prices of a Comet share one unit with 8 decimals, US$ or ETH depending on the market
+async fn get_comet_price(
+    web3: &Web3<Http>,
+    comet: H160,
+    price_feed: H160,
+    block: Option<BlockId>,
+) -> Result<f64, Box<dyn error::Error>> {
+    let price = onchain::call(
+        web3,
+        comet,
+        "getPrice(address)",
+        &[Token::Address(price_feed)],
+        &[ParamType::Uint(256)],
+        block,
+    )
+    .await?;
+
+    Ok(onchain::scale(onchain::get_uint(&price, 0)?, 8))
+}
+
+async fn get_balance(
+    web3: &Web3<Http>,
+    contract_address: H160,
+    account_address: H160,
+    block: Option<BlockId>,
+) -> Result<U256, Box<dyn error::Error>> {
+    let result = onchain::call(
+        web3,
+        contract_address,
+        "balanceOf(address)",
+        &[Token::Address(account_address)],
+        &[ParamType::Uint(256)],
+        block,
+    )
+    .await?;
+
+    onchain::get_uint(&result, 0)
+}
+
+// Some tokens like MKR return their symbol as bytes32, the address stands in for it
+async fn get_token(
+    web3: &Web3<Http>,
+    contract_address: H160,
+) -> Result<(String, u32), Box<dyn error::Error>> {
+    let symbol = onchain::get_symbol(web3, contract_address)
+        .await
+        .unwrap_or_else(|_| format!("{:?}", contract_address));
+    let decimals = onchain::get_decimals(web3, contract_address).await?;
+
+    Ok((symbol, decimals))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+
+    fn get_position(supplied: f64, borrowed: f64, usd_price: Option<f64>) -> LendingPosition {
+        LendingPosition {
+            symbol: "WETH".to_string(),
+            contract_address: "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2".to_string(),
+            supplied,
+            borrowed,
+            usd_price,
+        }
+    }
+
+    #[tokio::test]
+    async fn get_net_usd_success() {
+        let protocol = Protocol {
+            name: "Aave V3".to_string(),
+            positions: vec![
+                get_position(2.0, 0.0, Some(2000.0)),
+                get_position(0.0, 1500.0, Some(1.0)),
+                get_position(0.0, 0.0, None),
+            ],
+            health_factor: Some(2.2),
+            receipt_tokens: vec![],
+        };
+
+        assert_eq!(protocol.get_supplied_usd(), Some(4000.0));
+        assert_eq!(protocol.get_borrowed_usd(), Some(1500.0));
+        assert_eq!(protocol.get_net_usd(), Some(2500.0));
+        assert_eq!(
+            get_net_usd_total(&[protocol.clone(), protocol.clone()]),
+            Some(5000.0)
+        );
+        assert_eq!(get_net_usd_total(&[]), Some(0.0));
+        assert!(get_net_usd_total(&[]).unwrap().is_sign_positive());
+
+        // Unpriced debt
+        let mut unpriced = protocol.clone();
+        unpriced.positions[1].usd_price = None;
+        assert_eq!(unpriced.get_supplied_usd(), Some(4000.0));
+        assert_eq!(unpriced.get_borrowed_usd(), None);
+        assert_eq!(unpriced.get_net_usd(), None);
+        assert_eq!(get_net_usd_total(&[protocol, unpriced]), None);
+    }
+
+    #[tokio::test]
+    async fn get_health_factor_success() {
+        assert_eq!(get_health_factor(3000.0, 1500.0), Some(2.0));
+        assert_eq!(get_health_factor(3000.0, 0.0), None);
+    }
+
+    #[tokio::test]
+    async fn list_lending_for_account_success() {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_infura_key = settings
+            .get::<String>("test_infura")
+            .unwrap_or_else(|_| panic!("test infura key is not set in Settings.toml, exit."));
+
+        let endpoint = format!("https://mainnet.infura.io/v3/{}", test_infura_key);
+        let web3 = Web3::new(Http::new(&endpoint).unwrap());
+
+        // Vitalik's address
+        let account_address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045"
+            .parse::<H160>()
+            .unwrap();
+
+        let protocols = list_lending_for_account(&web3, account_address, None, false).await;
+        assert!(protocols
+            .iter()
+            .all(|protocol| !protocol.positions.is_empty()));
+    }
+}
@@ -1,5 +1,27 @@
+pub mod chain;
+pub mod chainlink;
+pub mod chart;
 pub mod coingecko;
+pub mod consensus;
+pub mod cost_basis;
+pub mod custom;
+pub mod defillama;
+pub mod diff;
+pub mod erc1155;
 pub mod erc20;
+pub mod fiat;
+pub mod gas;
+pub mod historical;
+pub mod history;
+pub mod lending;
+pub mod nft;
+pub mod onchain;
 pub mod paraswap;
 pub mod price_provider;
+pub mod pricing;
+pub mod quote;
 pub mod random;
+pub mod snapshot;
+pub mod staking;
+pub mod tax;
+pub mod uniswap;
@@ -0,0 +1,235 @@
+use super::chain::Explorer;
+use super::coingecko::Coingecko;
+use super::historical::Historical;
+use super::history;
+use super::onchain;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::error;
+use web3::ethabi::{ParamType, Token};
+use web3::transports::Http;
+use web3::types::{H160, U256};
+use web3::Web3;
+
+// ERC721 token held by the account
+#[derive(Debug, Clone, PartialEq)]
+pub struct Nft {
+    pub collection: String,
+    pub symbol: String,
+    pub contract_address: String,
+    pub token_id: U256,
+    pub token_uri: Option<String>,
+    pub floor_usd: Option<f64>,
+}
+
+// Discovered from Etherscan transfers, kept only when ownerOf confirms the account holds it
+pub async fn list_erc721_for_account(
+    web3: &Web3<Http>,
+    account_address: H160,
+    explorer: &Explorer,
+    historical: Option<&Historical>,
+    verbose: bool,
+) -> Result<Vec<Nft>, Box<dyn error::Error>> {
+    let account = format!("{:?}", account_address);
+    let endblock = historical.map_or(999999999, |historical| historical.block);
+
+    let entries = history::fetch_entries(&account, "tokennfttx", 0, endblock, explorer).await?;
+    if verbose {
+        println!("Fetched {} ERC721 transfers", entries.len());
+    }
+
+    let block = historical.map(Historical::get_block_id);
+    let mut nfts = vec![];
+
+    for mut nft in get_received_nfts(&entries, &account) {
+        let contract_address = nft.contract_address.parse::<H160>()?;
+
+        let owner = onchain::call(
+            web3,
+            contract_address,
+            "ownerOf(uint256)",
+            &[Token::Uint(nft.token_id)],
+            &[ParamType::Address],
+            block,
+        )
+        .await
+        .and_then(|result| onchain::get_address(&result, 0));
+        match owner {
+            Ok(owner) if owner == account_address => (),
+            _ => {
+                if verbose {
+                    println!(
+                        "{} #{} is no longer held, skipping",
+                        nft.collection, nft.token_id
+                    );
+                }
+                continue;
+            }
+        }
+
+        nft.token_uri = onchain::call(
+            web3,
+            contract_address,
+            "tokenURI(uint256)",
+            &[Token::Uint(nft.token_id)],
+            &[ParamType::String],
+            block,
+        )
+        .await
+        .and_then(|result| onchain::get_string(&result, 0))
+        .ok();
+
+        nfts.push(nft);
+    }
+
+    Ok(nfts)
+}
+
+// Floor prices are looked up once per collection
+pub async fn price_nfts(nfts: &mut [Nft], coingecko: &Coingecko, verbose: bool) {
+    let mut floor_prices: HashMap<String, Option<f64>> = HashMap::new();
+
+    for nft in nfts {
+        let floor_usd = match floor_prices.get(&nft.contract_address) {
+            Some(floor_usd) => *floor_usd,
+            None => {
+                let floor_usd = coingecko
+                    .get_nft_floor_price(&nft.contract_address, "usd", verbose)
+                    .await
+                    .ok();
+                floor_prices.insert(nft.contract_address.to_string(), floor_usd);
+                floor_usd
+            }
+        };
+
+        nft.floor_usd = floor_usd;
+    }
+}
+
+pub fn get_floor_usd_total(nfts: &[Nft]) -> f64 {
+    nfts.iter()
+        .filter_map(|nft| nft.floor_usd)
+        .fold(0.0, |total, floor_usd| total + floor_usd)
+}
+
+// Tokens whose latest transfer went to the account, oldest transfers first
+fn get_received_nfts(entries: &[Value], account: &str) -> Vec<Nft> {
+    let mut nfts: Vec<Nft> = vec![];
+
+    for entry in entries {
+        let nft = match parse_nft(entry) {
+            Some(nft) => nft,
+            None => continue,
+        };
+        let to = entry.get("to").and_then(Value::as_str).unwrap_or_default();
+
+        nfts.retain(|held| {
+            held.contract_address != nft.contract_address || held.token_id != nft.token_id
+        });
+        if to.eq_ignore_ascii_case(account) {
+            nfts.push(nft);
+        }
+    }
+
+    nfts
+}
+
+fn parse_nft(entry: &Value) -> Option<Nft> {
+    let get = |key: &str| entry.get(key).and_then(Value::as_str);
+
+    Some(Nft {
+        collection: get("tokenName")?.to_string(),
+        symbol: get("tokenSymbol")?.to_string(),
+        contract_address: get("contractAddress")?.to_lowercase(),
+        token_id: U256::from_dec_str(get("tokenID")?).ok()?,
+        token_uri: None,
+        floor_usd: None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+    use serde_json::json;
+
+    const ACCOUNT: &str = "0x000000000000000000000000000000000000dead";
+
+    fn get_entry(from: &str, to: &str, token_id: &str) -> Value {
+        json!({
+            "blockNumber": "18908894",
+            "timeStamp": "1704067199",
+            "hash": "0xabc",
+            "from": from,
+            "to": to,
+            "contractAddress": "0xBC4CA0EdA7647A8aB7C2061c2E118A18a936f13D",
+            "tokenID": token_id,
+            "tokenName": "BoredApeYachtClub",
+            "tokenSymbol": "BAYC",
+            "tokenDecimal": "0"
+        })
+    }
+
+    #[tokio::test]
+    async fn get_received_nfts_success() {
+        let other = "0x0000000000000000000000000000000000000001";
+        let entries = vec![
+            get_entry(other, ACCOUNT, "1"),
+            get_entry(other, ACCOUNT, "2"),
+            get_entry(ACCOUNT, other, "1"),
+            get_entry(other, "0x000000000000000000000000000000000000dEaD", "3"),
+        ];
+
+        let nfts = get_received_nfts(&entries, ACCOUNT);
+        assert_eq!(nfts.len(), 2);
+        assert_eq!(nfts[0].token_id, U256::from(2));
+        assert_eq!(nfts[0].collection, "BoredApeYachtClub");
+        assert_eq!(
+            nfts[0].contract_address,
+            "0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d"
+        );
+        assert_eq!(nfts[1].token_id, U256::from(3));
+    }
+
+    #[tokio::test]
+    async fn get_floor_usd_total_success() {
+        let mut nfts = get_received_nfts(
+            &[
+                get_entry("0x0000000000000000000000000000000000000001", ACCOUNT, "1"),
+                get_entry("0x0000000000000000000000000000000000000001", ACCOUNT, "2"),
+            ],
+            ACCOUNT,
+        );
+        nfts[0].floor_usd = Some(50000.0);
+
+        assert_eq!(get_floor_usd_total(&nfts), 50000.0);
+    }
+
+    #[tokio::test]
+    async fn list_erc721_for_account_success() {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_infura_key = settings
+            .get::<String>("test_infura")
+            .unwrap_or_else(|_| panic!("test infura key is not set in Settings.toml, exit."));
+        let test_etherscan_api_key = settings
+            .get::<String>("test_etherscan")
+            .unwrap_or_else(|_| panic!("test etherscan key is not set in Settings.toml, exit."));
+
+        let endpoint = format!("https://mainnet.infura.io/v3/{}", test_infura_key);
+        let web3 = Web3::new(Http::new(&endpoint).unwrap());
+
+        // Vitalik's address
+        let account_address = "0xd8dA6BF26964aF9D7eEd9e03E53415D37aA96045"
+            .parse::<H160>()
+            .unwrap();
+
+        let explorer = Explorer::etherscan(&test_etherscan_api_key);
+
+        let nfts = list_erc721_for_account(&web3, account_address, &explorer, None, false)
+            .await
+            .unwrap();
+        assert!(!nfts.is_empty());
+    }
+}
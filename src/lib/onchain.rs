@@ -0,0 +1,190 @@
+use std::error;
+use web3::ethabi::{self, ParamType, Token};
+use web3::signing::keccak256;
+use web3::transports::Http;
+use web3::types::{BlockId, Bytes, CallRequest, H160, U256};
+use web3::Web3;
+
+pub async fn call(
+    web3: &Web3<Http>,
+    contract_address: H160,
+    signature: &str,
+    inputs: &[Token],
+    outputs: &[ParamType],
+    block: Option<BlockId>,
+) -> Result<Vec<Token>, Box<dyn error::Error>> {
+    let mut data = keccak256(signature.as_bytes())[..4].to_vec();
+    data.extend(ethabi::encode(inputs));
+
+    let request = CallRequest {
+        to: Some(contract_address),
+        data: Some(Bytes(data)),
+        ..Default::default()
+    };
+    let result = web3.eth().call(request, block).await?;
+
+    if result.0.is_empty() {
+        return Err(format!(
+            "Empty response calling {} on contract {:?}",
+            signature, contract_address
+        )
+        .into());
+    }
+
+    Ok(ethabi::decode(outputs, &result.0)?)
+}
+
+pub async fn get_decimals(
+    web3: &Web3<Http>,
+    contract_address: H160,
+) -> Result<u32, Box<dyn error::Error>> {
+    let result = call(
+        web3,
+        contract_address,
+        "decimals()",
+        &[],
+        &[ParamType::Uint(8)],
+        None,
+    )
+    .await?;
+
+    Ok(get_uint(&result, 0)?.low_u32())
+}
+
+pub async fn get_symbol(
+    web3: &Web3<Http>,
+    contract_address: H160,
+) -> Result<String, Box<dyn error::Error>> {
+    let result = call(
+        web3,
+        contract_address,
+        "symbol()",
+        &[],
+        &[ParamType::String],
+        None,
+    )
+    .await?;
+
+    get_string(&result, 0)
+}
+
+pub fn get_uint(tokens: &[Token], index: usize) -> Result<U256, Box<dyn error::Error>> {
+    tokens
+        .get(index)
+        .cloned()
+        .and_then(Token::into_uint)
+        .ok_or_else(|| format!("Expected uint at output {}", index).into())
+}
+
+pub fn get_int(tokens: &[Token], index: usize) -> Result<U256, Box<dyn error::Error>> {
+    tokens
+        .get(index)
+        .cloned()
+        .and_then(Token::into_int)
+        .ok_or_else(|| format!("Expected int at output {}", index).into())
+}
+
+pub fn get_string(tokens: &[Token], index: usize) -> Result<String, Box<dyn error::Error>> {
+    tokens
+        .get(index)
+        .cloned()
+        .and_then(Token::into_string)
+        .ok_or_else(|| format!("Expected string at output {}", index).into())
+}
+
+pub fn get_address(tokens: &[Token], index: usize) -> Result<H160, Box<dyn error::Error>> {
+    tokens
+        .get(index)
+        .cloned()
+        .and_then(Token::into_address)
+        .ok_or_else(|| format!("Expected address at output {}", index).into())
+}
+
+pub fn u256_to_f64(value: U256) -> f64 {
+    value
+        .0
+        .iter()
+        .rev()
+        .fold(0.0, |total, limb| total * 2_f64.powi(64) + *limb as f64)
+}
+
+pub fn scale(value: U256, decimals: u32) -> f64 {
+    u256_to_f64(value) / 10_f64.powi(decimals as i32)
+}
+
+// Base units of an amount, the whole part is scaled as an integer so its digits stay exact
+pub fn unscale(amount: f64, decimals: u32) -> U256 {
+    let whole = amount.trunc();
+    let fraction = (amount - whole) * 10_f64.powi(decimals as i32);
+
+    U256::from(whole as u128) * U256::exp10(decimals as usize)
+        + U256::from(fraction.round() as u128)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+
+    #[tokio::test]
+    async fn u256_to_f64_success() {
+        assert_eq!(u256_to_f64(U256::from(12345)), 12345.0);
+        assert_eq!(
+            u256_to_f64(U256::from(2).pow(U256::from(96))),
+            2_f64.powi(96)
+        );
+    }
+
+    #[tokio::test]
+    async fn scale_success() {
+        let value = U256::from(1_500_000_u64);
+        assert_eq!(scale(value, 6), 1.5);
+    }
+
+    #[tokio::test]
+    async fn unscale_success() {
+        assert_eq!(unscale(1.5, 6), U256::from(1_500_000_u64));
+        assert_eq!(
+            unscale(1_000_000.0, 18).to_string(),
+            "1000000000000000000000000"
+        );
+    }
+
+    #[tokio::test]
+    async fn get_decimals_success() {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_infura_key = settings
+            .get::<String>("test_infura")
+            .unwrap_or_else(|_| panic!("test infura key is not set in Settings.toml, exit."));
+
+        let endpoint = format!("https://mainnet.infura.io/v3/{}", test_infura_key);
+        let transport = web3::transports::Http::new(&endpoint).unwrap();
+        let web3 = web3::Web3::new(transport);
+
+        // USDC token address
+        let contract_address: H160 = "a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".parse().unwrap();
+        let decimals = get_decimals(&web3, contract_address).await.unwrap();
+        assert_eq!(decimals, 6);
+    }
+
+    #[tokio::test]
+    async fn get_symbol_success() {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_infura_key = settings
+            .get::<String>("test_infura")
+            .unwrap_or_else(|_| panic!("test infura key is not set in Settings.toml, exit."));
+
+        let endpoint = format!("https://mainnet.infura.io/v3/{}", test_infura_key);
+        let transport = web3::transports::Http::new(&endpoint).unwrap();
+        let web3 = web3::Web3::new(transport);
+
+        // USDC token address
+        let contract_address: H160 = "a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".parse().unwrap();
+        let symbol = get_symbol(&web3, contract_address).await.unwrap();
+        assert_eq!(symbol, "USDC");
+    }
+}
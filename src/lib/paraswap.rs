@@ -1,4 +1,6 @@
-use super::price_provider;
+use super::chain::Chain;
+use super::onchain;
+use super::price_provider::{normalize_contract_address, PriceProvider, ETH_ADDRESS};
 use async_trait::async_trait;
 use serde_json::Value;
 use std::error;
@@ -6,10 +8,19 @@ use std::io;
 use std::thread::sleep;
 use std::time::Duration;
 
-pub struct Paraswap;
+// Quotes on the network of a chain, USD prices are quoted against its USD stablecoin
+pub struct Paraswap {
+    network: u64,
+    usd_token: String,
+    eth_token: String,
+}
 
 #[async_trait]
-impl price_provider::PriceProvider for Paraswap {
+impl PriceProvider for Paraswap {
+    fn get_name(&self) -> &str {
+        "paraswap"
+    }
+
     #[allow(dead_code)]
     async fn fetch<'a>(&self, url: &'a str, verbose: bool) -> Result<Value, Box<dyn error::Error>> {
         let mut retry: u32 = 0;
@@ -45,19 +56,9 @@ impl price_provider::PriceProvider for Paraswap {
     async fn get_token_id_from_contract_address<'a>(
         &self,
         contract_address: &'a str,
-        verbose: bool,
+        _verbose: bool,
     ) -> Result<String, Box<dyn error::Error>> {
-        let url = format!(
-            "https://api.coingecko.com/api/v3/coins/ethereum/contract/{}",
-            contract_address
-        );
-        let json = self.fetch(&url, verbose).await?;
-
-        let mix_selector = r#""id""#;
-
-        let value = jql::walker(&json, mix_selector)?;
-
-        Ok(value.as_str().ok_or("").unwrap_or("").to_string())
+        normalize_contract_address(contract_address)
     }
 
     #[allow(dead_code)]
@@ -67,45 +68,169 @@ impl price_provider::PriceProvider for Paraswap {
         versus_name: &'a str,
         verbose: bool,
     ) -> Result<f64, Box<dyn error::Error>> {
-        let to_contract_address;
+        let to_contract_address = self.get_versus_contract_address(versus_name)?;
 
-        match versus_name {
-            "eth" => to_contract_address = "0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee",
-            "usd" => to_contract_address = "0xdac17f958d2ee523a2206206994597c13d831ec7", // USDT token address
-            _ => {
-                return Err(Box::new(io::Error::new(
-                    io::ErrorKind::ConnectionRefused,
-                    format!("Could not fetch token price versus {}", versus_name),
-                )))
-            }
+        if from_contract_address.eq_ignore_ascii_case(to_contract_address) {
+            return Ok(1.0);
         }
 
-        let amount = "1000000000000";
+        // The probe quote gives the token decimals to quote one whole token
+        let probe = self
+            .get_quote(
+                from_contract_address,
+                to_contract_address,
+                PROBE_AMOUNT,
+                verbose,
+            )
+            .await?;
+        let amount = get_unit_amount(probe.src_decimals);
+        if amount == PROBE_AMOUNT {
+            return Ok(probe.unit_price());
+        }
+
+        let quote = self
+            .get_quote(from_contract_address, to_contract_address, &amount, verbose)
+            .await?;
+
+        Ok(quote.unit_price())
+    }
+}
+
+pub struct Quote {
+    pub src_amount: f64,
+    pub src_decimals: u32,
+    pub dest_amount: f64,
+    pub dest_decimals: u32,
+}
+
+impl Quote {
+    pub fn unit_price(&self) -> f64 {
+        let src_units = self.src_amount / 10_f64.powi(self.src_decimals as i32);
+        let dest_units = self.dest_amount / 10_f64.powi(self.dest_decimals as i32);
+
+        if src_units == 0.0 {
+            return 0.0;
+        }
+
+        dest_units / src_units
+    }
+}
+
+impl Paraswap {
+    // None on chains without a ParaSwap network or a USD stablecoin to quote against
+    pub fn new(chain: &Chain) -> Option<Paraswap> {
+        Some(Paraswap {
+            network: chain.paraswap_network?,
+            usd_token: chain.usd_token.clone()?,
+            eth_token: chain.eth_token.to_string(),
+        })
+    }
 
+    pub async fn get_quote(
+        &self,
+        from_contract_address: &str,
+        to_contract_address: &str,
+        amount: &str,
+        verbose: bool,
+    ) -> Result<Quote, Box<dyn error::Error>> {
         let url = format!(
-            "https://apiv5.paraswap.io/prices/?srcToken={}&destToken={}&amount={}&network=1",
-            from_contract_address, to_contract_address, amount
+            "https://apiv5.paraswap.io/prices/?srcToken={}&destToken={}&amount={}&network={}",
+            from_contract_address, to_contract_address, amount, self.network
         );
         let json = self.fetch(&url, verbose).await?;
-        let mix_selector = r#""priceRoute"."destAmount""#;
+        let mix_selector =
+            r#""priceRoute"|{"srcAmount", "srcDecimals", "destAmount", "destDecimals"}"#;
 
         let value: Value = jql::walker(&json, mix_selector)?;
 
-        Ok(value
-            .as_str()
-            .unwrap_or("0.0")
-            .parse::<f64>()
-            .unwrap_or(0.0))
+        Ok(Quote {
+            src_amount: parse_number(&value, "srcAmount")?,
+            src_decimals: parse_number(&value, "srcDecimals")? as u32,
+            dest_amount: parse_number(&value, "destAmount")?,
+            dest_decimals: parse_number(&value, "destDecimals")? as u32,
+        })
+    }
+
+    pub async fn get_token_decimals(
+        &self,
+        contract_address: &str,
+        verbose: bool,
+    ) -> Result<u32, Box<dyn error::Error>> {
+        if contract_address.eq_ignore_ascii_case(ETH_ADDRESS) {
+            return Ok(18);
+        }
+
+        let to_contract_address = self.get_versus_contract_address("eth")?;
+        let quote = self
+            .get_quote(contract_address, to_contract_address, PROBE_AMOUNT, verbose)
+            .await?;
+
+        Ok(quote.src_decimals)
+    }
+
+    pub async fn get_liquidation_value(
+        &self,
+        from_contract_address: &str,
+        balance: f64,
+        versus_name: &str,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let to_contract_address = self.get_versus_contract_address(versus_name)?;
+
+        let decimals = self
+            .get_token_decimals(from_contract_address, verbose)
+            .await?;
+        let amount = onchain::unscale(balance, decimals).to_string();
+
+        let quote = self
+            .get_quote(from_contract_address, to_contract_address, &amount, verbose)
+            .await?;
+
+        Ok(quote.dest_amount / 10_f64.powi(quote.dest_decimals as i32))
+    }
+
+    fn get_versus_contract_address(
+        &self,
+        versus_name: &str,
+    ) -> Result<&str, Box<dyn error::Error>> {
+        match versus_name {
+            "eth" => Ok(&self.eth_token),
+            "usd" => Ok(&self.usd_token),
+            _ => Err(Box::new(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("Could not fetch token price versus {}", versus_name),
+            ))),
+        }
+    }
+}
+
+const PROBE_AMOUNT: &str = "1000000000000";
+
+// One whole token in base units, as a string since it overflows u128 past 38 decimals
+fn get_unit_amount(decimals: u32) -> String {
+    format!("1{}", "0".repeat(decimals as usize))
+}
+
+fn parse_number(value: &Value, key: &str) -> Result<f64, Box<dyn error::Error>> {
+    match value.get(key) {
+        Some(Value::String(number)) => Ok(number.parse::<f64>()?),
+        Some(Value::Number(number)) => Ok(number.as_f64().unwrap_or(0.0)),
+        _ => Err(Box::new(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("Node \"{}\" not found on the price route", key),
+        ))),
     }
 }
+
 #[cfg(test)]
 mod test {
-    use crate::lib::paraswap::Paraswap;
+    use crate::lib::chain::Chain;
+    use crate::lib::paraswap::{get_unit_amount, Paraswap, Quote};
     use crate::lib::price_provider::PriceProvider;
 
     #[tokio::test]
     async fn fetch_success() {
-        let paraswap = Paraswap;
+        let paraswap = Paraswap::new(&Chain::default()).unwrap();
 
         // AAVE token address
         let from_contract_address = "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9";
@@ -126,7 +251,7 @@ mod test {
 
     #[tokio::test]
     async fn fetch_non_existent_token_fail() {
-        let paraswap = Paraswap;
+        let paraswap = Paraswap::new(&Chain::default()).unwrap();
 
         // non existent token address
         let from_contract_address = "0x0121212121212121212121212212121212121212";
@@ -150,7 +275,7 @@ mod test {
 
     #[tokio::test]
     async fn get_token_price_success() {
-        let paraswap = Paraswap;
+        let paraswap = Paraswap::new(&Chain::default()).unwrap();
 
         // AAVE token address
         let contract_address = "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9";
@@ -161,9 +286,93 @@ mod test {
         assert_ne!(price, 0.0);
     }
 
+    #[tokio::test]
+    async fn get_token_price_scaled_by_decimals_success() {
+        let paraswap = Paraswap::new(&Chain::default()).unwrap();
+
+        // USDC token address, 6 decimals priced in USDT, 6 decimals
+        let contract_address = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+        let price = paraswap
+            .get_token_price(contract_address, "usd", false)
+            .await
+            .unwrap();
+        assert!(price > 0.9 && price < 1.1);
+    }
+
+    #[tokio::test]
+    async fn get_token_id_success() {
+        let paraswap = Paraswap::new(&Chain::default()).unwrap();
+
+        // AAVE token address
+        let contract_address = "0x7Fc66500c84A76Ad7e9c93437bFc5Ac33E2DDaE9";
+        let id = paraswap
+            .get_token_id_from_contract_address(contract_address, false)
+            .await
+            .unwrap();
+        assert_eq!(id, "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9");
+    }
+
+    #[tokio::test]
+    async fn get_token_id_fail() {
+        let paraswap = Paraswap::new(&Chain::default()).unwrap();
+        let result = paraswap
+            .get_token_id_from_contract_address("0x98b2dE885E916b598f65DeD2", false)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn get_token_decimals_success() {
+        let paraswap = Paraswap::new(&Chain::default()).unwrap();
+
+        // USDC token address
+        let contract_address = "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+        let decimals = paraswap
+            .get_token_decimals(contract_address, false)
+            .await
+            .unwrap();
+        assert_eq!(decimals, 6);
+    }
+
+    #[tokio::test]
+    async fn get_liquidation_value_success() {
+        let paraswap = Paraswap::new(&Chain::default()).unwrap();
+
+        // AAVE token address
+        let contract_address = "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9";
+        let unit_price = paraswap
+            .get_token_price(contract_address, "usd", false)
+            .await
+            .unwrap();
+        let liquidation_value = paraswap
+            .get_liquidation_value(contract_address, 10.0, "usd", false)
+            .await
+            .unwrap();
+        assert_ne!(liquidation_value, 0.0);
+        assert!(liquidation_value <= unit_price * 10.0 * 1.01);
+    }
+
+    #[tokio::test]
+    async fn quote_unit_price_success() {
+        let quote = Quote {
+            src_amount: 2_000_000.0,
+            src_decimals: 6,
+            dest_amount: 1_000_000_000_000_000.0,
+            dest_decimals: 18,
+        };
+        assert_eq!(quote.unit_price(), 0.0005);
+    }
+
+    #[tokio::test]
+    async fn get_unit_amount_success() {
+        assert_eq!(get_unit_amount(0), "1");
+        assert_eq!(get_unit_amount(6), "1000000");
+        assert_eq!(get_unit_amount(40), format!("1{}", "0".repeat(40)));
+    }
+
     #[tokio::test]
     async fn get_token_price_fail() {
-        let paraswap = Paraswap;
+        let paraswap = Paraswap::new(&Chain::default()).unwrap();
 
         // non existent token address
         let contract_address = "0x0121212121212121212121212212121212121212";
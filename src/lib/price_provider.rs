@@ -1,9 +1,15 @@
+use super::historical::Historical;
 use async_trait::async_trait;
 use serde_json::Value;
 use std::error;
+use web3::types::H160;
+
+pub const ETH_ADDRESS: &str = "0xeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeeee";
 
 #[async_trait]
-pub trait PriceProvider {
+pub trait PriceProvider: Send + Sync {
+    fn get_name(&self) -> &str;
+
     async fn fetch<'a>(&self, url: &'a str, verbose: bool) -> Result<Value, Box<dyn error::Error>>;
 
     async fn get_token_id_from_contract_address<'a>(
@@ -18,4 +24,45 @@ pub trait PriceProvider {
         versus_name: &'a str,
         verbose: bool,
     ) -> Result<f64, Box<dyn error::Error>>;
+
+    async fn get_historical_token_price<'a>(
+        &self,
+        _token_id: &'a str,
+        _versus_name: &'a str,
+        _historical: &'a Historical,
+        _verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        Err(format!("Historical prices are not supported by {}", self.get_name()).into())
+    }
+
+    async fn prefetch<'a>(
+        &self,
+        _contract_addresses: &'a [String],
+        _verbose: bool,
+    ) -> Result<(), Box<dyn error::Error>> {
+        Ok(())
+    }
+
+    fn get_token_link(&self, _token_id: &str) -> Option<String> {
+        None
+    }
+
+    fn supports_versus(&self, versus_name: &str) -> bool {
+        matches!(versus_name, "usd" | "eth")
+    }
+}
+
+pub fn normalize_contract_address(contract_address: &str) -> Result<String, Box<dyn error::Error>> {
+    let raw_address = contract_address
+        .strip_prefix("0x")
+        .unwrap_or(contract_address);
+
+    raw_address.parse::<H160>().map_err(|err| {
+        format!(
+            "Error at specified contract address: {}. {:?}",
+            contract_address, err
+        )
+    })?;
+
+    Ok(format!("0x{}", raw_address.to_lowercase()))
 }
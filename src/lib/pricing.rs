@@ -0,0 +1,430 @@
+use super::chain::Chain;
+use super::chainlink::{self, Chainlink};
+use super::coingecko::Coingecko;
+use super::consensus;
+use super::custom::{self, Custom};
+use super::defillama::{self, DefiLlama};
+use super::historical::Historical;
+use super::paraswap::Paraswap;
+use super::price_provider::{normalize_contract_address, PriceProvider};
+use super::uniswap::{self, Uniswap};
+use config::Config;
+use std::collections::HashMap;
+use std::error;
+use std::io;
+
+pub const PROVIDER_NAMES: [&str; 5] =
+    ["chainlink", "coingecko", "paraswap", "defillama", "uniswap"];
+
+// Chainlink and Uniswap read prices on-chain and are skipped without an RPC endpoint or
+// outside Ethereum mainnet, their feeds and pools are the mainnet ones
+const ON_CHAIN_PROVIDER_NAMES: [&str; 2] = ["chainlink", "uniswap"];
+
+#[derive(Debug, Clone)]
+pub struct PricingConfig {
+    pub providers: Vec<String>,
+    pub overrides: HashMap<String, Vec<String>>,
+    pub min_liquidity: f64,
+    pub min_confidence: f64,
+    pub chainlink_feeds: chainlink::Feeds,
+    pub chainlink_max_age: u64,
+    pub max_deviation: f64,
+    pub custom: Vec<Custom>,
+    pub chain: Chain,
+}
+
+pub struct PriceProviders {
+    providers: HashMap<String, Box<dyn PriceProvider>>,
+    order: Vec<String>,
+    overrides: HashMap<String, Vec<String>>,
+}
+
+impl Default for PricingConfig {
+    fn default() -> PricingConfig {
+        PricingConfig {
+            providers: PROVIDER_NAMES.iter().map(|name| name.to_string()).collect(),
+            overrides: HashMap::new(),
+            min_liquidity: uniswap::DEFAULT_MIN_LIQUIDITY,
+            min_confidence: defillama::DEFAULT_MIN_CONFIDENCE,
+            chainlink_feeds: chainlink::default_feeds(),
+            chainlink_max_age: chainlink::DEFAULT_MAX_AGE,
+            max_deviation: consensus::DEFAULT_MAX_DEVIATION,
+            custom: vec![],
+            chain: Chain::default(),
+        }
+    }
+}
+
+impl PricingConfig {
+    // Reads the [pricing] section of Settings.toml, every key is optional:
+    //
+    // [pricing]
+    // providers = ["chainlink", "coingecko", "paraswap", "defillama", "uniswap"]
+    // max_deviation = 5.0
+    //
+    // [pricing.overrides]
+    // "<token-contract-address>" = ["uniswap"]
+    //
+    // [pricing.uniswap]
+    // min_liquidity = 10.0
+    //
+    // Custom providers from [pricing.custom.<name>] go after the built-in ones
+    pub fn from_settings(settings: &Config) -> Result<PricingConfig, Box<dyn error::Error>> {
+        let mut pricing = PricingConfig {
+            custom: custom::load_custom_providers(settings)?,
+            ..Default::default()
+        };
+
+        for custom in &pricing.custom {
+            if PROVIDER_NAMES.contains(&custom.name.as_str()) {
+                return Err(Box::new(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!(
+                        "Custom price provider {} shadows a built-in one",
+                        custom.name
+                    ),
+                )));
+            }
+            pricing.providers.push(custom.name.clone());
+        }
+
+        if let Ok(providers) = settings.get_array("pricing.providers") {
+            pricing.providers = pricing.get_provider_names(providers)?;
+        }
+
+        if let Ok(overrides) = settings.get_table("pricing.overrides") {
+            for (contract_address, providers) in overrides {
+                let providers = pricing.get_provider_names(providers.into_array()?)?;
+                pricing
+                    .overrides
+                    .insert(normalize_contract_address(&contract_address)?, providers);
+            }
+        }
+
+        if let Ok(max_deviation) = settings.get::<f64>("pricing.max_deviation") {
+            pricing.max_deviation = max_deviation;
+        }
+        if let Ok(min_liquidity) = settings.get::<f64>("pricing.uniswap.min_liquidity") {
+            pricing.min_liquidity = min_liquidity;
+        }
+        if let Ok(min_confidence) = settings.get::<f64>("pricing.defillama.min_confidence") {
+            pricing.min_confidence = min_confidence;
+        }
+        if let Ok(max_age) = settings.get::<u64>("pricing.chainlink.max_age") {
+            pricing.chainlink_max_age = max_age;
+        }
+        pricing.chainlink_feeds = chainlink::load_feeds(settings)?;
+
+        Ok(pricing)
+    }
+
+    fn get_provider_names(
+        &self,
+        values: Vec<config::Value>,
+    ) -> Result<Vec<String>, Box<dyn error::Error>> {
+        let mut names = vec![];
+
+        for value in values {
+            let name = value.into_string()?.to_lowercase();
+            if !self.is_provider_name(&name) {
+                return Err(self.unknown_provider_error(&name));
+            }
+            names.push(name);
+        }
+
+        Ok(names)
+    }
+
+    fn is_provider_name(&self, name: &str) -> bool {
+        PROVIDER_NAMES.contains(&name) || self.custom.iter().any(|custom| custom.name == name)
+    }
+
+    fn unknown_provider_error(&self, name: &str) -> Box<dyn error::Error> {
+        let names: Vec<&str> = PROVIDER_NAMES
+            .iter()
+            .copied()
+            .chain(self.custom.iter().map(|custom| custom.name.as_str()))
+            .collect();
+
+        Box::new(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Unknown price provider {}, expected one of: {}",
+                name,
+                names.join(", ")
+            ),
+        ))
+    }
+}
+
+impl PriceProviders {
+    pub fn new(
+        pricing: &PricingConfig,
+        endpoint: Option<&str>,
+    ) -> Result<PriceProviders, Box<dyn error::Error>> {
+        let mut providers: HashMap<String, Box<dyn PriceProvider>> = HashMap::new();
+
+        let names = pricing
+            .providers
+            .iter()
+            .chain(pricing.overrides.values().flatten());
+
+        for name in names {
+            if providers.contains_key(name) {
+                continue;
+            }
+            if let Some(price_provider) = build_price_provider(name, pricing, endpoint)? {
+                providers.insert(name.to_string(), price_provider);
+            }
+        }
+
+        Ok(PriceProviders {
+            providers,
+            order: pricing.providers.clone(),
+            overrides: pricing.overrides.clone(),
+        })
+    }
+
+    pub fn get_all(&self) -> Vec<&dyn PriceProvider> {
+        self.providers
+            .values()
+            .map(|price_provider| price_provider.as_ref())
+            .collect()
+    }
+
+    pub fn get_chain(&self, contract_address: &str) -> Vec<&dyn PriceProvider> {
+        let names = normalize_contract_address(contract_address)
+            .ok()
+            .and_then(|contract_address| self.overrides.get(&contract_address))
+            .unwrap_or(&self.order);
+
+        names
+            .iter()
+            .filter_map(|name| self.providers.get(name))
+            .map(|price_provider| price_provider.as_ref())
+            .collect()
+    }
+}
+
+pub async fn get_first_price(
+    price_providers: &[&dyn PriceProvider],
+    contract_address: &str,
+    versus_name: &str,
+    historical: Option<&Historical>,
+    verbose: bool,
+) -> Option<(String, f64)> {
+    for price_provider in price_providers {
+        let token_id = match price_provider
+            .get_token_id_from_contract_address(contract_address, verbose)
+            .await
+        {
+            Ok(token_id) => token_id,
+            Err(_) => continue,
+        };
+
+        if let Ok(price) =
+            get_price(*price_provider, &token_id, versus_name, historical, verbose).await
+        {
+            return Some((price_provider.get_name().to_string(), price));
+        }
+    }
+
+    None
+}
+
+// Current price, or the one at the historical block when given
+pub async fn get_price(
+    price_provider: &dyn PriceProvider,
+    token_id: &str,
+    versus_name: &str,
+    historical: Option<&Historical>,
+    verbose: bool,
+) -> Result<f64, Box<dyn error::Error>> {
+    match historical {
+        Some(historical) => {
+            price_provider
+                .get_historical_token_price(token_id, versus_name, historical, verbose)
+                .await
+        }
+        None => {
+            price_provider
+                .get_token_price(token_id, versus_name, verbose)
+                .await
+        }
+    }
+}
+
+fn build_price_provider(
+    name: &str,
+    pricing: &PricingConfig,
+    endpoint: Option<&str>,
+) -> Result<Option<Box<dyn PriceProvider>>, Box<dyn error::Error>> {
+    let endpoint = match (endpoint, ON_CHAIN_PROVIDER_NAMES.contains(&name)) {
+        (None, true) => return Ok(None),
+        (Some(_), true) if !pricing.chain.is_ethereum() => return Ok(None),
+        (endpoint, _) => endpoint.unwrap_or_default(),
+    };
+
+    let price_provider: Box<dyn PriceProvider> = match name {
+        "chainlink" => Box::new(Chainlink::new(
+            web3::Web3::new(web3::transports::Http::new(endpoint)?),
+            pricing.chainlink_feeds.clone(),
+            pricing.chainlink_max_age,
+        )),
+        "coingecko" => Box::new(Coingecko::new(&pricing.chain)),
+        "paraswap" => match Paraswap::new(&pricing.chain) {
+            Some(paraswap) => Box::new(paraswap),
+            None => return Ok(None),
+        },
+        "defillama" => Box::new(DefiLlama::new(pricing.min_confidence, &pricing.chain)),
+        "uniswap" => Box::new(Uniswap::new(endpoint, pricing.min_liquidity)?),
+        _ => match pricing.custom.iter().find(|custom| custom.name == name) {
+            Some(custom) => Box::new(custom.clone()),
+            None => return Err(pricing.unknown_provider_error(name)),
+        },
+    };
+
+    Ok(Some(price_provider))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lib::chain;
+    use config;
+
+    fn get_settings(toml: &str) -> Config {
+        config::Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn from_settings_success() {
+        let settings = get_settings(
+            r#"
+            [pricing]
+            providers = ["defillama", "coingecko"]
+            max_deviation = 2.5
+
+            [pricing.overrides]
+            "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e" = ["uniswap"]
+
+            [pricing.uniswap]
+            min_liquidity = 50.0
+            "#,
+        );
+
+        let pricing = PricingConfig::from_settings(&settings).unwrap();
+        assert_eq!(pricing.providers, vec!["defillama", "coingecko"]);
+        assert_eq!(pricing.max_deviation, 2.5);
+        assert_eq!(pricing.min_liquidity, 50.0);
+        assert_eq!(
+            pricing
+                .overrides
+                .get("0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e"),
+            Some(&vec!["uniswap".to_string()])
+        );
+    }
+
+    #[tokio::test]
+    async fn from_settings_unknown_provider_fail() {
+        let settings = get_settings(
+            r#"
+            [pricing]
+            providers = ["coingecko", "binance"]
+            "#,
+        );
+
+        let result = PricingConfig::from_settings(&settings);
+        if let Result::Err(err) = result {
+            assert_eq!(
+                (*err).to_string(),
+                "Unknown price provider binance, expected one of: chainlink, coingecko, paraswap, defillama, uniswap"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn from_settings_custom_success() {
+        let settings = get_settings(
+            r#"
+            [pricing.overrides]
+            "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e" = ["llama", "coingecko"]
+
+            [pricing.custom.llama]
+            url = "https://coins.llama.fi/prices/current/ethereum:{contract}"
+            selector = '"coins"."ethereum:{contract}"."price"'
+            "#,
+        );
+
+        let pricing = PricingConfig::from_settings(&settings).unwrap();
+        assert_eq!(pricing.providers.last().unwrap(), "llama");
+
+        let price_providers = PriceProviders::new(&pricing, None).unwrap();
+        let chain: Vec<&str> = price_providers
+            .get_chain("0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e")
+            .iter()
+            .map(|price_provider| price_provider.get_name())
+            .collect();
+        assert_eq!(chain, vec!["llama", "coingecko"]);
+    }
+
+    #[tokio::test]
+    async fn get_chain_success() {
+        let mut pricing = PricingConfig {
+            providers: vec!["paraswap".to_string(), "coingecko".to_string()],
+            ..Default::default()
+        };
+        pricing.overrides.insert(
+            "0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e".to_string(),
+            vec!["defillama".to_string()],
+        );
+
+        let price_providers = PriceProviders::new(&pricing, None).unwrap();
+
+        let chain: Vec<&str> = price_providers
+            .get_chain("0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9")
+            .iter()
+            .map(|price_provider| price_provider.get_name())
+            .collect();
+        assert_eq!(chain, vec!["paraswap", "coingecko"]);
+
+        let chain: Vec<&str> = price_providers
+            .get_chain("0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e")
+            .iter()
+            .map(|price_provider| price_provider.get_name())
+            .collect();
+        assert_eq!(chain, vec!["defillama"]);
+    }
+
+    #[tokio::test]
+    async fn get_chain_without_endpoint_success() {
+        let price_providers = PriceProviders::new(&PricingConfig::default(), None).unwrap();
+
+        let chain: Vec<&str> = price_providers
+            .get_chain("0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9")
+            .iter()
+            .map(|price_provider| price_provider.get_name())
+            .collect();
+        assert_eq!(chain, vec!["coingecko", "paraswap", "defillama"]);
+    }
+
+    #[tokio::test]
+    async fn get_chain_outside_ethereum_success() {
+        let pricing = PricingConfig {
+            chain: chain::default_chains("", "").remove(1),
+            ..Default::default()
+        };
+        let price_providers =
+            PriceProviders::new(&pricing, Some("https://polygon-rpc.com")).unwrap();
+
+        let chain: Vec<&str> = price_providers
+            .get_chain("0xd6df932a45c0f255f85145f286ea0b292b21c90b")
+            .iter()
+            .map(|price_provider| price_provider.get_name())
+            .collect();
+        assert_eq!(chain, vec!["coingecko", "paraswap", "defillama"]);
+    }
+}
@@ -0,0 +1,139 @@
+use super::historical::Historical;
+use super::onchain;
+use super::price_provider::{normalize_contract_address, ETH_ADDRESS};
+use super::pricing::{self, PriceProviders};
+use std::error;
+use web3::transports::Http;
+use web3::types::H160;
+use web3::Web3;
+
+// Symbols accepted by --quote besides a token contract address
+const KNOWN_TOKENS: [(&str, &str); 8] = [
+    ("eth", ETH_ADDRESS),
+    ("weth", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"),
+    ("btc", "0x2260fac5e5542a773aa44fbcfedf7c193bc2c599"),
+    ("wbtc", "0x2260fac5e5542a773aa44fbcfedf7c193bc2c599"),
+    ("steth", "0xae7ab96520de3a18e5e111b5eaab095312d7fe84"),
+    ("usdc", "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48"),
+    ("usdt", "0xdac17f958d2ee523a2206206994597c13d831ec7"),
+    ("dai", "0x6b175474e89094c44da98b954eedeac495271d0f"),
+];
+
+// Reference token the portfolio is denominated in
+#[derive(Debug, Clone)]
+pub struct Quote {
+    pub symbol: String,
+    pub usd_price: f64,
+}
+
+impl Quote {
+    pub async fn load(
+        name: &str,
+        price_providers: &PriceProviders,
+        web3: &Web3<Http>,
+        historical: Option<&Historical>,
+        verbose: bool,
+    ) -> Result<Quote, Box<dyn error::Error>> {
+        let (symbol, contract_address) = match get_known_token(name) {
+            Some(contract_address) => (name.to_uppercase(), contract_address.to_string()),
+            None => {
+                let contract_address = normalize_contract_address(name)?;
+                let symbol = onchain::get_symbol(web3, contract_address.parse::<H160>()?)
+                    .await
+                    .unwrap_or_else(|_| contract_address.clone());
+                (symbol, contract_address)
+            }
+        };
+
+        let (price_source, usd_price) = pricing::get_first_price(
+            &price_providers.get_chain(&contract_address),
+            &contract_address,
+            "usd",
+            historical,
+            verbose,
+        )
+        .await
+        .ok_or_else(|| format!("Could not fetch {} price from any price provider", symbol))?;
+
+        // Every value is divided by the quote price
+        if usd_price <= 0.0 {
+            return Err(format!(
+                "Invalid {} price of {} US$ from {}, cannot quote in it",
+                symbol, usd_price, price_source
+            )
+            .into());
+        }
+
+        if verbose {
+            println!(
+                "Quote token {} {} at {:.2} US$ from {}",
+                symbol, contract_address, usd_price, price_source
+            );
+        }
+
+        Ok(Quote { symbol, usd_price })
+    }
+
+    pub fn convert(&self, usd_value: f64) -> f64 {
+        usd_value / self.usd_price
+    }
+}
+
+fn get_known_token(name: &str) -> Option<&'static str> {
+    KNOWN_TOKENS
+        .iter()
+        .find(|(symbol, _)| symbol.eq_ignore_ascii_case(name))
+        .map(|(_, contract_address)| *contract_address)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::lib::pricing::PricingConfig;
+
+    #[tokio::test]
+    async fn get_known_token_success() {
+        assert_eq!(
+            get_known_token("BTC"),
+            Some("0x2260fac5e5542a773aa44fbcfedf7c193bc2c599")
+        );
+        assert_eq!(
+            get_known_token("0x2260fac5e5542a773aa44fbcfedf7c193bc2c599"),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn convert_success() {
+        let quote = Quote {
+            symbol: "WBTC".to_string(),
+            usd_price: 50000.0,
+        };
+        assert_eq!(quote.convert(25000.0), 0.5);
+    }
+
+    #[tokio::test]
+    async fn load_success() {
+        let pricing = PricingConfig {
+            providers: vec!["coingecko".to_string()],
+            ..Default::default()
+        };
+        let price_providers = PriceProviders::new(&pricing, None).unwrap();
+        let web3 = Web3::new(Http::new("http://localhost:8545").unwrap());
+
+        let quote = Quote::load("steth", &price_providers, &web3, None, false)
+            .await
+            .unwrap();
+        assert_eq!(quote.symbol, "STETH");
+        assert_ne!(quote.usd_price, 0.0);
+    }
+
+    #[tokio::test]
+    async fn load_invalid_address_fail() {
+        let price_providers = PriceProviders::new(&PricingConfig::default(), None).unwrap();
+        let web3 = Web3::new(Http::new("http://localhost:8545").unwrap());
+
+        let result = Quote::load("0x1234", &price_providers, &web3, None, false).await;
+        assert!(result.is_err());
+    }
+}
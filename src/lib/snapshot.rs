@@ -0,0 +1,347 @@
+use super::erc20::{TokenInfo, Tokens};
+use super::price_provider::ETH_ADDRESS;
+use rusqlite::{params, Connection, Row};
+use std::error;
+use std::io;
+
+pub const DEFAULT_DATABASE: &str = "snapshots.db";
+
+// Saved scan of an address at a block, positions include ETH
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    pub id: i64,
+    pub timestamp: i64,
+    pub block: u64,
+    pub address: String,
+    pub positions: Vec<Position>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Position {
+    pub symbol: String,
+    pub contract_address: String,
+    pub balance: f64,
+    pub usd_price: f64,
+    pub eth_price: f64,
+    pub price_source: String,
+}
+
+pub struct Snapshots {
+    connection: Connection,
+}
+
+impl Position {
+    // Token symbols are keyed by their JSON value, quotes included
+    pub fn new(symbol: &str, token_info: &TokenInfo) -> Position {
+        Position {
+            symbol: symbol.trim_matches('"').to_string(),
+            contract_address: token_info.contract_address.to_lowercase(),
+            balance: token_info.balance,
+            usd_price: token_info.usd_price,
+            eth_price: token_info.eth_price,
+            price_source: token_info.price_source.to_string(),
+        }
+    }
+
+    // Token given by symbol or contract address
+    pub fn matches(&self, token: &str) -> bool {
+        self.symbol.eq_ignore_ascii_case(token) || self.contract_address.eq_ignore_ascii_case(token)
+    }
+
+    pub fn get_usd_balance(&self) -> f64 {
+        self.balance * self.usd_price
+    }
+
+    pub fn get_eth_balance(&self) -> f64 {
+        self.balance * self.eth_price
+    }
+
+    fn from_row(row: &Row) -> rusqlite::Result<Position> {
+        Ok(Position {
+            symbol: row.get(0)?,
+            contract_address: row.get(1)?,
+            balance: row.get(2)?,
+            usd_price: row.get(3)?,
+            eth_price: row.get(4)?,
+            price_source: row.get(5)?,
+        })
+    }
+}
+
+impl Snapshot {
+    pub fn new(
+        address: &str,
+        block: u64,
+        timestamp: i64,
+        eth_info: &TokenInfo,
+        tokens: &Tokens,
+    ) -> Snapshot {
+        let mut eth_position = Position::new("ETH", eth_info);
+        eth_position.contract_address = ETH_ADDRESS.to_string();
+
+        let mut positions = vec![eth_position];
+
+        let mut token_positions: Vec<Position> = tokens
+            .iter()
+            .filter_map(|(symbol, token_info)| {
+                token_info
+                    .as_ref()
+                    .filter(|token_info| token_info.balance > 0.0)
+                    .map(|token_info| Position::new(symbol, token_info))
+            })
+            .collect();
+        token_positions.sort_by(|a, b| a.symbol.cmp(&b.symbol));
+        positions.extend(token_positions);
+
+        Snapshot {
+            id: 0,
+            timestamp,
+            block,
+            address: address.to_lowercase(),
+            positions,
+        }
+    }
+
+    pub fn get_usd_balance(&self) -> f64 {
+        self.positions.iter().map(Position::get_usd_balance).sum()
+    }
+
+    pub fn get_eth_balance(&self) -> f64 {
+        self.positions.iter().map(Position::get_eth_balance).sum()
+    }
+}
+
+impl Snapshots {
+    pub fn open(path: &str) -> Result<Snapshots, Box<dyn error::Error>> {
+        let connection = Connection::open(path)?;
+
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS snapshots (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                timestamp INTEGER NOT NULL,
+                block INTEGER NOT NULL,
+                address TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS positions (
+                snapshot_id INTEGER NOT NULL REFERENCES snapshots(id) ON DELETE CASCADE,
+                symbol TEXT NOT NULL,
+                contract_address TEXT NOT NULL,
+                balance REAL NOT NULL,
+                usd_price REAL NOT NULL,
+                eth_price REAL NOT NULL,
+                price_source TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS snapshots_address ON snapshots(address, timestamp);",
+        )?;
+
+        Ok(Snapshots { connection })
+    }
+
+    pub fn save(&mut self, snapshot: &Snapshot) -> Result<i64, Box<dyn error::Error>> {
+        let transaction = self.connection.transaction()?;
+
+        transaction.execute(
+            "INSERT INTO snapshots (timestamp, block, address) VALUES (?1, ?2, ?3)",
+            params![snapshot.timestamp, snapshot.block as i64, snapshot.address],
+        )?;
+        let id = transaction.last_insert_rowid();
+
+        for position in &snapshot.positions {
+            transaction.execute(
+                "INSERT INTO positions (snapshot_id, symbol, contract_address, balance, usd_price, eth_price, price_source)
+                VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                params![
+                    id,
+                    position.symbol,
+                    position.contract_address,
+                    position.balance,
+                    position.usd_price,
+                    position.eth_price,
+                    position.price_source
+                ],
+            )?;
+        }
+
+        transaction.commit()?;
+
+        Ok(id)
+    }
+
+    // Oldest first, of every address when none is given
+    pub fn list(&self, address: Option<&str>) -> Result<Vec<Snapshot>, Box<dyn error::Error>> {
+        let mut statement = self.connection.prepare(
+            "SELECT id, timestamp, block, address FROM snapshots
+            WHERE ?1 IS NULL OR address = ?1
+            ORDER BY timestamp, id",
+        )?;
+
+        let address = address.map(str::to_lowercase);
+        let rows = statement.query_map(params![address], |row| {
+            Ok(Snapshot {
+                id: row.get(0)?,
+                timestamp: row.get(1)?,
+                block: row.get::<_, i64>(2)? as u64,
+                address: row.get(3)?,
+                positions: vec![],
+            })
+        })?;
+
+        let mut snapshots = vec![];
+        for row in rows {
+            let mut snapshot = row?;
+            snapshot.positions = self.get_positions(snapshot.id)?;
+            snapshots.push(snapshot);
+        }
+
+        Ok(snapshots)
+    }
+
+    pub fn get(&self, id: i64) -> Result<Snapshot, Box<dyn error::Error>> {
+        let mut statement = self
+            .connection
+            .prepare("SELECT id, timestamp, block, address FROM snapshots WHERE id = ?1")?;
+
+        let mut rows = statement.query(params![id])?;
+        let row = rows.next()?.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Snapshot {} not found", id),
+            )
+        })?;
+
+        Ok(Snapshot {
+            id: row.get(0)?,
+            timestamp: row.get(1)?,
+            block: row.get::<_, i64>(2)? as u64,
+            address: row.get(3)?,
+            positions: self.get_positions(id)?,
+        })
+    }
+
+    fn get_positions(&self, snapshot_id: i64) -> Result<Vec<Position>, Box<dyn error::Error>> {
+        let mut statement = self.connection.prepare(
+            "SELECT symbol, contract_address, balance, usd_price, eth_price, price_source
+            FROM positions WHERE snapshot_id = ?1 ORDER BY rowid",
+        )?;
+
+        let rows = statement.query_map(params![snapshot_id], Position::from_row)?;
+
+        let mut positions = vec![];
+        for row in rows {
+            positions.push(row?);
+        }
+
+        Ok(positions)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_snapshot(address: &str, timestamp: i64) -> Snapshot {
+        let eth_info = TokenInfo::new("", &2.0, &2000.0, &1.0, "");
+        let mut tokens = Tokens::new();
+        tokens.insert(
+            "\"YFI\"".to_string(),
+            Some(TokenInfo::new(
+                "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e",
+                &1.0,
+                &8000.0,
+                &4.0,
+                "",
+            )),
+        );
+        tokens.insert(
+            "\"AAVE\"".to_string(),
+            Some(TokenInfo::new(
+                "0x7fc66500c84a76ad7e9c93437bfc5ac33e2ddae9",
+                &0.0,
+                &100.0,
+                &0.05,
+                "",
+            )),
+        );
+        tokens.insert("\"SCAM\"".to_string(), None);
+
+        Snapshot::new(address, 18908894, timestamp, &eth_info, &tokens)
+    }
+
+    #[tokio::test]
+    async fn new_success() {
+        let snapshot = get_snapshot("0x000000000000000000000000000000000000dEaD", 1704067199);
+
+        assert_eq!(
+            snapshot.address,
+            "0x000000000000000000000000000000000000dead"
+        );
+        assert_eq!(snapshot.positions.len(), 2);
+        assert_eq!(snapshot.positions[0].contract_address, ETH_ADDRESS);
+        assert_eq!(snapshot.positions[1].symbol, "YFI");
+        assert_eq!(
+            snapshot.positions[1].contract_address,
+            "0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e"
+        );
+        assert_eq!(snapshot.get_usd_balance(), 12000.0);
+        assert_eq!(snapshot.get_eth_balance(), 6.0);
+    }
+
+    #[tokio::test]
+    async fn matches_success() {
+        let snapshot = get_snapshot("0x000000000000000000000000000000000000dead", 1704067199);
+
+        assert!(snapshot.positions[1].matches("yfi"));
+        assert!(snapshot.positions[1].matches("0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e"));
+        assert!(!snapshot.positions[1].matches("ETH"));
+    }
+
+    #[tokio::test]
+    async fn save_and_get_success() {
+        let mut snapshots = Snapshots::open(":memory:").unwrap();
+        let mut snapshot = get_snapshot("0x000000000000000000000000000000000000dead", 1704067199);
+
+        snapshot.id = snapshots.save(&snapshot).unwrap();
+        assert_eq!(snapshots.get(snapshot.id).unwrap(), snapshot);
+    }
+
+    #[tokio::test]
+    async fn list_success() {
+        let mut snapshots = Snapshots::open(":memory:").unwrap();
+        snapshots
+            .save(&get_snapshot(
+                "0x000000000000000000000000000000000000dead",
+                1706745599,
+            ))
+            .unwrap();
+        snapshots
+            .save(&get_snapshot(
+                "0x000000000000000000000000000000000000dead",
+                1704067199,
+            ))
+            .unwrap();
+        snapshots
+            .save(&get_snapshot(
+                "0x0000000000000000000000000000000000000001",
+                1704067199,
+            ))
+            .unwrap();
+
+        assert_eq!(snapshots.list(None).unwrap().len(), 3);
+
+        let listed = snapshots
+            .list(Some("0x000000000000000000000000000000000000dEaD"))
+            .unwrap();
+        assert_eq!(listed.len(), 2);
+        assert_eq!(listed[0].timestamp, 1704067199);
+        assert_eq!(listed[0].positions.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn get_fail() {
+        let snapshots = Snapshots::open(":memory:").unwrap();
+        let result = snapshots.get(42);
+        if let Result::Err(err) = result {
+            assert_eq!((*err).to_string(), "Snapshot 42 not found");
+        }
+    }
+}
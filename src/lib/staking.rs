@@ -0,0 +1,132 @@
+use super::historical::Historical;
+use super::onchain;
+use super::price_provider::normalize_contract_address;
+use std::error;
+use std::io;
+use web3::ethabi::ParamType;
+use web3::transports::Http;
+use web3::types::H160;
+use web3::Web3;
+
+// Wrapped and staked ETH on Ethereum mainnet with the call giving the ETH one token is worth,
+// WETH is wrapped one to one and stETH rebases so its balance already counts in ETH
+const ETH_BACKED_TOKENS: [(&str, &str, Option<&str>); 5] = [
+    ("WETH", "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", None),
+    ("stETH", "0xae7ab96520de3a18e5e111b5eaab095312d7fe84", None),
+    (
+        "wstETH",
+        "0x7f39c581f595b53c5cb19bd0b3f8da6c935e2ca0",
+        Some("stEthPerToken()"),
+    ),
+    (
+        "rETH",
+        "0xae78736cd615f374d3085123a210448e74fc6393",
+        Some("getExchangeRate()"),
+    ),
+    (
+        "cbETH",
+        "0xbe9895146f7af43049ca1c1ae358b0541ea49704",
+        Some("exchangeRate()"),
+    ),
+];
+
+pub fn is_eth_backed(contract_address: &str) -> bool {
+    find_eth_backed(contract_address).is_some()
+}
+
+// ETH redeemable for one token, read at the historical block when given
+pub async fn get_eth_rate(
+    web3: &Web3<Http>,
+    contract_address: &str,
+    historical: Option<&Historical>,
+) -> Result<f64, Box<dyn error::Error>> {
+    let signature = match find_eth_backed(contract_address) {
+        Some((_, _, signature)) => signature,
+        None => {
+            return Err(Box::new(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a wrapped or staked ETH token", contract_address),
+            )))
+        }
+    };
+
+    let signature = match signature {
+        Some(signature) => signature,
+        None => return Ok(1.0),
+    };
+
+    let result = onchain::call(
+        web3,
+        contract_address.parse::<H160>()?,
+        signature,
+        &[],
+        &[ParamType::Uint(256)],
+        historical.map(Historical::get_block_id),
+    )
+    .await?;
+
+    Ok(onchain::scale(onchain::get_uint(&result, 0)?, 18))
+}
+
+fn find_eth_backed(contract_address: &str) -> Option<(&str, &str, Option<&str>)> {
+    let contract_address = normalize_contract_address(contract_address).ok()?;
+
+    ETH_BACKED_TOKENS
+        .into_iter()
+        .find(|(_, eth_backed_address, _)| *eth_backed_address == contract_address)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+
+    #[tokio::test]
+    async fn is_eth_backed_success() {
+        assert!(is_eth_backed("0x7f39c581f595b53c5cb19bd0b3f8DA6c935E2Ca0"));
+        assert!(is_eth_backed("c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2"));
+        // YFI token address
+        assert!(!is_eth_backed("0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e"));
+    }
+
+    #[tokio::test]
+    async fn get_eth_rate_success() {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_infura_key = settings
+            .get::<String>("test_infura")
+            .unwrap_or_else(|_| panic!("test infura key is not set in Settings.toml, exit."));
+
+        let endpoint = format!("https://mainnet.infura.io/v3/{}", test_infura_key);
+        let web3 = Web3::new(Http::new(&endpoint).unwrap());
+
+        // wstETH and rETH are worth more than one ETH since they accrue staking rewards
+        let wsteth_rate = get_eth_rate(&web3, "0x7f39c581f595b53c5cb19bd0b3f8da6c935e2ca0", None)
+            .await
+            .unwrap();
+        let reth_rate = get_eth_rate(&web3, "0xae78736cd615f374d3085123a210448e74fc6393", None)
+            .await
+            .unwrap();
+        let weth_rate = get_eth_rate(&web3, "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2", None)
+            .await
+            .unwrap();
+        assert!(wsteth_rate > 1.0);
+        assert!(reth_rate > 1.0);
+        assert_eq!(weth_rate, 1.0);
+    }
+
+    #[tokio::test]
+    async fn get_eth_rate_fail() {
+        let web3 = Web3::new(Http::new("http://localhost:8545").unwrap());
+
+        // YFI token address
+        let result = get_eth_rate(&web3, "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e", None).await;
+        if let Result::Err(err) = result {
+            assert_eq!(
+                (*err).to_string(),
+                "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e is not a wrapped or staked ETH token"
+            );
+        }
+    }
+}
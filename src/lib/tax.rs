@@ -0,0 +1,472 @@
+use super::cost_basis::{CostBasis, Disposal};
+use chrono::{DateTime, Months};
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub enum Format {
+    #[default]
+    Generic,
+    Koinly,
+    Form8949,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Term {
+    Short,
+    Long,
+}
+
+// Disposal of a token with its capital gain
+#[derive(Debug, Clone, PartialEq)]
+pub struct Gain {
+    pub symbol: String,
+    pub disposal: Disposal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Totals {
+    pub proceeds: f64,
+    pub cost: f64,
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Format::Generic => write!(f, "generic"),
+            Format::Koinly => write!(f, "koinly"),
+            Format::Form8949 => write!(f, "8949"),
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(format: &str) -> Result<Format, String> {
+        match format.to_lowercase().as_str() {
+            "generic" => Ok(Format::Generic),
+            "koinly" => Ok(Format::Koinly),
+            "8949" => Ok(Format::Form8949),
+            _ => Err(format!(
+                "Unknown tax report format {}, expected generic, koinly or 8949",
+                format
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Term::Short => write!(f, "short"),
+            Term::Long => write!(f, "long"),
+        }
+    }
+}
+
+impl Gain {
+    pub fn get_gain(&self) -> Option<f64> {
+        self.disposal.get_gain()
+    }
+
+    // Long term when held for more than a year
+    pub fn get_term(&self) -> Term {
+        let one_year_later = self
+            .disposal
+            .acquired
+            .and_then(|acquired| DateTime::from_timestamp(acquired, 0))
+            .and_then(|acquired| acquired.checked_add_months(Months::new(12)))
+            .map(|one_year_later| one_year_later.timestamp());
+
+        match one_year_later {
+            Some(one_year_later) if self.disposal.disposed > one_year_later => Term::Long,
+            _ => Term::Short,
+        }
+    }
+}
+
+impl Gain {
+    // Disposed without a priced acquisition to match, its cost and gain are left blank
+    pub fn is_missing_basis(&self) -> bool {
+        self.disposal.cost.is_none()
+    }
+
+    // Gas paid in the native asset, a disposal tagged apart from the sales
+    pub fn is_fee(&self) -> bool {
+        self.disposal.fee
+    }
+}
+
+impl Totals {
+    pub fn get_gain(&self) -> f64 {
+        self.proceeds - self.cost
+    }
+}
+
+// Disposals of every token during the year in UTC, oldest first
+pub fn get_gains(cost_bases: &HashMap<String, CostBasis>, start: i64, end: i64) -> Vec<Gain> {
+    let mut gains: Vec<Gain> = cost_bases
+        .values()
+        .flat_map(|cost_basis| {
+            cost_basis
+                .disposals
+                .iter()
+                .filter(|disposal| disposal.disposed >= start && disposal.disposed <= end)
+                .map(|disposal| Gain {
+                    symbol: cost_basis.symbol.to_string(),
+                    disposal: disposal.clone(),
+                })
+        })
+        .collect();
+
+    gains.sort_by(|a, b| {
+        a.disposal
+            .disposed
+            .cmp(&b.disposal.disposed)
+            .then_with(|| a.symbol.cmp(&b.symbol))
+    });
+
+    gains
+}
+
+// Totals of the disposals with a known proceeds and cost
+pub fn get_totals(gains: &[Gain], term: Term) -> Totals {
+    gains
+        .iter()
+        .filter(|gain| gain.get_term() == term)
+        .filter_map(|gain| Some((gain.disposal.proceeds?, gain.disposal.cost?)))
+        .fold(Totals::default(), |totals, (proceeds, cost)| Totals {
+            proceeds: totals.proceeds + proceeds,
+            cost: totals.cost + cost,
+        })
+}
+
+pub fn write_csv<W: io::Write>(
+    writer: W,
+    gains: &[Gain],
+    format: Format,
+) -> Result<(), Box<dyn error::Error>> {
+    let mut writer = csv::Writer::from_writer(writer);
+
+    match format {
+        Format::Generic => {
+            writer.write_record([
+                "Symbol",
+                "Amount",
+                "Date Acquired",
+                "Date Disposed",
+                "Proceeds USD",
+                "Cost USD",
+                "Gain USD",
+                "Term",
+                "Tx Hash",
+                "Missing Basis",
+                "Gas Fee",
+            ])?;
+            for gain in gains {
+                writer.write_record([
+                    gain.symbol.to_string(),
+                    format!("{}", gain.disposal.amount),
+                    format_date(gain.disposal.acquired, "%Y-%m-%d"),
+                    format_date(Some(gain.disposal.disposed), "%Y-%m-%d"),
+                    format_usd(gain.disposal.proceeds),
+                    format_usd(gain.disposal.cost),
+                    format_usd(gain.get_gain()),
+                    gain.get_term().to_string(),
+                    gain.disposal.hash.to_string(),
+                    format_flag(gain.is_missing_basis()),
+                    format_flag(gain.is_fee()),
+                ])?;
+            }
+        }
+        // Koinly universal import, one sell per disposal received as its proceeds in USD,
+        // gas fees are labelled as a cost
+        Format::Koinly => {
+            writer.write_record([
+                "Date",
+                "Sent Amount",
+                "Sent Currency",
+                "Received Amount",
+                "Received Currency",
+                "Fee Amount",
+                "Fee Currency",
+                "Net Worth Amount",
+                "Net Worth Currency",
+                "Label",
+                "Description",
+                "TxHash",
+            ])?;
+            for gain in gains {
+                writer.write_record([
+                    format_date(Some(gain.disposal.disposed), "%Y-%m-%d %H:%M UTC"),
+                    format!("{}", gain.disposal.amount),
+                    gain.symbol.to_string(),
+                    format_usd(gain.disposal.proceeds),
+                    "USD".to_string(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    String::new(),
+                    match gain.is_fee() {
+                        true => "cost".to_string(),
+                        false => String::new(),
+                    },
+                    match gain.is_missing_basis() {
+                        true => "Missing cost basis".to_string(),
+                        false => format!(
+                            "Acquired {} for {} USD",
+                            format_date(gain.disposal.acquired, "%Y-%m-%d"),
+                            format_usd(gain.disposal.cost)
+                        ),
+                    },
+                    gain.disposal.hash.to_string(),
+                ])?;
+            }
+        }
+        // Part I short term rows first, then Part II long term rows
+        Format::Form8949 => {
+            writer.write_record([
+                "Part",
+                "(a) Description of property",
+                "(b) Date acquired",
+                "(c) Date sold or disposed of",
+                "(d) Proceeds",
+                "(e) Cost or other basis",
+                "(f) Code",
+                "(g) Amount of adjustment",
+                "(h) Gain or (loss)",
+            ])?;
+            for (part, term) in [("I", Term::Short), ("II", Term::Long)] {
+                for gain in gains.iter().filter(|gain| gain.get_term() == term) {
+                    writer.write_record([
+                        part.to_string(),
+                        get_description(gain),
+                        format_date(gain.disposal.acquired, "%m/%d/%Y"),
+                        format_date(Some(gain.disposal.disposed), "%m/%d/%Y"),
+                        format_usd(gain.disposal.proceeds),
+                        format_usd(gain.disposal.cost),
+                        String::new(),
+                        String::new(),
+                        format_usd(gain.get_gain()),
+                    ])?;
+                }
+            }
+        }
+    }
+
+    writer.flush()?;
+
+    Ok(())
+}
+
+fn format_date(timestamp: Option<i64>, format: &str) -> String {
+    match timestamp.and_then(|timestamp| DateTime::from_timestamp(timestamp, 0)) {
+        Some(datetime) => datetime.format(format).to_string(),
+        None => String::new(),
+    }
+}
+
+// Amount and symbol, followed by the gas fee and missing basis tags
+fn get_description(gain: &Gain) -> String {
+    let mut description = format!("{} {}", gain.disposal.amount, gain.symbol);
+    if gain.is_fee() {
+        description.push_str(" (gas fee)");
+    }
+    if gain.is_missing_basis() {
+        description.push_str(" (missing basis)");
+    }
+
+    description
+}
+
+fn format_flag(flag: bool) -> String {
+    match flag {
+        true => "yes".to_string(),
+        false => String::new(),
+    }
+}
+
+fn format_usd(usd_value: Option<f64>) -> String {
+    usd_value
+        .map(|usd_value| format!("{:.2}", usd_value))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn get_gain(symbol: &str, acquired: i64, disposed: i64, proceeds: f64, cost: f64) -> Gain {
+        Gain {
+            symbol: symbol.to_string(),
+            disposal: Disposal {
+                hash: "0xabc".to_string(),
+                acquired: Some(acquired),
+                disposed,
+                amount: 1.5,
+                proceeds: Some(proceeds),
+                cost: Some(cost),
+                fee: false,
+            },
+        }
+    }
+
+    fn get_cost_bases() -> HashMap<String, CostBasis> {
+        let mut cost_bases = HashMap::new();
+        cost_bases.insert(
+            "0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e".to_string(),
+            CostBasis {
+                symbol: "YFI".to_string(),
+                lots: vec![],
+                disposals: vec![
+                    get_gain("YFI", 1669852800, 1704067199, 300.0, 100.0).disposal,
+                    get_gain("YFI", 1704067200, 1706745599, 200.0, 250.0).disposal,
+                ],
+            },
+        );
+        cost_bases
+    }
+
+    #[tokio::test]
+    async fn get_term_success() {
+        // 2023-01-01 to 2024-01-01
+        assert_eq!(
+            get_gain("YFI", 1672531200, 1704067200, 0.0, 0.0).get_term(),
+            Term::Short
+        );
+        assert_eq!(
+            get_gain("YFI", 1672531200, 1704067201, 0.0, 0.0).get_term(),
+            Term::Long
+        );
+    }
+
+    #[tokio::test]
+    async fn get_gains_success() {
+        // 2024
+        let gains = get_gains(&get_cost_bases(), 1704067200, 1735689599);
+        assert_eq!(gains.len(), 1);
+        assert_eq!(gains[0].get_gain(), Some(-50.0));
+
+        // 2023
+        let gains = get_gains(&get_cost_bases(), 1672531200, 1704067199);
+        assert_eq!(gains.len(), 1);
+        assert_eq!(gains[0].get_term(), Term::Long);
+        assert_eq!(get_totals(&gains, Term::Long).get_gain(), 200.0);
+        assert_eq!(get_totals(&gains, Term::Short), Totals::default());
+    }
+
+    #[tokio::test]
+    async fn write_csv_generic_success() {
+        let gains = vec![get_gain("YFI", 1669852800, 1704067199, 300.0, 100.0)];
+        let mut output = vec![];
+
+        write_csv(&mut output, &gains, Format::Generic).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "Symbol,Amount,Date Acquired,Date Disposed,Proceeds USD,Cost USD,Gain USD,Term,Tx Hash,Missing Basis,Gas Fee\n\
+            YFI,1.5,2022-12-01,2023-12-31,300.00,100.00,200.00,long,0xabc,,\n"
+        );
+    }
+
+    #[tokio::test]
+    async fn write_csv_missing_basis_success() {
+        let mut gain = get_gain("YFI", 0, 1704067199, 300.0, 0.0);
+        gain.disposal.acquired = None;
+        gain.disposal.cost = None;
+        let gains = vec![gain];
+
+        let mut output = vec![];
+        write_csv(&mut output, &gains, Format::Generic).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().nth(1),
+            Some("YFI,1.5,,2023-12-31,300.00,,,short,0xabc,yes,")
+        );
+
+        let mut output = vec![];
+        write_csv(&mut output, &gains, Format::Form8949).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().nth(1),
+            Some("I,1.5 YFI (missing basis),,12/31/2023,300.00,,,,")
+        );
+        assert_eq!(get_totals(&gains, Term::Short), Totals::default());
+    }
+
+    #[tokio::test]
+    async fn write_csv_fee_success() {
+        let mut gain = get_gain("ETH", 1669852800, 1704067199, 30.0, 10.0);
+        gain.disposal.fee = true;
+        let gains = vec![gain];
+
+        let mut output = vec![];
+        write_csv(&mut output, &gains, Format::Generic).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output.lines().nth(1).unwrap().ends_with(",0xabc,,yes"));
+
+        let mut output = vec![];
+        write_csv(&mut output, &gains, Format::Koinly).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().nth(1),
+            Some("2023-12-31 23:59 UTC,1.5,ETH,30.00,USD,,,,,cost,Acquired 2022-12-01 for 10.00 USD,0xabc")
+        );
+
+        let mut output = vec![];
+        write_csv(&mut output, &gains, Format::Form8949).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert!(output
+            .lines()
+            .nth(1)
+            .unwrap()
+            .starts_with("II,1.5 ETH (gas fee),"));
+    }
+
+    #[tokio::test]
+    async fn write_csv_koinly_success() {
+        let gains = vec![get_gain("YFI", 1669852800, 1704067199, 300.0, 100.0)];
+        let mut output = vec![];
+
+        write_csv(&mut output, &gains, Format::Koinly).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        assert_eq!(
+            output.lines().nth(1),
+            Some("2023-12-31 23:59 UTC,1.5,YFI,300.00,USD,,,,,,Acquired 2022-12-01 for 100.00 USD,0xabc")
+        );
+    }
+
+    #[tokio::test]
+    async fn write_csv_8949_success() {
+        let gains = vec![
+            get_gain("YFI", 1669852800, 1704067199, 300.0, 100.0),
+            get_gain("DAI", 1704067200, 1706745599, 200.0, 250.0),
+        ];
+        let mut output = vec![];
+
+        write_csv(&mut output, &gains, Format::Form8949).unwrap();
+        let output = String::from_utf8(output).unwrap();
+        let lines: Vec<&str> = output.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(
+            lines[1],
+            "I,1.5 DAI,01/01/2024,01/31/2024,200.00,250.00,,,-50.00"
+        );
+        assert_eq!(
+            lines[2],
+            "II,1.5 YFI,12/01/2022,12/31/2023,300.00,100.00,,,200.00"
+        );
+    }
+
+    #[tokio::test]
+    async fn format_from_str_fail() {
+        let result = "turbotax".parse::<Format>();
+        assert_eq!(
+            result,
+            Err("Unknown tax report format turbotax, expected generic, koinly or 8949".to_string())
+        );
+    }
+}
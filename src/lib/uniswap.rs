@@ -0,0 +1,489 @@
+use super::onchain;
+use super::price_provider::{normalize_contract_address, PriceProvider, ETH_ADDRESS};
+use async_trait::async_trait;
+use serde_json::Value;
+use std::error;
+use std::io;
+use web3::ethabi::{ParamType, Token};
+use web3::transports::Http;
+use web3::types::{H160, U256};
+use web3::Web3;
+
+const UNISWAP_V2_FACTORY: &str = "5c69bee701ef814a2b6a3edd4b1652cb9cc5aa6f";
+const UNISWAP_V3_FACTORY: &str = "1f98431c8ad98523631ae4a59f267346ea31f984";
+const WETH_ADDRESS: &str = "c02aaa39b223fe8d0a0e5c4f27ead9083c756cc2";
+const USDC_ADDRESS: &str = "a0b86991c6218b36c1d19d4a2e9eb0ce3606eb48";
+const V3_FEE_TIERS: [u32; 4] = [100, 500, 3000, 10000];
+
+// Minimum liquidity of a pool, measured in WETH on its quote side
+pub const DEFAULT_MIN_LIQUIDITY: f64 = 10.0;
+
+pub struct Uniswap {
+    web3: Web3<Http>,
+    min_liquidity: f64,
+}
+
+// Depth is the quote token held by the pool, the same measure for V2 and V3 pools whereas
+// V3 virtual reserves only cover the liquidity of the current tick
+#[derive(Debug)]
+pub struct Pool {
+    pub address: H160,
+    pub fee: Option<u32>,
+    pub price: f64,
+    pub depth: f64,
+}
+
+#[async_trait]
+impl PriceProvider for Uniswap {
+    fn get_name(&self) -> &str {
+        "uniswap"
+    }
+
+    async fn fetch<'a>(
+        &self,
+        url: &'a str,
+        _verbose: bool,
+    ) -> Result<Value, Box<dyn error::Error>> {
+        Err(Box::new(io::Error::new(
+            io::ErrorKind::Unsupported,
+            format!("Uniswap prices are read on-chain, could not fetch {}", url),
+        )))
+    }
+
+    async fn get_token_id_from_contract_address<'a>(
+        &self,
+        contract_address: &'a str,
+        _verbose: bool,
+    ) -> Result<String, Box<dyn error::Error>> {
+        normalize_contract_address(contract_address)
+    }
+
+    async fn get_token_price<'a>(
+        &self,
+        contract_address: &'a str,
+        versus_name: &'a str,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let token = match contract_address.eq_ignore_ascii_case(ETH_ADDRESS) {
+            true => WETH_ADDRESS.parse::<H160>()?,
+            false => normalize_contract_address(contract_address)?.parse::<H160>()?,
+        };
+
+        let price = match versus_name {
+            "eth" => self.get_price_in_weth(token, verbose).await?,
+            "usd" => self.get_price_in_usdc(token, verbose).await?,
+            _ => {
+                return Err(Box::new(io::Error::new(
+                    io::ErrorKind::ConnectionRefused,
+                    format!("Could not fetch token price versus {}", versus_name),
+                )))
+            }
+        };
+
+        if verbose {
+            println!(
+                "Uniswap price of {:?} versus {}: {}",
+                token, versus_name, price
+            );
+        }
+
+        Ok(price)
+    }
+}
+
+impl Uniswap {
+    pub fn new(endpoint: &str, min_liquidity: f64) -> Result<Uniswap, Box<dyn error::Error>> {
+        let transport = Http::new(endpoint)?;
+
+        Ok(Uniswap {
+            web3: Web3::new(transport),
+            min_liquidity,
+        })
+    }
+
+    async fn get_price_in_weth(
+        &self,
+        token: H160,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let weth: H160 = WETH_ADDRESS.parse()?;
+
+        if token == weth {
+            return Ok(1.0);
+        }
+
+        if let Some(pool) = self
+            .get_deepest_pool(token, weth, self.min_liquidity, verbose)
+            .await?
+        {
+            return Ok(pool.price);
+        }
+
+        let weth_price = self.get_weth_price_in_usdc(verbose).await?;
+        let usdc_price = self
+            .get_price_in_usdc_pools(token, weth_price, verbose)
+            .await?;
+
+        Ok(usdc_price / weth_price)
+    }
+
+    async fn get_price_in_usdc(
+        &self,
+        token: H160,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let weth: H160 = WETH_ADDRESS.parse()?;
+        let usdc: H160 = USDC_ADDRESS.parse()?;
+
+        if token == usdc {
+            return Ok(1.0);
+        }
+
+        let weth_price = self.get_weth_price_in_usdc(verbose).await?;
+
+        if token == weth {
+            return Ok(weth_price);
+        }
+
+        if let Some(pool) = self
+            .get_deepest_pool(token, weth, self.min_liquidity, verbose)
+            .await?
+        {
+            return Ok(pool.price * weth_price);
+        }
+
+        self.get_price_in_usdc_pools(token, weth_price, verbose)
+            .await
+    }
+
+    async fn get_price_in_usdc_pools(
+        &self,
+        token: H160,
+        weth_price: f64,
+        verbose: bool,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let usdc: H160 = USDC_ADDRESS.parse()?;
+
+        match self
+            .get_deepest_pool(token, usdc, self.min_liquidity * weth_price, verbose)
+            .await?
+        {
+            Some(pool) => Ok(pool.price),
+            None => Err(Box::new(io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("No Uniswap pool with enough liquidity for {:?}", token),
+            ))),
+        }
+    }
+
+    async fn get_weth_price_in_usdc(&self, verbose: bool) -> Result<f64, Box<dyn error::Error>> {
+        let weth: H160 = WETH_ADDRESS.parse()?;
+        let usdc: H160 = USDC_ADDRESS.parse()?;
+
+        match self.get_deepest_pool(weth, usdc, 0.0, verbose).await? {
+            Some(pool) => Ok(pool.price),
+            None => Err("No Uniswap pool for WETH/USDC".into()),
+        }
+    }
+
+    pub async fn get_deepest_pool(
+        &self,
+        token: H160,
+        quote: H160,
+        min_depth: f64,
+        verbose: bool,
+    ) -> Result<Option<Pool>, Box<dyn error::Error>> {
+        let pools = self.get_pools(token, quote).await?;
+
+        let pool = pools
+            .into_iter()
+            .filter(|pool| pool.depth >= min_depth)
+            .max_by(|a, b| a.depth.total_cmp(&b.depth));
+
+        if verbose {
+            if let Some(pool) = &pool {
+                println!(
+                    "Using Uniswap pool {:?} with fee tier {:?} and depth {:.2}",
+                    pool.address, pool.fee, pool.depth
+                );
+            }
+        }
+
+        Ok(pool)
+    }
+
+    pub async fn get_pools(
+        &self,
+        token: H160,
+        quote: H160,
+    ) -> Result<Vec<Pool>, Box<dyn error::Error>> {
+        let token_decimals = onchain::get_decimals(&self.web3, token).await?;
+        let quote_decimals = onchain::get_decimals(&self.web3, quote).await?;
+
+        let mut pools = Vec::new();
+
+        if let Ok(Some(pool)) = self
+            .get_v2_pool(token, quote, token_decimals, quote_decimals)
+            .await
+        {
+            pools.push(pool);
+        }
+
+        for fee in V3_FEE_TIERS {
+            if let Ok(Some(pool)) = self
+                .get_v3_pool(token, quote, fee, token_decimals, quote_decimals)
+                .await
+            {
+                pools.push(pool);
+            }
+        }
+
+        Ok(pools)
+    }
+
+    async fn get_v2_pool(
+        &self,
+        token: H160,
+        quote: H160,
+        token_decimals: u32,
+        quote_decimals: u32,
+    ) -> Result<Option<Pool>, Box<dyn error::Error>> {
+        let result = onchain::call(
+            &self.web3,
+            UNISWAP_V2_FACTORY.parse()?,
+            "getPair(address,address)",
+            &[Token::Address(token), Token::Address(quote)],
+            &[ParamType::Address],
+            None,
+        )
+        .await?;
+        let address = onchain::get_address(&result, 0)?;
+
+        if address.is_zero() {
+            return Ok(None);
+        }
+
+        let reserves = onchain::call(
+            &self.web3,
+            address,
+            "getReserves()",
+            &[],
+            &[
+                ParamType::Uint(112),
+                ParamType::Uint(112),
+                ParamType::Uint(32),
+            ],
+            None,
+        )
+        .await?;
+
+        let (token_reserve, quote_reserve) = match token < quote {
+            true => (
+                onchain::get_uint(&reserves, 0)?,
+                onchain::get_uint(&reserves, 1)?,
+            ),
+            false => (
+                onchain::get_uint(&reserves, 1)?,
+                onchain::get_uint(&reserves, 0)?,
+            ),
+        };
+
+        let token_reserve = onchain::scale(token_reserve, token_decimals);
+        let quote_reserve = onchain::scale(quote_reserve, quote_decimals);
+
+        if token_reserve == 0.0 {
+            return Ok(None);
+        }
+
+        Ok(Some(Pool {
+            address,
+            fee: None,
+            price: quote_reserve / token_reserve,
+            depth: self
+                .get_quote_balance(quote, address, quote_decimals)
+                .await?,
+        }))
+    }
+
+    async fn get_v3_pool(
+        &self,
+        token: H160,
+        quote: H160,
+        fee: u32,
+        token_decimals: u32,
+        quote_decimals: u32,
+    ) -> Result<Option<Pool>, Box<dyn error::Error>> {
+        let result = onchain::call(
+            &self.web3,
+            UNISWAP_V3_FACTORY.parse()?,
+            "getPool(address,address,uint24)",
+            &[
+                Token::Address(token),
+                Token::Address(quote),
+                Token::Uint(fee.into()),
+            ],
+            &[ParamType::Address],
+            None,
+        )
+        .await?;
+        let address = onchain::get_address(&result, 0)?;
+
+        if address.is_zero() {
+            return Ok(None);
+        }
+
+        let slot0 = onchain::call(
+            &self.web3,
+            address,
+            "slot0()",
+            &[],
+            &[
+                ParamType::Uint(160),
+                ParamType::Int(24),
+                ParamType::Uint(16),
+                ParamType::Uint(16),
+                ParamType::Uint(16),
+                ParamType::Uint(8),
+                ParamType::Bool,
+            ],
+            None,
+        )
+        .await?;
+        let liquidity = onchain::call(
+            &self.web3,
+            address,
+            "liquidity()",
+            &[],
+            &[ParamType::Uint(128)],
+            None,
+        )
+        .await?;
+
+        let sqrt_price_x96 = onchain::get_uint(&slot0, 0)?;
+        let liquidity = onchain::get_uint(&liquidity, 0)?;
+
+        if sqrt_price_x96.is_zero() || liquidity.is_zero() {
+            return Ok(None);
+        }
+
+        let token_is_token0 = token < quote;
+
+        Ok(Some(Pool {
+            address,
+            fee: Some(fee),
+            price: sqrt_price_to_price(
+                sqrt_price_x96,
+                token_is_token0,
+                token_decimals,
+                quote_decimals,
+            ),
+            depth: self
+                .get_quote_balance(quote, address, quote_decimals)
+                .await?,
+        }))
+    }
+
+    async fn get_quote_balance(
+        &self,
+        quote: H160,
+        pool: H160,
+        quote_decimals: u32,
+    ) -> Result<f64, Box<dyn error::Error>> {
+        let balance = onchain::call(
+            &self.web3,
+            quote,
+            "balanceOf(address)",
+            &[Token::Address(pool)],
+            &[ParamType::Uint(256)],
+            None,
+        )
+        .await?;
+
+        Ok(onchain::scale(
+            onchain::get_uint(&balance, 0)?,
+            quote_decimals,
+        ))
+    }
+}
+
+fn sqrt_price_to_price(
+    sqrt_price_x96: U256,
+    token_is_token0: bool,
+    token_decimals: u32,
+    quote_decimals: u32,
+) -> f64 {
+    let sqrt_price = onchain::u256_to_f64(sqrt_price_x96) / 2_f64.powi(96);
+    let raw_price = match token_is_token0 {
+        true => sqrt_price * sqrt_price,
+        false => 1.0 / (sqrt_price * sqrt_price),
+    };
+
+    raw_price * 10_f64.powi(token_decimals as i32 - quote_decimals as i32)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use config;
+
+    fn get_uniswap() -> Uniswap {
+        let config_builder = config::Config::builder()
+            .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
+        let settings = config_builder.build().unwrap();
+        let test_infura_key = settings
+            .get::<String>("test_infura")
+            .unwrap_or_else(|_| panic!("test infura key is not set in Settings.toml, exit."));
+
+        let endpoint = format!("https://mainnet.infura.io/v3/{}", test_infura_key);
+        Uniswap::new(&endpoint, DEFAULT_MIN_LIQUIDITY).unwrap()
+    }
+
+    #[tokio::test]
+    async fn get_token_price_success() {
+        let uniswap = get_uniswap();
+
+        // UNI token address
+        let contract_address = "0x1f9840a85d5af5bf1d1762f925bdaddc4201f984";
+        let price = uniswap
+            .get_token_price(contract_address, "usd", false)
+            .await
+            .unwrap();
+        let price_eth = uniswap
+            .get_token_price(contract_address, "eth", false)
+            .await
+            .unwrap();
+        assert_ne!(price, 0.0);
+        assert_ne!(price_eth, 0.0);
+    }
+
+    #[tokio::test]
+    async fn get_token_price_weth_success() {
+        let uniswap = get_uniswap();
+        let price_eth = uniswap
+            .get_token_price(&format!("0x{}", WETH_ADDRESS), "eth", false)
+            .await
+            .unwrap();
+        assert_eq!(price_eth, 1.0);
+    }
+
+    #[tokio::test]
+    async fn get_token_price_fail() {
+        let uniswap = get_uniswap();
+
+        // non existent token address
+        let contract_address = "0x0121212121212121212121212212121212121212";
+        let result = uniswap
+            .get_token_price(contract_address, "usd", false)
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn sqrt_price_to_price_success() {
+        // sqrt(4) * 2^96, token0 priced at 4 units of token1 with equal decimals
+        let sqrt_price_x96 = U256::from(2) * U256::from(2).pow(U256::from(96));
+        assert_eq!(sqrt_price_to_price(sqrt_price_x96, true, 18, 18), 4.0);
+        assert_eq!(sqrt_price_to_price(sqrt_price_x96, false, 18, 18), 0.25);
+        assert_eq!(sqrt_price_to_price(sqrt_price_x96, true, 18, 6), 4e12);
+    }
+}
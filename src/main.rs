@@ -1,23 +1,239 @@
 mod lib;
 
 use config::Config;
-use lib::{coingecko, erc20, paraswap, price_provider::PriceProvider, random};
+use lib::chain::{self, Chain};
+use lib::coingecko::Coingecko;
+use lib::cost_basis::{self, CostBasis, Method};
+use lib::diff::{self, Change, PositionDiff};
+use lib::erc1155::{self, MultiToken};
+use lib::fiat::{self, Currencies};
+use lib::gas::{self, GasTotal};
+use lib::historical::{self, Historical};
+use lib::history::{self, Direction, Filter, Transfer};
+use lib::lending::{self, Protocol};
+use lib::nft::{self, Nft};
+use lib::price_provider::{PriceProvider, ETH_ADDRESS};
+use lib::pricing::{self, PriceProviders, PricingConfig};
+use lib::quote::Quote;
+use lib::snapshot::{self, Snapshot, Snapshots};
+use lib::tax::{self, Term};
+use lib::{chart, erc20, onchain, paraswap, random, staking};
 
 #[macro_use]
 extern crate prettytable;
-use clap::Parser;
+use chrono::Utc;
+use clap::{Parser, Subcommand};
 use piechart::{Chart, Data, Style};
-use prettytable::Table;
+use prettytable::{Row, Table};
 use std::cmp::Ordering::Equal;
 use std::error;
 
 #[derive(Parser, Debug)]
-#[command(author, version, about, long_about = None)]
+#[command(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
 struct Args {
-    #[arg(short, long)]
-    address: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[arg(short, long, required = true)]
+    address: Option<String>,
     #[arg(short, long, default_value_t = false)]
     verbose: bool,
+    /// Value every position by a ParaSwap quote for its full balance
+    #[arg(short, long, default_value_t = false)]
+    liquidation: bool,
+    /// Query every price provider and take the median price
+    #[arg(short, long, default_value_t = false)]
+    consensus: bool,
+    /// Fiat currencies to show balances in, e.g. eur,chf
+    #[arg(long, value_delimiter = ',', default_value = fiat::DEFAULT_CURRENCY)]
+    currency: Vec<String>,
+    /// Token to denominate the portfolio in, a symbol like btc or steth or a contract address
+    #[arg(short, long)]
+    quote: Option<String>,
+    /// Value the portfolio at a past block number or YYYY-MM-DD date, needs an archive RPC
+    #[arg(long)]
+    at: Option<String>,
+    /// Save the scan as a snapshot in the SQLite database
+    #[arg(short, long, default_value_t = false)]
+    save: bool,
+    /// Add cost basis and PnL columns from the transfer history, matching lots by fifo, lifo or hifo
+    #[arg(long)]
+    cost_basis: Option<Method>,
+    /// List the ERC721 NFTs held, valued at the floor price of their collection
+    #[arg(long, default_value_t = false)]
+    nft: bool,
+    /// List the ERC1155 tokens held, found from their transfer events
+    #[arg(long, default_value_t = false)]
+    erc1155: bool,
+    /// Value the Aave V3 and Compound positions, supplies and debts, on Ethereum mainnet
+    #[arg(long, default_value_t = false)]
+    lending: bool,
+    /// Add an ETH exposure row counting wrapped and staked ETH at their exchange rate
+    #[arg(long, default_value_t = false)]
+    eth_exposure: bool,
+    /// Chain to scan, ethereum, polygon, arbitrum, optimism, base, bsc or one from [chains] in Settings.toml
+    #[arg(long, default_value = chain::DEFAULT_CHAIN, conflicts_with = "all_chains")]
+    chain: String,
+    /// Scan the address on every chain and add up the totals
+    #[arg(long, default_value_t = false)]
+    all_chains: bool,
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Browse the saved portfolio snapshots
+    Snapshots {
+        #[command(subcommand)]
+        command: SnapshotsCommand,
+    },
+    /// Compare two snapshots, or a snapshot against the live portfolio
+    Diff {
+        /// Id of the older snapshot
+        from: i64,
+        /// Id of the newer snapshot, the live portfolio of the same address when omitted
+        to: Option<i64>,
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
+    },
+    /// Chart the value of the saved snapshots of an address over time
+    Chart {
+        #[arg(short, long)]
+        address: String,
+        /// Chart a single token, by symbol or contract address
+        #[arg(short, long)]
+        token: Option<String>,
+        /// Range to chart, e.g. 30d, 12w, 6m, 1y or a YYYY-MM-DD date
+        #[arg(long, default_value = "30d")]
+        since: String,
+        /// Price the balances of the latest snapshot with CoinGecko daily prices instead
+        #[arg(short, long, default_value_t = false)]
+        backfill: bool,
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
+    },
+    /// List the ETH, internal and ERC20 transfers of an address with their US$ value at the time
+    History {
+        #[arg(short, long)]
+        address: String,
+        /// Only transfers of a token, by symbol or contract address
+        #[arg(short, long)]
+        token: Option<String>,
+        /// Only incoming or outgoing transfers, in or out
+        #[arg(short, long)]
+        direction: Option<Direction>,
+        /// Only transfers since 30d, 12w, 6m, 1y or a YYYY-MM-DD date
+        #[arg(long)]
+        since: Option<String>,
+        /// Only transfers until a YYYY-MM-DD date, inclusive
+        #[arg(long)]
+        until: Option<String>,
+        /// Chain the transfers were made on
+        #[arg(long, default_value = chain::DEFAULT_CHAIN)]
+        chain: String,
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
+    },
+    /// Export the capital gains of an address over a calendar year as CSV
+    Tax {
+        #[arg(short, long)]
+        address: String,
+        #[arg(short, long)]
+        year: i32,
+        /// Lot matching method, fifo, lifo or hifo
+        #[arg(short, long, default_value = "fifo")]
+        method: Method,
+        /// CSV layout, generic, koinly or 8949
+        #[arg(short, long, default_value = "generic")]
+        format: tax::Format,
+        /// CSV file to write, tax-<year>-<format>.csv when omitted
+        #[arg(short, long)]
+        output: Option<String>,
+        /// Chain the disposals were made on
+        #[arg(long, default_value = chain::DEFAULT_CHAIN)]
+        chain: String,
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
+    },
+    /// Report the ETH spent on gas by an address, by month and by contract called
+    Gas {
+        #[arg(short, long)]
+        address: String,
+        /// First block of the range, the first block of the chain when omitted
+        #[arg(long)]
+        startblock: Option<u64>,
+        /// Last block of the range, the latest block when omitted
+        #[arg(long)]
+        endblock: Option<u64>,
+        /// Chain the gas was spent on
+        #[arg(long, default_value = chain::DEFAULT_CHAIN)]
+        chain: String,
+        #[arg(short, long, default_value_t = false)]
+        verbose: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum SnapshotsCommand {
+    /// List saved snapshots, optionally of one address
+    List {
+        #[arg(short, long)]
+        address: Option<String>,
+    },
+    /// Show the positions of a saved snapshot
+    Show { id: i64 },
+}
+
+struct ScanOptions {
+    quote: Option<String>,
+    at: Option<String>,
+    save: bool,
+    nft: bool,
+    erc1155: bool,
+    lending: bool,
+    eth_exposure: bool,
+}
+
+struct ChartOptions {
+    token: Option<String>,
+    since: String,
+    backfill: bool,
+}
+
+struct TaxOptions {
+    year: i32,
+    method: Method,
+    format: tax::Format,
+    output: String,
+}
+
+struct Scan {
+    eth_info: erc20::TokenInfo,
+    list_erc20: erc20::Tokens,
+    nfts: Vec<Nft>,
+    multi_tokens: Vec<MultiToken>,
+    lending: Vec<Protocol>,
+    block: u64,
+    timestamp: i64,
+}
+
+struct ChainTotal {
+    chain: Chain,
+    native_balance: f64,
+    usd_total: f64,
+}
+
+struct Columns {
+    liquidation: bool,
+    consensus: bool,
+    currencies: Vec<String>,
+    quote: Option<Quote>,
+    cost_basis: Option<Method>,
 }
 
 #[tokio::main]
@@ -28,182 +244,1691 @@ async fn main() -> Result<(), Box<dyn error::Error>> {
         .add_source(config::File::new("Settings.toml", config::FileFormat::Toml));
     let settings = config_builder.build()?;
 
+    match args.command {
+        Some(Command::Snapshots { command }) => return browse_snapshots(command, &settings),
+        Some(Command::Diff { from, to, verbose }) => {
+            return compare_snapshots(from, to, &settings, verbose).await
+        }
+        Some(Command::Chart {
+            address,
+            token,
+            since,
+            backfill,
+            verbose,
+        }) => {
+            let chart_options = ChartOptions {
+                token,
+                since,
+                backfill,
+            };
+            return chart_value(address, chart_options, &settings, verbose).await;
+        }
+        Some(Command::History {
+            address,
+            token,
+            direction,
+            since,
+            until,
+            chain,
+            verbose,
+        }) => {
+            let chain = chain::load_chain(&settings, &chain)?;
+            let now = Utc::now().timestamp();
+            let filter = Filter {
+                token,
+                direction,
+                since: match since {
+                    Some(since) => Some(historical::parse_since(&since, now)?),
+                    None => None,
+                },
+                until: match until {
+                    Some(until) => Some(historical::parse_date(&until)?),
+                    None => None,
+                },
+            };
+            return list_history(address, filter, &chain, &settings, verbose).await;
+        }
+        Some(Command::Tax {
+            address,
+            year,
+            method,
+            format,
+            output,
+            chain,
+            verbose,
+        }) => {
+            let chain = chain::load_chain(&settings, &chain)?;
+            let output = output.unwrap_or_else(|| format!("tax-{}-{}.csv", year, format));
+            let tax_options = TaxOptions {
+                year,
+                method,
+                format,
+                output,
+            };
+            return export_tax_report(address, tax_options, &chain, &settings, verbose).await;
+        }
+        Some(Command::Gas {
+            address,
+            startblock,
+            endblock,
+            chain,
+            verbose,
+        }) => {
+            let chain = chain::load_chain(&settings, &chain)?;
+            let list_config = erc20::ListConfig::new(startblock, endblock, false, verbose);
+            return report_gas_spend(address, list_config, &chain, &settings).await;
+        }
+        None => (),
+    }
+
     let verbose: bool = args.verbose;
 
-    let address = args.address;
+    let columns = Columns {
+        liquidation: args.liquidation,
+        consensus: args.consensus,
+        currencies: args.currency,
+        quote: None,
+        cost_basis: args.cost_basis,
+    };
+
+    let options = ScanOptions {
+        quote: args.quote,
+        at: args.at,
+        save: args.save,
+        nft: args.nft,
+        erc1155: args.erc1155,
+        lending: args.lending,
+        eth_exposure: args.eth_exposure,
+    };
 
-    scan_balances(address, settings, verbose).await?;
+    let address = args.address.ok_or("Missing address")?;
+
+    let chains = match args.all_chains {
+        true => chain::load_chains(&settings)?,
+        false => vec![chain::load_chain(&settings, &args.chain)?],
+    };
+
+    scan_balances(address, chains, settings, verbose, columns, options).await?;
 
     Ok(())
 }
 
 async fn scan_balances(
     address: String,
+    chains: Vec<Chain>,
     settings: Config,
     verbose: bool,
+    mut columns: Columns,
+    options: ScanOptions,
 ) -> Result<(), Box<dyn error::Error>> {
-    let infura_key = settings.get::<String>("infura")?;
-    let etherscan_key = settings.get::<String>("etherscan")?;
-    let ethplorer_key = settings.get::<String>("ethplorer")?;
+    let address = parse_address(address)?;
+
+    if options.eth_exposure && chains.iter().all(|chain| !chain.is_eth_native()) {
+        return Err("ETH exposure needs a chain paying gas in ETH".into());
+    }
+
+    // Snapshots have no chain, they hold the mainnet balances
+    if options.save && chains.iter().any(|chain| !chain.is_ethereum()) {
+        return Err("Snapshots can only be saved on ethereum".into());
+    }
+
+    if chains.len() > 1
+        && options
+            .at
+            .as_deref()
+            .is_some_and(|at| at.parse::<u64>().is_ok())
+    {
+        return Err(
+            "Block numbers differ between chains, use a YYYY-MM-DD date with --all-chains".into(),
+        );
+    }
+
+    let mut data = vec![];
+    let mut chain_totals = vec![];
+
+    for chain in &chains {
+        let scan = match scan(address, chain, &settings, verbose, &mut columns, &options).await {
+            Ok(scan) => scan,
+            Err(err) if chains.len() > 1 => {
+                println!("Skipping {}: {}", chain.name, err);
+                continue;
+            }
+            Err(err) => return Err(err),
+        };
+
+        if options.save {
+            let snapshot = Snapshot::new(
+                &format!("{:?}", address),
+                scan.block,
+                scan.timestamp,
+                &scan.eth_info,
+                &scan.list_erc20,
+            );
+            let id = Snapshots::open(&get_database(&settings))?.save(&snapshot)?;
+            println!("Saved snapshot {} at block {}", id, scan.block);
+        }
+
+        let native_balance = scan.eth_info.balance;
+        let (chain_data, usd_total) = print_scan(scan, chain, &columns, &options);
+
+        match chains.len() {
+            1 => data = chain_data,
+            _ => {
+                if usd_total > 0.0 {
+                    data.push(Data {
+                        label: chain.name.to_string(),
+                        value: get_chart_value(usd_total, &columns),
+                        color: Some(Style::new().fg(random::get_color())),
+                        fill: random::get_char(),
+                    });
+                }
+            }
+        }
+
+        chain_totals.push(ChainTotal {
+            chain: chain.clone(),
+            native_balance,
+            usd_total,
+        });
+    }
+
+    if chains.len() > 1 {
+        println!("Total on all chains:");
+        let mut table = Table::new();
+        fill_table_with_chain_totals(&mut table, &chain_totals);
+        table.printstd();
+    }
+
+    data.sort_by(|a, b| b.value.partial_cmp(&a.value).unwrap_or(Equal));
+
+    Chart::new()
+        .radius(20)
+        .aspect_ratio(4)
+        .legend(true)
+        .draw(&data);
+
+    Ok(())
+}
+
+// Prints the tables of a chain, returns its pie slices and its total with the tokens at floor
+// price and lending positions
+fn print_scan(
+    scan: Scan,
+    chain: &Chain,
+    columns: &Columns,
+    options: &ScanOptions,
+) -> (Vec<Data>, f64) {
+    match options.at {
+        Some(_) => println!(
+            "Balance of ERC20 tokens on {} at block {} ({}):",
+            chain.name,
+            scan.block,
+            historical::format_timestamp(scan.timestamp)
+        ),
+        None => println!("Balance of ERC20 tokens on {}:", chain.name),
+    }
+
+    let mut data = vec![Data {
+        label: chain.native_asset.to_string(),
+        value: get_chart_value(scan.eth_info.usd_balance, columns),
+        color: Some(Style::new().fg(random::get_color())),
+        fill: random::get_char(),
+    }];
+
+    // aTokens and cTokens are valued through their protocol instead, snapshots keep them
+    let mut list_erc20 = scan.list_erc20;
+    if options.lending && chain.is_ethereum() {
+        let receipt_tokens: Vec<&String> = scan
+            .lending
+            .iter()
+            .flat_map(|protocol| &protocol.receipt_tokens)
+            .collect();
+        list_erc20.retain(|_, token_info| match token_info {
+            Some(token_info) => {
+                !receipt_tokens.contains(&&token_info.contract_address.to_lowercase())
+            }
+            None => true,
+        });
+    }
+
+    let mut table = Table::new();
+
+    fill_table_with_eth(&mut table, &scan.eth_info, &chain.native_asset, columns);
+    if options.eth_exposure && chain.is_eth_native() {
+        let eth_exposure = get_eth_exposure(&scan.eth_info, &list_erc20, columns);
+        table.add_row(get_token_row(
+            "ETH EXPOSURE",
+            &eth_exposure,
+            &chain.native_asset,
+            columns,
+        ));
+    }
+    let usd_total = fill_table_with_erc20(
+        &mut table,
+        &scan.eth_info,
+        list_erc20,
+        &mut data,
+        &chain.native_asset,
+        columns,
+    );
+
+    table.printstd();
+
+    if options.eth_exposure && !chain.is_eth_native() {
+        println!(
+            "No ETH exposure row on {}, its native asset is {}",
+            chain.name, chain.native_asset
+        );
+    }
+
+    let mut extra_usd_totals = vec![];
+    let mut descriptions = vec![];
+
+    if options.erc1155 {
+        println!("ERC1155 tokens:");
+        let mut multi_token_table = Table::new();
+        fill_table_with_multi_tokens(&mut multi_token_table, &scan.multi_tokens);
+        multi_token_table.printstd();
+
+        extra_usd_totals.push(("ERC1155", erc1155::get_usd_total(&scan.multi_tokens)));
+    }
+
+    if options.nft {
+        println!("ERC721 tokens:");
+        let mut nft_table = Table::new();
+        fill_table_with_nfts(&mut nft_table, &scan.nfts);
+        nft_table.printstd();
+
+        extra_usd_totals.push(("NFTs", nft::get_floor_usd_total(&scan.nfts)));
+    }
+
+    match (options.nft, options.erc1155) {
+        (true, true) => descriptions.push("NFTs and ERC1155 tokens at floor price"),
+        (true, false) => descriptions.push("NFTs at floor price"),
+        (false, true) => descriptions.push("ERC1155 tokens at floor price"),
+        (false, false) => (),
+    }
+
+    if options.lending && chain.is_ethereum() {
+        println!("Lending positions:");
+        let mut position_table = Table::new();
+        fill_table_with_lending_positions(&mut position_table, &scan.lending);
+        position_table.printstd();
+
+        println!("Lending protocols:");
+        let mut protocol_table = Table::new();
+        fill_table_with_lending_protocols(&mut protocol_table, &scan.lending);
+        protocol_table.printstd();
+
+        match lending::get_net_usd_total(&scan.lending) {
+            Some(net_usd_total) => {
+                extra_usd_totals.push(("Lending", net_usd_total));
+                descriptions.push("lending positions");
+            }
+            None => println!("Lending positions are left out of the total, some have no price"),
+        }
+    }
+
+    let extra_usd_total = extra_usd_totals
+        .iter()
+        .fold(0.0, |total, (_, extra_usd_total)| total + extra_usd_total);
+
+    if !extra_usd_totals.is_empty() {
+        for (label, extra_usd_total) in &extra_usd_totals {
+            if *extra_usd_total > 0.0 {
+                data.push(Data {
+                    label: label.to_string(),
+                    value: get_chart_value(*extra_usd_total, columns),
+                    color: Some(Style::new().fg(random::get_color())),
+                    fill: random::get_char(),
+                });
+            }
+        }
+        println!(
+            "Total with {}: {:.2} US$",
+            descriptions.join(" and "),
+            usd_total + extra_usd_total
+        );
+    }
+
+    (data, usd_total + extra_usd_total)
+}
+
+fn parse_address(address: String) -> Result<web3::types::H160, Box<dyn error::Error>> {
+    let mut raw_address = address;
+
+    if let Some(stripped) = raw_address.strip_prefix("0x") {
+        raw_address = stripped.to_owned();
+    }
+
+    let address = raw_address
+        .parse::<web3::types::H160>()
+        .map_err(|err| format!("Error at specified address: {}. {:?}", raw_address, err))?;
+
+    Ok(address)
+}
+
+async fn scan(
+    address: web3::types::H160,
+    chain: &Chain,
+    settings: &Config,
+    verbose: bool,
+    columns: &mut Columns,
+    options: &ScanOptions,
+) -> Result<Scan, Box<dyn error::Error>> {
+    let ethplorer_key = match chain.is_ethereum() {
+        true => settings.get::<String>("ethplorer")?,
+        false => String::new(),
+    };
+
+    let endpoint = chain.rpc_url.to_string();
+
+    if verbose {
+        println!("Address: {}", address)
+    }
+
+    if verbose {
+        println!("Calling balance...");
+    }
+
+    let transport = web3::transports::Http::new(&endpoint)?;
+    let web3 = web3::Web3::new(transport);
+
+    let historical = match &options.at {
+        Some(at) => Some(Historical::resolve(at, &web3, &chain.explorer, verbose).await?),
+        None => None,
+    };
+
+    if historical.is_some() && columns.liquidation {
+        return Err("Liquidation values are only available at the latest block".into());
+    }
+
+    let mut pricing = PricingConfig::from_settings(settings)?;
+    pricing.chain = chain.clone();
+    let price_providers = PriceProviders::new(&pricing, Some(&endpoint))?;
+    let currencies = Currencies::load(&columns.currencies, verbose).await?;
+    columns.currencies = currencies.names.clone();
+
+    // Loaded once, quote symbols are mainnet tokens priced on mainnet whichever chain is scanned
+    if let (Some(quote), None) = (&options.quote, &columns.quote) {
+        columns.quote = Some(match chain.is_ethereum() {
+            true => {
+                Quote::load(quote, &price_providers, &web3, historical.as_ref(), verbose).await?
+            }
+            false => load_mainnet_quote(quote, settings, historical.as_ref(), verbose).await?,
+        });
+    }
+
+    let mut eth_info = get_eth_balance(
+        web3.clone(),
+        address,
+        chain,
+        &price_providers.get_chain(ETH_ADDRESS),
+        &currencies,
+        historical.as_ref(),
+        verbose,
+    )
+    .await?;
+
+    if verbose {
+        println!(
+            "{} balance of {:?} on {}: {:.6} / {:.2} US$",
+            chain.native_asset, address, chain.name, eth_info.balance, eth_info.usd_balance
+        );
+    }
+
+    if columns.liquidation {
+        eth_info.liquidation_usd =
+            get_eth_liquidation_value(eth_info.balance, chain, verbose).await;
+    }
+
+    println!("Loading ERC20 token transactions, this will take a while...");
+
+    let endblock = historical.map(|historical| historical.block);
+    let mut list_config = erc20::ListConfig::new(None, endblock, true, verbose);
+    list_config.liquidation = columns.liquidation;
+    list_config.consensus = columns.consensus;
+    list_config.endpoint = Some(endpoint);
+    list_config.pricing = pricing;
+    list_config.currencies = currencies;
+    list_config.historical = historical;
+
+    let mut list_erc20 =
+        erc20::list_erc20_for_account(address, &chain.explorer, &ethplorer_key, list_config)
+            .await?;
+
+    if !chain.is_eth_native() {
+        for token_info in list_erc20.values_mut().flatten() {
+            token_info.set_native_price(eth_info.usd_price);
+        }
+    }
+
+    let (block, timestamp) = match historical {
+        Some(historical) => (historical.block, historical.timestamp),
+        None => (
+            web3.eth().block_number().await?.as_u64(),
+            Utc::now().timestamp(),
+        ),
+    };
+
+    if let Some(method) = columns.cost_basis {
+        println!("Loading transfer history for the cost basis, this will take a while...");
+
+        let contract_addresses: Vec<String> = list_erc20
+            .values()
+            .flatten()
+            .map(|token_info| token_info.contract_address.to_lowercase())
+            .chain([ETH_ADDRESS.to_string()])
+            .collect();
+
+        let mut transfers = history::list_transfers_for_account(address, chain, verbose)
+            .await?
+            .into_iter()
+            .filter(|transfer| {
+                transfer.block <= block && contract_addresses.contains(&transfer.contract_address)
+            })
+            .collect::<Vec<Transfer>>();
+        history::price_transfers(&mut transfers, &price_providers, verbose).await;
+
+        let cost_bases = cost_basis::compute(&transfers, method);
+        let missing_basis_count: usize = cost_bases
+            .values()
+            .map(CostBasis::get_missing_basis_count)
+            .sum();
+        if missing_basis_count > 0 {
+            println!(
+                "{} disposals without a known price or acquisition are left out of the realized PnL",
+                missing_basis_count
+            );
+        }
+        eth_info.set_cost_basis(cost_bases.get(ETH_ADDRESS));
+        for token_info in list_erc20.values_mut().flatten() {
+            token_info.set_cost_basis(cost_bases.get(&token_info.contract_address.to_lowercase()));
+        }
+    }
+
+    let mut nfts = vec![];
+    if options.nft {
+        println!("Loading ERC721 token transactions...");
+        nfts = nft::list_erc721_for_account(
+            &web3,
+            address,
+            &chain.explorer,
+            historical.as_ref(),
+            verbose,
+        )
+        .await?;
+
+        // Floor prices are current ones, useless at a past block
+        if historical.is_none() {
+            nft::price_nfts(&mut nfts, &Coingecko::new(chain), verbose).await;
+        }
+    }
+
+    let mut multi_tokens = vec![];
+    if options.erc1155 {
+        println!("Loading ERC1155 transfer events...");
+        multi_tokens =
+            erc1155::list_erc1155_for_account(&web3, address, historical.as_ref(), verbose).await?;
+
+        if historical.is_none() {
+            erc1155::price_multi_tokens(&mut multi_tokens, &Coingecko::new(chain), verbose).await;
+        }
+    }
+
+    // Only the Ethereum mainnet deployments of the lending protocols are known
+    let mut lending = vec![];
+    if options.lending && chain.is_ethereum() {
+        println!("Loading lending positions...");
+        lending =
+            lending::list_lending_for_account(&web3, address, historical.as_ref(), verbose).await;
+        lending::price_protocols(&mut lending, &price_providers, historical.as_ref(), verbose)
+            .await;
+    }
+
+    Ok(Scan {
+        eth_info,
+        list_erc20,
+        nfts,
+        multi_tokens,
+        lending,
+        block,
+        timestamp,
+    })
+}
+
+// Quote priced on mainnet at the same moment as a scan of another chain
+async fn load_mainnet_quote(
+    quote: &str,
+    settings: &Config,
+    historical: Option<&Historical>,
+    verbose: bool,
+) -> Result<Quote, Box<dyn error::Error>> {
+    let chain = chain::load_chain(settings, chain::DEFAULT_CHAIN)?;
+    let web3 = web3::Web3::new(web3::transports::Http::new(&chain.rpc_url)?);
+
+    let historical = match historical {
+        Some(historical) => Some(historical.on_chain(&chain.explorer).await?),
+        None => None,
+    };
+
+    let mut pricing = PricingConfig::from_settings(settings)?;
+    pricing.chain = chain.clone();
+    let price_providers = PriceProviders::new(&pricing, Some(&chain.rpc_url))?;
+
+    Quote::load(quote, &price_providers, &web3, historical.as_ref(), verbose).await
+}
+
+fn browse_snapshots(
+    command: SnapshotsCommand,
+    settings: &Config,
+) -> Result<(), Box<dyn error::Error>> {
+    let snapshots = Snapshots::open(&get_database(settings))?;
+
+    let mut table = Table::new();
+
+    match command {
+        SnapshotsCommand::List { address } => {
+            let list = snapshots.list(address.as_deref())?;
+            fill_table_with_snapshots(&mut table, &list);
+        }
+        SnapshotsCommand::Show { id } => {
+            let snapshot = snapshots.get(id)?;
+            println!(
+                "Snapshot {} of {} at block {} ({}):",
+                snapshot.id,
+                snapshot.address,
+                snapshot.block,
+                historical::format_timestamp(snapshot.timestamp)
+            );
+            fill_table_with_snapshot(&mut table, &snapshot);
+        }
+    }
+
+    table.printstd();
+
+    Ok(())
+}
+
+async fn compare_snapshots(
+    from: i64,
+    to: Option<i64>,
+    settings: &Config,
+    verbose: bool,
+) -> Result<(), Box<dyn error::Error>> {
+    let snapshots = Snapshots::open(&get_database(settings))?;
+
+    let from = snapshots.get(from)?;
+    let to = match to {
+        Some(to) => snapshots.get(to)?,
+        None => {
+            let mut columns = Columns {
+                liquidation: false,
+                consensus: false,
+                currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+                quote: None,
+                cost_basis: None,
+            };
+            let options = ScanOptions {
+                quote: None,
+                at: None,
+                save: false,
+                nft: false,
+                erc1155: false,
+                lending: false,
+                eth_exposure: false,
+            };
+            let address = parse_address(from.address.to_string())?;
+            let chain = chain::load_chain(settings, chain::DEFAULT_CHAIN)?;
+            let scan = scan(address, &chain, settings, verbose, &mut columns, &options).await?;
+            Snapshot::new(
+                &from.address,
+                scan.block,
+                scan.timestamp,
+                &scan.eth_info,
+                &scan.list_erc20,
+            )
+        }
+    };
+
+    println!(
+        "Changes of {} from block {} ({}) to block {} ({}):",
+        to.address,
+        from.block,
+        historical::format_timestamp(from.timestamp),
+        to.block,
+        historical::format_timestamp(to.timestamp)
+    );
+
+    let mut table = Table::new();
+    fill_table_with_diff(&mut table, &from, &to, &diff::diff_snapshots(&from, &to));
+    table.printstd();
+
+    Ok(())
+}
+
+async fn chart_value(
+    address: String,
+    chart_options: ChartOptions,
+    settings: &Config,
+    verbose: bool,
+) -> Result<(), Box<dyn error::Error>> {
+    let address = format!("{:?}", parse_address(address)?);
+    let now = Utc::now().timestamp();
+    let since = historical::parse_since(&chart_options.since, now)?;
+    let token = chart_options.token.as_deref();
+
+    let snapshots = Snapshots::open(&get_database(settings))?.list(Some(&address))?;
+
+    if let Some(token) = token {
+        let held = snapshots.iter().any(|snapshot| {
+            snapshot
+                .positions
+                .iter()
+                .any(|position| position.matches(token))
+        });
+        if !held {
+            return Err(format!("No {} position in the snapshots of {}", token, address).into());
+        }
+    }
+
+    let points: Vec<(i64, f64)> = match chart_options.backfill {
+        true => {
+            let latest = snapshots
+                .last()
+                .ok_or_else(|| format!("No snapshots of {} to backfill from", address))?;
+            get_backfilled_values(latest, token, since, now, verbose).await?
+        }
+        false => snapshots
+            .iter()
+            .filter(|snapshot| snapshot.timestamp >= since)
+            .map(|snapshot| (snapshot.timestamp, get_snapshot_value(snapshot, token)))
+            .collect(),
+    };
+
+    if points.is_empty() {
+        return Err(format!(
+            "No values of {} since {}",
+            address,
+            historical::format_timestamp(since)
+        )
+        .into());
+    }
+
+    println!(
+        "{} value of {} in US$ since {}:",
+        token.unwrap_or("Portfolio"),
+        address,
+        historical::format_timestamp(since)
+    );
+    for line in chart::render(&points, chart::DEFAULT_WIDTH, chart::DEFAULT_HEIGHT) {
+        println!("{}", line);
+    }
+
+    Ok(())
+}
+
+fn get_snapshot_value(snapshot: &Snapshot, token: Option<&str>) -> f64 {
+    snapshot
+        .positions
+        .iter()
+        .filter(|position| token.is_none_or(|token| position.matches(token)))
+        .map(|position| position.get_usd_balance())
+        .sum()
+}
 
-    let endpoint = format!("https://mainnet.infura.io/v3/{}", infura_key);
+// Balances are held constant at the ones of the snapshot
+async fn get_backfilled_values(
+    snapshot: &Snapshot,
+    token: Option<&str>,
+    since: i64,
+    now: i64,
+    verbose: bool,
+) -> Result<Vec<(i64, f64)>, Box<dyn error::Error>> {
+    let days = ((now - since) / 86400 + 1) as u32;
+    let mut values = vec![];
+
+    let positions = snapshot
+        .positions
+        .iter()
+        .filter(|position| token.is_none_or(|token| position.matches(token)));
+
+    for position in positions {
+        let token_id = Coingecko::default()
+            .get_token_id_from_contract_address(&position.contract_address, verbose)
+            .await
+            .unwrap_or_default();
+        if token_id.is_empty() {
+            if verbose {
+                println!("No CoinGecko history for {}, skipping", position.symbol);
+            }
+            continue;
+        }
+
+        let prices = Coingecko::default()
+            .get_daily_prices(&token_id, "usd", days, verbose)
+            .await?;
+        values.push(
+            prices
+                .into_iter()
+                .filter(|(timestamp, _)| *timestamp >= since)
+                .map(|(timestamp, price)| (timestamp, position.balance * price))
+                .collect(),
+        );
+    }
+
+    Ok(chart::sum_by_day(&values))
+}
+
+async fn list_history(
+    address: String,
+    filter: Filter,
+    chain: &Chain,
+    settings: &Config,
+    verbose: bool,
+) -> Result<(), Box<dyn error::Error>> {
+    let address = parse_address(address)?;
+    let endpoint = &chain.rpc_url;
+
+    println!("Loading transactions, this will take a while...");
+
+    let all_transfers = history::list_transfers_for_account(address, chain, verbose).await?;
+    let ledger_balance = history::get_ledger_balance(&all_transfers, ETH_ADDRESS);
+
+    let mut transfers = all_transfers
+        .into_iter()
+        .filter(|transfer| filter.matches(transfer))
+        .collect::<Vec<Transfer>>();
+
+    let mut pricing = PricingConfig::from_settings(settings)?;
+    pricing.chain = chain.clone();
+    let price_providers = PriceProviders::new(&pricing, Some(endpoint))?;
+    history::price_transfers(&mut transfers, &price_providers, verbose).await;
+
+    println!("Transfers of {:?} on {}:", address, chain.name);
+
+    let mut table = Table::new();
+    fill_table_with_transfers(&mut table, &transfers);
+    table.printstd();
+
+    let web3 = web3::Web3::new(web3::transports::Http::new(endpoint)?);
+    let balance = onchain::scale(web3.eth().balance(address, None).await?, 18);
+
+    // Beacon chain withdrawals and block rewards are not transactions and stay unexplained
+    println!(
+        "{} ledger: {}, balance: {}, unexplained: {}",
+        chain.native_asset,
+        format_native(ledger_balance, &chain.native_asset),
+        format_native(balance, &chain.native_asset),
+        format_native(balance - ledger_balance, &chain.native_asset)
+    );
+
+    Ok(())
+}
+
+async fn export_tax_report(
+    address: String,
+    tax_options: TaxOptions,
+    chain: &Chain,
+    settings: &Config,
+    verbose: bool,
+) -> Result<(), Box<dyn error::Error>> {
+    let address = parse_address(address)?;
+
+    let start = historical::parse_date(&format!("{}-01-01", tax_options.year))? - 86399;
+    let end = historical::parse_date(&format!("{}-12-31", tax_options.year))?;
+
+    println!("Loading transactions, this will take a while...");
+
+    // Earlier transfers are needed for the cost of the tokens disposed during the year
+    let mut transfers = history::list_transfers_for_account(address, chain, verbose)
+        .await?
+        .into_iter()
+        .filter(|transfer| transfer.timestamp <= end)
+        .collect::<Vec<Transfer>>();
+
+    let mut pricing = PricingConfig::from_settings(settings)?;
+    pricing.chain = chain.clone();
+    let price_providers = PriceProviders::new(&pricing, Some(&chain.rpc_url))?;
+    history::price_transfers(&mut transfers, &price_providers, verbose).await;
+
+    let cost_bases = cost_basis::compute(&transfers, tax_options.method);
+    let gains = tax::get_gains(&cost_bases, start, end);
+
+    tax::write_csv(
+        std::fs::File::create(&tax_options.output)?,
+        &gains,
+        tax_options.format,
+    )?;
+    println!(
+        "Wrote {} disposals of {:?} on {} in {} to {}",
+        gains.len(),
+        address,
+        chain.name,
+        tax_options.year,
+        tax_options.output
+    );
+
+    let missing_basis_count = gains.iter().filter(|gain| gain.is_missing_basis()).count();
+    if missing_basis_count > 0 {
+        println!(
+            "{} disposals have no known cost basis, marked in the report and left out of the totals",
+            missing_basis_count
+        );
+    }
+
+    let mut table = Table::new();
+    fill_table_with_tax_totals(&mut table, &gains);
+    table.printstd();
+
+    Ok(())
+}
+
+async fn report_gas_spend(
+    address: String,
+    list_config: erc20::ListConfig,
+    chain: &Chain,
+    settings: &Config,
+) -> Result<(), Box<dyn error::Error>> {
+    let address = parse_address(address)?;
+
+    println!("Loading transactions, this will take a while...");
+
+    let mut gas_spends =
+        gas::list_gas_spend_for_account(address, &chain.explorer, &list_config).await?;
+
+    if chain.is_op_stack() {
+        println!("Loading the L1 data fees of the transactions...");
+        let web3 = web3::Web3::new(web3::transports::Http::new(&chain.rpc_url)?);
+        gas::add_l1_fees(&mut gas_spends, &web3, list_config.verbose).await;
+    }
+
+    let mut pricing = PricingConfig::from_settings(settings)?;
+    pricing.chain = chain.clone();
+    let price_providers = PriceProviders::new(&pricing, Some(&chain.rpc_url))?;
+    gas::price_gas_spends(&mut gas_spends, &price_providers, list_config.verbose).await;
+
+    let total = gas::get_total(&gas_spends);
+
+    println!(
+        "Gas spent by {:?} on {} from block {} to block {}:",
+        address, chain.name, list_config.startblock, list_config.endblock
+    );
+    let mut table = Table::new();
+    fill_table_with_gas_totals(
+        &mut table,
+        "MONTH",
+        &gas::group_by_month(&gas_spends),
+        &total,
+        &chain.native_asset,
+    );
+    table.printstd();
+
+    println!("Gas spent by contract called:");
+    let mut table = Table::new();
+    fill_table_with_gas_totals(
+        &mut table,
+        "CONTRACT ADDRESS",
+        &gas::group_by_contract(&gas_spends),
+        &total,
+        &chain.native_asset,
+    );
+    table.printstd();
+
+    Ok(())
+}
+
+fn get_database(settings: &Config) -> String {
+    settings
+        .get::<String>("database")
+        .unwrap_or_else(|_| snapshot::DEFAULT_DATABASE.to_string())
+}
+
+fn fill_table_with_snapshots(table: &mut Table, snapshots: &[Snapshot]) {
+    table.add_row(row![
+        "ID",
+        "DATE",
+        "BLOCK",
+        "ADDRESS",
+        "TOKENS",
+        "TOTAL ETH",
+        "TOTAL USD"
+    ]);
+
+    for snapshot in snapshots {
+        table.add_row(row![
+            snapshot.id,
+            historical::format_timestamp(snapshot.timestamp),
+            snapshot.block,
+            snapshot.address,
+            snapshot.positions.len(),
+            format!("{:.6} Ξ", snapshot.get_eth_balance()),
+            format!("{:.2} US$", snapshot.get_usd_balance())
+        ]);
+    }
+}
+
+fn fill_table_with_transfers(table: &mut Table, transfers: &[Transfer]) {
+    table.add_row(row![
+        "DATE",
+        "TYPE",
+        "DIRECTION",
+        "TOKEN",
+        "AMOUNT",
+        "COUNTERPARTY",
+        "VALUE USD",
+        "TX HASH"
+    ]);
+
+    for transfer in transfers {
+        table.add_row(row![
+            historical::format_timestamp(transfer.timestamp),
+            transfer.kind,
+            transfer.direction,
+            transfer.symbol,
+            format!("{:.6}", transfer.amount),
+            transfer.counterparty,
+            transfer
+                .usd_value
+                .map(|usd_value| format!("{:.2} US$", usd_value))
+                .unwrap_or_default(),
+            transfer.hash
+        ]);
+    }
+}
+
+fn fill_table_with_tax_totals(table: &mut Table, gains: &[tax::Gain]) {
+    table.add_row(row!["TERM", "PROCEEDS USD", "COST USD", "GAIN USD"]);
+
+    for (label, term) in [("Short term", Term::Short), ("Long term", Term::Long)] {
+        let totals = tax::get_totals(gains, term);
+        table.add_row(row![
+            label,
+            format!("{:.2} US$", totals.proceeds),
+            format!("{:.2} US$", totals.cost),
+            format!("{:.2} US$", totals.get_gain())
+        ]);
+    }
+}
+
+fn fill_table_with_gas_totals(
+    table: &mut Table,
+    key_title: &str,
+    gas_totals: &[GasTotal],
+    total: &GasTotal,
+    native_asset: &str,
+) {
+    table.add_row(row![
+        key_title,
+        "TRANSACTIONS",
+        format!("GAS {}", native_asset),
+        "GAS USD"
+    ]);
+
+    for gas_total in gas_totals.iter().chain([total]) {
+        table.add_row(row![
+            gas_total.key,
+            gas_total.transactions,
+            format_native(gas_total.eth, native_asset),
+            format_usd(gas_total.usd_value)
+        ]);
+    }
+}
+
+fn fill_table_with_chain_totals(table: &mut Table, chain_totals: &[ChainTotal]) {
+    table.add_row(row!["CHAIN", "NATIVE BALANCE", "TOTAL USD"]);
+
+    for chain_total in chain_totals {
+        table.add_row(row![
+            chain_total.chain.name,
+            format!(
+                "{:.6} {}",
+                chain_total.native_balance, chain_total.chain.native_asset
+            ),
+            format!("{:.2} US$", chain_total.usd_total)
+        ]);
+    }
+
+    let usd_total = chain_totals
+        .iter()
+        .fold(0.0, |total, chain_total| total + chain_total.usd_total);
+    table.add_row(row!["TOTAL", "", format!("{:.2} US$", usd_total)]);
+}
+
+fn fill_table_with_snapshot(table: &mut Table, snapshot: &Snapshot) {
+    table.add_row(row![
+        "TOKEN",
+        "CONTRACT ADDRESS",
+        "TOKEN BALANCE",
+        "PRICE USD",
+        "TOTAL ETH",
+        "TOTAL USD",
+        "PRICE SOURCE"
+    ]);
+
+    for position in &snapshot.positions {
+        table.add_row(row![
+            position.symbol,
+            position.contract_address,
+            format!("{:.6}", position.balance),
+            format!("{:.2} US$", position.usd_price),
+            format!("{:.6} Ξ", position.get_eth_balance()),
+            format!("{:.2} US$", position.get_usd_balance()),
+            position.price_source
+        ]);
+    }
+
+    table.add_row(row![
+        "TOTAL",
+        "",
+        "",
+        "",
+        format!("{:.6} Ξ", snapshot.get_eth_balance()),
+        format!("{:.2} US$", snapshot.get_usd_balance()),
+        ""
+    ]);
+}
+
+fn fill_table_with_diff(table: &mut Table, from: &Snapshot, to: &Snapshot, diffs: &[PositionDiff]) {
+    table.add_row(row![
+        "TOKEN",
+        "CONTRACT ADDRESS",
+        "CHANGE",
+        "BALANCE FROM",
+        "BALANCE TO",
+        "BALANCE DELTA",
+        "VALUE FROM",
+        "VALUE TO",
+        "VALUE DELTA",
+        "DELTA %",
+        "PRICE EFFECT",
+        "QUANTITY EFFECT"
+    ]);
+
+    let mut total_price_effect = 0.0;
+    let mut total_quantity_effect = 0.0;
+
+    for diff in diffs {
+        total_price_effect += diff.price_effect;
+        total_quantity_effect += diff.quantity_effect;
+
+        let change = match diff.change {
+            Change::Added => "added",
+            Change::Removed => "removed",
+            Change::Changed => "",
+        };
+
+        table.add_row(row![
+            diff.symbol,
+            diff.contract_address,
+            change,
+            format!("{:.6}", diff.from_balance),
+            format!("{:.6}", diff.to_balance),
+            format!("{:+.6}", diff.get_balance_delta()),
+            format!("{:.2} US$", diff.from_usd_value),
+            format!("{:.2} US$", diff.to_usd_value),
+            format!("{:+.2} US$", diff.get_usd_delta()),
+            format_percent(diff.get_usd_delta_percent()),
+            format!("{:+.2} US$", diff.price_effect),
+            format!("{:+.2} US$", diff.quantity_effect)
+        ]);
+    }
+
+    let from_usd_balance = from.get_usd_balance();
+    let to_usd_balance = to.get_usd_balance();
+    let total_percent = match from_usd_balance > 0.0 {
+        true => Some((to_usd_balance - from_usd_balance) / from_usd_balance * 100.0),
+        false => None,
+    };
+
+    table.add_row(row![
+        "TOTAL",
+        "",
+        "",
+        "",
+        "",
+        "",
+        format!("{:.2} US$", from_usd_balance),
+        format!("{:.2} US$", to_usd_balance),
+        format!("{:+.2} US$", to_usd_balance - from_usd_balance),
+        format_percent(total_percent),
+        format!("{:+.2} US$", total_price_effect),
+        format!("{:+.2} US$", total_quantity_effect)
+    ]);
+}
+
+fn format_percent(percent: Option<f64>) -> String {
+    match percent {
+        Some(percent) => format!("{:+.2} %", percent),
+        None => "-".to_string(),
+    }
+}
+
+fn fill_table_with_eth(
+    table: &mut Table,
+    eth_info: &erc20::TokenInfo,
+    native_asset: &str,
+    columns: &Columns,
+) {
+    let mut header = row![
+        "TOKEN",
+        "CONTRACT ADDRESS",
+        "TOKEN BALANCE",
+        format!("TOTAL {}", native_asset)
+    ];
+
+    if let Some(quote) = &columns.quote {
+        header.add_cell(cell!(format!("TOTAL {}", quote.symbol)));
+    }
+
+    for currency in &columns.currencies {
+        header.add_cell(cell!(format!("TOTAL {}", currency.to_uppercase())));
+    }
+    header.add_cell(cell!("COINGECKO LINK"));
+
+    if columns.liquidation {
+        header.add_cell(cell!("LIQUIDATION USD"));
+        header.add_cell(cell!("PRICE IMPACT %"));
+    }
+
+    if columns.consensus {
+        header.add_cell(cell!("PRICE SOURCE"));
+        header.add_cell(cell!("SPREAD %"));
+    }
+
+    if columns.cost_basis.is_some() {
+        header.add_cell(cell!("COST BASIS USD"));
+        header.add_cell(cell!("UNREALIZED PNL USD"));
+        header.add_cell(cell!("REALIZED PNL USD"));
+    }
+
+    table.add_row(header);
+    table.add_row(get_token_row(native_asset, eth_info, native_asset, columns));
+}
+
+// Rows of the tokens worth at least a cent and their TOTAL row, returns the US$ total
+fn fill_table_with_erc20(
+    table: &mut Table,
+    eth_info: &erc20::TokenInfo,
+    list_erc20: erc20::Tokens,
+    data: &mut Vec<Data>,
+    native_asset: &str,
+    columns: &Columns,
+) -> f64 {
+    let mut total_eth_balance = eth_info.eth_balance;
+    let mut total_usd_balance = eth_info.usd_balance;
+    // Only the balances ParaSwap quoted add up to the liquidation total and its price impact
+    let mut total_liquidation_usd = eth_info.liquidation_usd.unwrap_or(0.0);
+    let mut total_quoted_usd_balance = match eth_info.liquidation_usd {
+        Some(_) => eth_info.usd_balance,
+        None => 0.0,
+    };
+    let mut total_fiat_balances = get_fiat_balances(eth_info, columns);
+    let mut total_cost_basis = eth_info.cost_basis.unwrap_or(0.0);
+    let mut total_unrealized_pnl = eth_info.get_unrealized_pnl().unwrap_or(0.0);
+    let mut total_realized_pnl = eth_info.realized_pnl.unwrap_or(0.0);
+
+    for (token_symbol, values) in &list_erc20 {
+        match values {
+            Some(values) => {
+                let usd_balance: f64 = values.usd_balance;
+                let eth_balance: f64 = values.eth_balance;
+
+                if usd_balance >= 0.01 {
+                    total_usd_balance += usd_balance;
+                    total_eth_balance += eth_balance;
+                    if let Some(liquidation_usd) = values.liquidation_usd {
+                        total_liquidation_usd += liquidation_usd;
+                        total_quoted_usd_balance += usd_balance;
+                    }
+                    total_cost_basis += values.cost_basis.unwrap_or(0.0);
+                    total_unrealized_pnl += values.get_unrealized_pnl().unwrap_or(0.0);
+                    total_realized_pnl += values.realized_pnl.unwrap_or(0.0);
+                    for (total, fiat_balance) in total_fiat_balances
+                        .iter_mut()
+                        .zip(get_fiat_balances(values, columns))
+                    {
+                        *total = add_fiat_balance(*total, fiat_balance);
+                    }
+
+                    table.add_row(get_token_row(token_symbol, values, native_asset, columns));
+
+                    data.push(Data {
+                        label: token_symbol.into(),
+                        value: get_chart_value(usd_balance, columns),
+                        color: Some(Style::new().fg(random::get_color())),
+                        fill: random::get_char(),
+                    });
+                }
+            }
+            None => (),
+        }
+    }
+
+    let mut total_row = row![
+        "TOTAL",
+        "",
+        "",
+        format_native(total_eth_balance, native_asset)
+    ];
+
+    add_quote_cell(&mut total_row, total_usd_balance, columns);
+
+    add_fiat_cells(&mut total_row, &total_fiat_balances, columns);
+    total_row.add_cell(cell!(""));
+
+    if columns.liquidation {
+        add_liquidation_cells(
+            &mut total_row,
+            total_quoted_usd_balance,
+            Some(total_liquidation_usd),
+        );
+    }
+
+    if columns.consensus {
+        total_row.add_cell(cell!(""));
+        total_row.add_cell(cell!(""));
+    }
+
+    if columns.cost_basis.is_some() {
+        add_cost_basis_cells(
+            &mut total_row,
+            Some(total_cost_basis),
+            Some(total_unrealized_pnl),
+            Some(total_realized_pnl),
+        );
+    }
+
+    table.add_row(total_row);
+
+    total_usd_balance
+}
+
+fn fill_table_with_nfts(table: &mut Table, nfts: &[Nft]) {
+    table.add_row(row![
+        "COLLECTION",
+        "CONTRACT ADDRESS",
+        "TOKEN ID",
+        "TOKEN URI",
+        "FLOOR USD"
+    ]);
+
+    for nft in nfts {
+        table.add_row(row![
+            nft.collection,
+            nft.contract_address,
+            nft.token_id,
+            nft.token_uri.as_deref().unwrap_or("-"),
+            nft.floor_usd
+                .map(|floor_usd| format!("{:.2} US$", floor_usd))
+                .unwrap_or_else(|| "-".to_string())
+        ]);
+    }
+
+    table.add_row(row![
+        "TOTAL",
+        "",
+        "",
+        "",
+        format!("{:.2} US$", nft::get_floor_usd_total(nfts))
+    ]);
+}
+
+fn fill_table_with_multi_tokens(table: &mut Table, multi_tokens: &[MultiToken]) {
+    table.add_row(row![
+        "COLLECTION",
+        "CONTRACT ADDRESS",
+        "TOKEN ID",
+        "TOKEN BALANCE",
+        "TOTAL USD"
+    ]);
+
+    for multi_token in multi_tokens {
+        table.add_row(row![
+            multi_token.collection,
+            multi_token.contract_address,
+            multi_token.token_id,
+            multi_token.balance,
+            multi_token
+                .get_usd_value()
+                .map(|usd_value| format!("{:.2} US$", usd_value))
+                .unwrap_or_default()
+        ]);
+    }
+
+    table.add_row(row![
+        "TOTAL",
+        "",
+        "",
+        "",
+        format!("{:.2} US$", erc1155::get_usd_total(multi_tokens))
+    ]);
+}
 
-    let mut raw_address = address;
+fn fill_table_with_lending_positions(table: &mut Table, protocols: &[Protocol]) {
+    table.add_row(row![
+        "PROTOCOL",
+        "TOKEN",
+        "CONTRACT ADDRESS",
+        "SUPPLIED",
+        "BORROWED",
+        "SUPPLIED USD",
+        "BORROWED USD"
+    ]);
 
-    if let Some(stripped) = raw_address.strip_prefix("0x") {
-        raw_address = stripped.to_owned();
+    for protocol in protocols {
+        for position in &protocol.positions {
+            table.add_row(row![
+                protocol.name,
+                position.symbol,
+                position.contract_address,
+                format!("{:.6}", position.supplied),
+                format!("{:.6}", position.borrowed),
+                format_usd(position.get_supplied_usd()),
+                format_usd(position.get_borrowed_usd())
+            ]);
+        }
     }
+}
 
-    let address = raw_address
-        .parse::<web3::types::H160>()
-        .map_err(|err| format!("Error at specified address: {}. {:?}", raw_address, err))?;
+// Health factors below 1 can be liquidated, accounts without debt have none
+fn fill_table_with_lending_protocols(table: &mut Table, protocols: &[Protocol]) {
+    table.add_row(row![
+        "PROTOCOL",
+        "SUPPLIED USD",
+        "BORROWED USD",
+        "NET USD",
+        "HEALTH FACTOR"
+    ]);
 
-    if verbose {
-        println!("Address: {}", address)
+    for protocol in protocols {
+        table.add_row(row![
+            protocol.name,
+            format_usd(protocol.get_supplied_usd()),
+            format_usd(protocol.get_borrowed_usd()),
+            format_usd(protocol.get_net_usd()),
+            protocol
+                .health_factor
+                .map(|health_factor| format!("{:.2}", health_factor))
+                .unwrap_or_else(|| "-".to_string())
+        ]);
     }
 
-    if verbose {
-        println!("Calling balance...");
-    }
+    table.add_row(row![
+        "TOTAL",
+        "",
+        "",
+        format_usd(lending::get_net_usd_total(protocols)),
+        ""
+    ]);
+}
 
-    let transport = web3::transports::Http::new(&endpoint)?;
-    let web3 = web3::Web3::new(transport);
+fn format_usd(usd_value: Option<f64>) -> String {
+    match usd_value {
+        Some(usd_value) => format!("{:.2} US$", usd_value),
+        None => "-".to_string(),
+    }
+}
 
-    let (eth_balance, eth_balance_vs_usd) = get_eth_balance(web3, address, verbose).await?;
+// Native ETH with the wrapped and staked ETH held, left out of the TOTAL row
+fn get_eth_exposure(
+    eth_info: &erc20::TokenInfo,
+    list_erc20: &erc20::Tokens,
+    columns: &Columns,
+) -> erc20::TokenInfo {
+    let eth_backed: Vec<&erc20::TokenInfo> = list_erc20
+        .values()
+        .flatten()
+        .filter(|token_info| {
+            staking::is_eth_backed(&token_info.contract_address) && token_info.usd_balance >= 0.01
+        })
+        .collect();
 
-    if verbose {
-        println!(
-            "ETH balance of {:?}: {:.6} Ξ / {:.2} US$",
-            address, eth_balance, eth_balance_vs_usd
-        );
+    let balance = eth_backed
+        .iter()
+        .fold(eth_info.balance, |total, token_info| {
+            total + token_info.eth_balance
+        });
+    let mut fiat_balances = get_fiat_balances(eth_info, columns);
+    for token_info in &eth_backed {
+        for (total, fiat_balance) in fiat_balances
+            .iter_mut()
+            .zip(get_fiat_balances(token_info, columns))
+        {
+            *total = add_fiat_balance(*total, fiat_balance);
+        }
     }
 
-    println!("Loading ERC20 token transactions, this will take a while...");
+    let mut eth_exposure = erc20::TokenInfo::new("", &balance, &eth_info.usd_price, &1.0, "");
+    eth_exposure.usd_balance = eth_backed
+        .iter()
+        .fold(eth_info.usd_balance, |total, token_info| {
+            total + token_info.usd_balance
+        });
+    eth_exposure.fiat_balances = columns
+        .currencies
+        .iter()
+        .cloned()
+        .zip(fiat_balances)
+        .filter_map(|(currency, fiat_balance)| Some((currency, fiat_balance?)))
+        .collect();
 
-    let list_config = erc20::ListConfig::new(None, None, true, verbose);
+    eth_exposure
+}
 
-    let list_erc20 =
-        erc20::list_erc20_for_account(address, &etherscan_key, &ethplorer_key, list_config).await?;
+fn get_token_row(
+    token_symbol: &str,
+    token_info: &erc20::TokenInfo,
+    native_asset: &str,
+    columns: &Columns,
+) -> Row {
+    let mut token_row = row![
+        token_symbol,
+        token_info.contract_address,
+        format!("{:.6}", token_info.balance),
+        format_native(token_info.eth_balance, native_asset)
+    ];
 
-    println!("Balance of ERC20 tokens:");
+    add_quote_cell(&mut token_row, token_info.usd_balance, columns);
 
-    let mut data = vec![Data {
-        label: "ETH".into(),
-        value: eth_balance_vs_usd as f32,
-        color: Some(Style::new().fg(random::get_color())),
-        fill: random::get_char(),
-    }];
+    add_fiat_cells(
+        &mut token_row,
+        &get_fiat_balances(token_info, columns),
+        columns,
+    );
+    token_row.add_cell(cell!(token_info.coingecko_link));
 
-    let mut table = Table::new();
+    if columns.liquidation {
+        add_liquidation_cells(
+            &mut token_row,
+            token_info.usd_balance,
+            token_info.liquidation_usd,
+        );
+    }
 
-    fill_table_with_eth(&mut table, eth_balance, eth_balance_vs_usd);
-    fill_table_with_erc20(
-        &mut table,
-        eth_balance,
-        eth_balance_vs_usd,
-        list_erc20,
-        &mut data,
-    );
+    if columns.consensus {
+        let spread = match token_info.price_spread {
+            Some(spread) => format!("{:.2} %", spread),
+            None => "-".to_string(),
+        };
+        token_row.add_cell(cell!(token_info.price_source));
+        token_row.add_cell(cell!(spread));
+    }
 
-    table.printstd();
+    if columns.cost_basis.is_some() {
+        add_cost_basis_cells(
+            &mut token_row,
+            token_info.cost_basis,
+            token_info.get_unrealized_pnl(),
+            token_info.realized_pnl,
+        );
+    }
 
-    data.sort_by(|a, b| b.value.partial_cmp(&a.value).unwrap_or(Equal));
+    token_row
+}
 
-    Chart::new()
-        .radius(20)
-        .aspect_ratio(4)
-        .legend(true)
-        .draw(&data);
+fn format_native(balance: f64, native_asset: &str) -> String {
+    match native_asset {
+        "ETH" => format!("{:.6} Ξ", balance),
+        _ => format!("{:.6} {}", balance, native_asset),
+    }
+}
 
-    Ok(())
+fn add_quote_cell(row: &mut Row, usd_balance: f64, columns: &Columns) {
+    if let Some(quote) = &columns.quote {
+        row.add_cell(cell!(format!(
+            "{:.6} {}",
+            quote.convert(usd_balance),
+            quote.symbol
+        )));
+    }
 }
 
-fn fill_table_with_eth(table: &mut Table, eth_balance: f64, eth_balance_vs_usd: f64) {
-    table.add_row(row![
-        "TOKEN",
-        "CONTRACT ADDRESS",
-        "TOKEN BALANCE",
-        "TOTAL ETH",
-        "TOTAL USD",
-        "COINGECKO LINK"
-    ]);
-    table.add_row(row![
-        "ETH",
-        "",
-        format!("{:.6}", eth_balance),
-        format!("{:.6} Ξ", eth_balance),
-        format!("{:.2} US$", eth_balance_vs_usd),
-        "https://coingecko.com/en/coins/ethereum".to_string()
-    ]);
+fn get_chart_value(usd_balance: f64, columns: &Columns) -> f32 {
+    match &columns.quote {
+        Some(quote) => quote.convert(usd_balance) as f32,
+        None => usd_balance as f32,
+    }
 }
 
-fn fill_table_with_erc20(
-    table: &mut Table,
-    mut total_eth_balance: f64,
-    mut total_usd_balance: f64,
-    list_erc20: erc20::Tokens,
-    data: &mut Vec<Data>,
-) {
-    for (token_symbol, values) in &list_erc20 {
-        match values {
-            Some(values) => {
-                let balance: f64 = values.balance;
-                let usd_balance: f64 = values.usd_balance;
-                let eth_balance: f64 = values.eth_balance;
-                let coingecko_link: &String = &values.coingecko_link;
+// None for a currency the balance could not be converted to
+fn get_fiat_balances(token_info: &erc20::TokenInfo, columns: &Columns) -> Vec<Option<f64>> {
+    columns
+        .currencies
+        .iter()
+        .map(|currency| {
+            token_info
+                .fiat_balances
+                .iter()
+                .find(|(name, _)| name == currency)
+                .map(|(_, fiat_balance)| *fiat_balance)
+        })
+        .collect()
+}
 
-                if usd_balance >= 0.01 {
-                    total_usd_balance += usd_balance;
-                    total_eth_balance += eth_balance;
+// A total is unknown as soon as one of its balances is
+fn add_fiat_balance(total: Option<f64>, fiat_balance: Option<f64>) -> Option<f64> {
+    Some(total? + fiat_balance?)
+}
 
-                    table.add_row(row![
-                        token_symbol,
-                        values.contract_address,
-                        format!("{:.6}", balance),
-                        format!("{:.6} Ξ", eth_balance),
-                        format!("{:.2} US$", usd_balance),
-                        coingecko_link.to_string()
-                    ]);
+fn add_fiat_cells(row: &mut Row, fiat_balances: &[Option<f64>], columns: &Columns) {
+    for (currency, fiat_balance) in columns.currencies.iter().zip(fiat_balances) {
+        match fiat_balance {
+            Some(fiat_balance) => row.add_cell(cell!(format!(
+                "{:.2} {}",
+                fiat_balance,
+                fiat::get_symbol(currency)
+            ))),
+            None => row.add_cell(cell!("")),
+        }
+    }
+}
 
-                    data.push(Data {
-                        label: token_symbol.into(),
-                        value: usd_balance as f32,
-                        color: Some(Style::new().fg(random::get_color())),
-                        fill: random::get_char(),
-                    });
-                }
-            }
-            None => (),
+fn add_liquidation_cells(row: &mut Row, usd_balance: f64, liquidation_usd: Option<f64>) {
+    match liquidation_usd {
+        Some(liquidation_usd) => {
+            let price_impact = match usd_balance > 0.0 {
+                true => (usd_balance - liquidation_usd) / usd_balance * 100.0,
+                false => 0.0,
+            };
+            row.add_cell(cell!(format!("{:.2} US$", liquidation_usd)));
+            row.add_cell(cell!(format!("{:.2} %", price_impact)));
+        }
+        None => {
+            row.add_cell(cell!("-"));
+            row.add_cell(cell!("-"));
+        }
+    }
+}
+
+fn add_cost_basis_cells(
+    row: &mut Row,
+    cost_basis: Option<f64>,
+    unrealized_pnl: Option<f64>,
+    realized_pnl: Option<f64>,
+) {
+    for value in [cost_basis, unrealized_pnl, realized_pnl] {
+        match value {
+            Some(value) => row.add_cell(cell!(format!("{:.2} US$", value))),
+            None => row.add_cell(cell!("-")),
         }
     }
-    table.add_row(row![
-        "TOTAL",
-        "",
-        "",
-        format!("{:.6} Ξ", total_eth_balance),
-        format!("{:.2} US$", total_usd_balance),
-        ""
-    ]);
 }
 
 async fn get_eth_balance(
     web3: web3::Web3<web3::transports::Http>,
     address: web3::types::H160,
+    chain: &Chain,
+    price_providers: &[&dyn PriceProvider],
+    currencies: &Currencies,
+    historical: Option<&Historical>,
     verbose: bool,
-) -> Result<(f64, f64), Box<dyn error::Error>> {
-    let price_provider = coingecko::Coingecko;
-    let balance = web3.eth().balance(address, None).await?.low_u64();
-    let eth_balance = balance as f64 / 10_u64.pow(18) as f64;
-    let eth_balance_vs_usd = eth_balance
-        * price_provider
-            .get_token_price("ethereum", "usd", verbose)
-            .await?;
-    Ok((eth_balance, eth_balance_vs_usd))
+) -> Result<erc20::TokenInfo, Box<dyn error::Error>> {
+    let block = historical.map(Historical::get_block_number);
+    let balance = web3.eth().balance(address, block).await?;
+    let eth_balance = onchain::scale(balance, 18);
+    let (price_source, eth_price_vs_usd) =
+        pricing::get_first_price(price_providers, ETH_ADDRESS, "usd", historical, verbose)
+            .await
+            .ok_or_else(|| {
+                format!(
+                    "Could not fetch {} price from any price provider",
+                    chain.native_asset
+                )
+            })?;
+
+    let mut eth_info = erc20::TokenInfo::new(
+        "",
+        &eth_balance,
+        &eth_price_vs_usd,
+        &1.0,
+        &chain.get_coingecko_link(),
+    );
+    eth_info.price_source = price_source;
+    eth_info.fiat_balances = vec![];
+
+    for currency in &currencies.names {
+        let fiat_providers: Vec<&dyn PriceProvider> = price_providers
+            .iter()
+            .copied()
+            .filter(|price_provider| {
+                currency != fiat::DEFAULT_CURRENCY && price_provider.supports_versus(currency)
+            })
+            .collect();
+
+        let fiat_balance = match pricing::get_first_price(
+            &fiat_providers,
+            ETH_ADDRESS,
+            currency,
+            historical,
+            verbose,
+        )
+        .await
+        {
+            Some((_, eth_price)) => Some(eth_balance * eth_price),
+            None => currencies.convert(currency, eth_info.usd_balance),
+        };
+        if let Some(fiat_balance) = fiat_balance {
+            eth_info
+                .fiat_balances
+                .push((currency.to_string(), fiat_balance));
+        }
+    }
+
+    Ok(eth_info)
+}
+
+async fn get_eth_liquidation_value(eth_balance: f64, chain: &Chain, verbose: bool) -> Option<f64> {
+    paraswap::Paraswap::new(chain)?
+        .get_liquidation_value(ETH_ADDRESS, eth_balance, "usd", verbose)
+        .await
+        .ok()
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::lib::chainlink;
     use config;
     use web3::types::H160;
 
@@ -222,29 +1947,588 @@ mod test {
         let endpoint = format!("https://mainnet.infura.io/v3/{}", test_infura_key);
         let transport = web3::transports::Http::new(&endpoint).unwrap();
         let web3 = web3::Web3::new(transport);
+        let price_provider = chainlink::Chainlink::new(
+            web3.clone(),
+            chainlink::default_feeds(),
+            chainlink::DEFAULT_MAX_AGE,
+        );
 
-        let (eth_balance, eth_balance_vs_usd) = get_eth_balance(web3, test_account_address, false)
-            .await
-            .unwrap();
-        assert_ne!(eth_balance, 0.0);
-        assert_ne!(eth_balance_vs_usd, 0.0);
+        let eth_info = get_eth_balance(
+            web3,
+            test_account_address,
+            &Chain::default(),
+            &[&price_provider],
+            &Currencies::default(),
+            None,
+            false,
+        )
+        .await
+        .unwrap();
+        assert_ne!(eth_info.balance, 0.0);
+        assert_ne!(eth_info.usd_balance, 0.0);
+        assert_eq!(eth_info.price_source, "chainlink");
     }
 
     #[tokio::test]
     async fn fill_table_with_eth_success() {
         let mut table = Table::new();
-        let eth_balance = 0.0;
-        let eth_balance_vs_usd = 0.0;
+        let eth_info = erc20::TokenInfo::new("", &0.0, &0.0, &1.0, "");
+        let columns = Columns {
+            liquidation: false,
+            consensus: false,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: None,
+            cost_basis: None,
+        };
+
+        fill_table_with_eth(&mut table, &eth_info, "ETH", &columns);
+        assert_eq!(table.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_eth_native_asset_success() {
+        let mut table = Table::new();
+        let eth_info = erc20::TokenInfo::new("", &100.0, &0.25, &1.0, "");
+        let columns = Columns {
+            liquidation: false,
+            consensus: false,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: None,
+            cost_basis: None,
+        };
+
+        fill_table_with_eth(&mut table, &eth_info, "POL", &columns);
+        assert_eq!(table[0][3].get_content(), "TOTAL POL");
+        assert_eq!(table[1][0].get_content(), "POL");
+        assert_eq!(table[1][3].get_content(), "100.000000 POL");
+    }
+
+    #[tokio::test]
+    async fn get_eth_exposure_success() {
+        let eth_info = erc20::TokenInfo::new("", &1.0, &2000.0, &1.0, "");
+        let mut list_erc20 = erc20::Tokens::new();
+        // wstETH, worth 1.2 ETH at its exchange rate
+        list_erc20.insert(
+            "wstETH".to_string(),
+            Some(erc20::TokenInfo::new(
+                "0x7f39c581f595b53c5cb19bd0b3f8da6c935e2ca0",
+                &2.0,
+                &2400.0,
+                &1.2,
+                "",
+            )),
+        );
+        // YFI
+        list_erc20.insert(
+            "YFI".to_string(),
+            Some(erc20::TokenInfo::new(
+                "0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e",
+                &1.0,
+                &6000.0,
+                &3.0,
+                "",
+            )),
+        );
+        let columns = Columns {
+            liquidation: false,
+            consensus: false,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: None,
+            cost_basis: None,
+        };
+
+        let eth_exposure = get_eth_exposure(&eth_info, &list_erc20, &columns);
+        assert!((eth_exposure.balance - 3.4).abs() < 1e-9);
+        assert_eq!(eth_exposure.usd_balance, 6800.0);
+        assert_eq!(
+            eth_exposure.fiat_balances,
+            vec![(fiat::DEFAULT_CURRENCY.to_string(), 6800.0)]
+        );
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_eth_liquidation_success() {
+        let mut table = Table::new();
+        let mut eth_info = erc20::TokenInfo::new("", &1.0, &2000.0, &1.0, "");
+        eth_info.liquidation_usd = Some(1990.0);
+        let columns = Columns {
+            liquidation: true,
+            consensus: false,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: None,
+            cost_basis: None,
+        };
+
+        fill_table_with_eth(&mut table, &eth_info, "ETH", &columns);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0].len(), 8);
+        assert_eq!(table[1][7].get_content(), "0.50 %");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_erc20_liquidation_success() {
+        let mut table = Table::new();
+        let mut eth_info = erc20::TokenInfo::new("", &1.0, &2000.0, &1.0, "");
+        eth_info.liquidation_usd = Some(1990.0);
+        let mut list_erc20 = erc20::Tokens::new();
+        // YFI without a ParaSwap quote
+        list_erc20.insert(
+            "YFI".to_string(),
+            Some(erc20::TokenInfo::new(
+                "0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e",
+                &1.0,
+                &6000.0,
+                &3.0,
+                "",
+            )),
+        );
+        let columns = Columns {
+            liquidation: true,
+            consensus: false,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: None,
+            cost_basis: None,
+        };
+
+        let usd_total = fill_table_with_erc20(
+            &mut table,
+            &eth_info,
+            list_erc20,
+            &mut vec![],
+            "ETH",
+            &columns,
+        );
+        assert_eq!(usd_total, 8000.0);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0][6].get_content(), "-");
+        assert_eq!(table[1][6].get_content(), "1990.00 US$");
+        assert_eq!(table[1][7].get_content(), "0.50 %");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_eth_consensus_success() {
+        let mut table = Table::new();
+        let mut eth_info = erc20::TokenInfo::new("", &1.0, &2000.0, &1.0, "");
+        eth_info.price_source = "chainlink".to_string();
+        let columns = Columns {
+            liquidation: false,
+            consensus: true,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: None,
+            cost_basis: None,
+        };
+
+        fill_table_with_eth(&mut table, &eth_info, "ETH", &columns);
+        assert_eq!(table[0].len(), 8);
+        assert_eq!(table[1][6].get_content(), "chainlink");
+        assert_eq!(table[1][7].get_content(), "-");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_erc20_no_consensus_success() {
+        let mut table = Table::new();
+        let eth_info = erc20::TokenInfo::new("", &1.0, &2000.0, &1.0, "");
+        let quotes = vec![
+            ("coingecko".to_string(), 5000.0),
+            ("paraswap".to_string(), 7000.0),
+        ];
+        let consensus = crate::lib::consensus::get_consensus(
+            &quotes,
+            crate::lib::consensus::DEFAULT_MAX_DEVIATION,
+        )
+        .unwrap();
+        let mut token_info = erc20::TokenInfo::new(
+            "0x0bc529c00c6401aef6d220be8c6ea1667f6ad93e",
+            &1.0,
+            &consensus.price,
+            &3.0,
+            "",
+        );
+        token_info.price_source = consensus.get_source();
+        token_info.price_spread = consensus.spread;
+        let mut list_erc20 = erc20::Tokens::new();
+        list_erc20.insert("YFI".to_string(), Some(token_info));
+        let columns = Columns {
+            liquidation: false,
+            consensus: true,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: None,
+            cost_basis: None,
+        };
+
+        let usd_total = fill_table_with_erc20(
+            &mut table,
+            &eth_info,
+            list_erc20,
+            &mut vec![],
+            "ETH",
+            &columns,
+        );
+        assert_eq!(usd_total, 8000.0);
+        assert_eq!(table.len(), 2);
+        assert_eq!(table[0][0].get_content(), "YFI");
+        assert_eq!(
+            table[0][6].get_content(),
+            "no consensus; outlier: coingecko, paraswap"
+        );
+        assert_eq!(table[0][7].get_content(), "33.33 %");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_eth_currencies_success() {
+        let mut table = Table::new();
+        let mut eth_info = erc20::TokenInfo::new("", &1.0, &2000.0, &1.0, "");
+        eth_info.fiat_balances = vec![("eur".to_string(), 1800.0), ("chf".to_string(), 1750.0)];
+        let columns = Columns {
+            liquidation: false,
+            consensus: false,
+            currencies: vec!["eur".to_string(), "chf".to_string()],
+            quote: None,
+            cost_basis: None,
+        };
+
+        fill_table_with_eth(&mut table, &eth_info, "ETH", &columns);
+        assert_eq!(table[0].len(), 7);
+        assert_eq!(table[0][4].get_content(), "TOTAL EUR");
+        assert_eq!(table[1][4].get_content(), "1800.00 €");
+        assert_eq!(table[1][5].get_content(), "1750.00 CHF");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_eth_unconverted_currency_success() {
+        let mut table = Table::new();
+        let mut eth_info = erc20::TokenInfo::new("", &1.0, &2000.0, &1.0, "");
+        eth_info.fiat_balances = vec![("eur".to_string(), 1800.0)];
+        let columns = Columns {
+            liquidation: false,
+            consensus: false,
+            currencies: vec!["eur".to_string(), "chf".to_string()],
+            quote: None,
+            cost_basis: None,
+        };
+
+        fill_table_with_eth(&mut table, &eth_info, "ETH", &columns);
+        assert_eq!(table[1][4].get_content(), "1800.00 €");
+        assert_eq!(table[1][5].get_content(), "");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_eth_quote_success() {
+        let mut table = Table::new();
+        let eth_info = erc20::TokenInfo::new("", &2.0, &2000.0, &1.0, "");
+        let columns = Columns {
+            liquidation: false,
+            consensus: false,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: Some(Quote {
+                symbol: "WBTC".to_string(),
+                usd_price: 40000.0,
+            }),
+            cost_basis: None,
+        };
+
+        fill_table_with_eth(&mut table, &eth_info, "ETH", &columns);
+        assert_eq!(table[0].len(), 7);
+        assert_eq!(table[0][4].get_content(), "TOTAL WBTC");
+        assert_eq!(table[1][4].get_content(), "0.100000 WBTC");
+        assert_eq!(get_chart_value(eth_info.usd_balance, &columns), 0.1);
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_eth_cost_basis_success() {
+        let mut table = Table::new();
+        let mut eth_info = erc20::TokenInfo::new("", &2.0, &2000.0, &1.0, "");
+        eth_info.cost_basis = Some(3000.0);
+        eth_info.cost_basis_amount = Some(2.0);
+        eth_info.realized_pnl = Some(-100.0);
+        let columns = Columns {
+            liquidation: false,
+            consensus: false,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: None,
+            cost_basis: Some(Method::Fifo),
+        };
+
+        fill_table_with_eth(&mut table, &eth_info, "ETH", &columns);
+        assert_eq!(table[0].len(), 9);
+        assert_eq!(table[0][6].get_content(), "COST BASIS USD");
+        assert_eq!(table[1][6].get_content(), "3000.00 US$");
+        assert_eq!(table[1][7].get_content(), "1000.00 US$");
+        assert_eq!(table[1][8].get_content(), "-100.00 US$");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_snapshot_success() {
+        let mut table = Table::new();
+        let eth_info = erc20::TokenInfo::new("", &2.0, &2000.0, &1.0, "");
+        let snapshot = Snapshot::new(
+            "0x000000000000000000000000000000000000dead",
+            18908894,
+            1704067199,
+            &eth_info,
+            &erc20::Tokens::new(),
+        );
 
-        fill_table_with_eth(&mut table, eth_balance, eth_balance_vs_usd);
+        fill_table_with_snapshot(&mut table, &snapshot);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[2][5].get_content(), "4000.00 US$");
+
+        let mut table = Table::new();
+        fill_table_with_snapshots(&mut table, &[snapshot]);
         assert_eq!(table.len(), 2);
+        assert_eq!(table[1][1].get_content(), "2023-12-31 23:59:59 UTC");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_transfers_success() {
+        let mut table = Table::new();
+        let transfer = Transfer {
+            hash: "0xabc".to_string(),
+            block: 18908894,
+            timestamp: 1704067199,
+            kind: history::Kind::Erc20,
+            direction: Direction::In,
+            counterparty: "0x0000000000000000000000000000000000000001".to_string(),
+            symbol: "DAI".to_string(),
+            contract_address: "0x6b175474e89094c44da98b954eedeac495271d0f".to_string(),
+            amount: 1.5,
+            usd_value: Some(1.5),
+        };
+        let unpriced = Transfer {
+            usd_value: None,
+            ..transfer.clone()
+        };
+
+        fill_table_with_transfers(&mut table, &[transfer, unpriced]);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[1][1].get_content(), "ERC20");
+        assert_eq!(table[1][2].get_content(), "in");
+        assert_eq!(table[1][4].get_content(), "1.500000");
+        assert_eq!(table[1][6].get_content(), "1.50 US$");
+        assert_eq!(table[2][6].get_content(), "");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_tax_totals_success() {
+        let mut table = Table::new();
+        let gains = vec![tax::Gain {
+            symbol: "YFI".to_string(),
+            disposal: cost_basis::Disposal {
+                hash: "0xabc".to_string(),
+                acquired: Some(1704067200),
+                disposed: 1706745599,
+                amount: 1.0,
+                proceeds: Some(200.0),
+                cost: Some(250.0),
+                fee: false,
+            },
+        }];
+
+        fill_table_with_tax_totals(&mut table, &gains);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[1][3].get_content(), "-50.00 US$");
+        assert_eq!(table[2][3].get_content(), "0.00 US$");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_gas_totals_success() {
+        let mut table = Table::new();
+        let gas_totals = vec![GasTotal {
+            key: "2024-01".to_string(),
+            transactions: 2,
+            eth: 0.05,
+            usd_value: Some(100.0),
+        }];
+        let total = GasTotal {
+            key: "TOTAL".to_string(),
+            usd_value: None,
+            ..gas_totals[0].clone()
+        };
+
+        fill_table_with_gas_totals(&mut table, "MONTH", &gas_totals, &total, "ETH");
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[1][2].get_content(), "0.050000 Ξ");
+        assert_eq!(table[1][3].get_content(), "100.00 US$");
+        assert_eq!(table[2][0].get_content(), "TOTAL");
+        assert_eq!(table[2][3].get_content(), "-");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_nfts_success() {
+        let mut table = Table::new();
+        let nft = Nft {
+            collection: "BoredApeYachtClub".to_string(),
+            symbol: "BAYC".to_string(),
+            contract_address: "0xbc4ca0eda7647a8ab7c2061c2e118a18a936f13d".to_string(),
+            token_id: web3::types::U256::from(42),
+            token_uri: None,
+            floor_usd: Some(50000.0),
+        };
+        let unpriced = Nft {
+            floor_usd: None,
+            ..nft.clone()
+        };
+
+        fill_table_with_nfts(&mut table, &[nft, unpriced]);
+        assert_eq!(table.len(), 4);
+        assert_eq!(table[1][2].get_content(), "42");
+        assert_eq!(table[1][3].get_content(), "-");
+        assert_eq!(table[2][4].get_content(), "-");
+        assert_eq!(table[3][4].get_content(), "50000.00 US$");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_lending_success() {
+        let protocol = Protocol {
+            name: "Aave V3".to_string(),
+            positions: vec![
+                lending::LendingPosition {
+                    symbol: "WETH".to_string(),
+                    contract_address: "0xc02aaa39b223fe8d0a0e5c4f27ead9083c756cc2".to_string(),
+                    supplied: 2.0,
+                    borrowed: 0.0,
+                    usd_price: Some(2000.0),
+                },
+                lending::LendingPosition {
+                    symbol: "USDC".to_string(),
+                    contract_address: "0xa0b86991c6218b36c1d19d4a2e9eb0ce3606eb48".to_string(),
+                    supplied: 0.0,
+                    borrowed: 1500.0,
+                    usd_price: None,
+                },
+            ],
+            health_factor: Some(2.2),
+            receipt_tokens: vec![],
+        };
+
+        let mut position_table = Table::new();
+        fill_table_with_lending_positions(&mut position_table, std::slice::from_ref(&protocol));
+        assert_eq!(position_table.len(), 3);
+        assert_eq!(position_table[1][5].get_content(), "4000.00 US$");
+        assert_eq!(position_table[2][6].get_content(), "-");
+
+        let mut protocol_table = Table::new();
+        fill_table_with_lending_protocols(&mut protocol_table, std::slice::from_ref(&protocol));
+        assert_eq!(protocol_table.len(), 3);
+        assert_eq!(protocol_table[1][1].get_content(), "4000.00 US$");
+        assert_eq!(protocol_table[1][2].get_content(), "-");
+        assert_eq!(protocol_table[1][3].get_content(), "-");
+        assert_eq!(protocol_table[1][4].get_content(), "2.20");
+        assert_eq!(protocol_table[2][3].get_content(), "-");
+
+        let mut priced = protocol;
+        priced.positions[1].usd_price = Some(1.0);
+        let mut protocol_table = Table::new();
+        fill_table_with_lending_protocols(&mut protocol_table, &[priced]);
+        assert_eq!(protocol_table[1][3].get_content(), "2500.00 US$");
+        assert_eq!(protocol_table[2][3].get_content(), "2500.00 US$");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_multi_tokens_success() {
+        let mut table = Table::new();
+        let multi_token = MultiToken {
+            collection: "parallel".to_string(),
+            contract_address: "0x76be3b62873462d2142405439777e971754e8e77".to_string(),
+            token_id: web3::types::U256::from(7),
+            balance: web3::types::U256::from(3),
+            floor_usd: None,
+        };
+
+        fill_table_with_multi_tokens(&mut table, &[multi_token]);
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[1][3].get_content(), "3");
+        assert_eq!(table[1][4].get_content(), "");
+        assert_eq!(table[2][4].get_content(), "0.00 US$");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_chain_totals_success() {
+        let mut table = Table::new();
+        let chains = chain::default_chains("", "");
+        let chain_totals = vec![
+            ChainTotal {
+                chain: chains[0].clone(),
+                native_balance: 1.5,
+                usd_total: 4000.0,
+            },
+            ChainTotal {
+                chain: chains[1].clone(),
+                native_balance: 100.0,
+                usd_total: 250.0,
+            },
+        ];
+
+        fill_table_with_chain_totals(&mut table, &chain_totals);
+        assert_eq!(table.len(), 4);
+        assert_eq!(table[2][0].get_content(), "polygon");
+        assert_eq!(table[2][1].get_content(), "100.000000 POL");
+        assert_eq!(table[3][2].get_content(), "4250.00 US$");
+    }
+
+    #[tokio::test]
+    async fn fill_table_with_diff_success() {
+        let mut table = Table::new();
+        let from = Snapshot::new(
+            "0x000000000000000000000000000000000000dead",
+            18908894,
+            1704067199,
+            &erc20::TokenInfo::new("", &2.0, &2000.0, &1.0, ""),
+            &erc20::Tokens::new(),
+        );
+        let to = Snapshot::new(
+            "0x000000000000000000000000000000000000dead",
+            19127000,
+            1706745599,
+            &erc20::TokenInfo::new("", &3.0, &2500.0, &1.0, ""),
+            &erc20::Tokens::new(),
+        );
+
+        fill_table_with_diff(&mut table, &from, &to, &diff::diff_snapshots(&from, &to));
+        assert_eq!(table.len(), 3);
+        assert_eq!(table[1][8].get_content(), "+3500.00 US$");
+        assert_eq!(table[1][9].get_content(), "+87.50 %");
+        assert_eq!(table[2][10].get_content(), "+1500.00 US$");
+        assert_eq!(table[2][11].get_content(), "+2000.00 US$");
+    }
+
+    #[tokio::test]
+    async fn get_snapshot_value_success() {
+        let mut tokens = erc20::Tokens::new();
+        tokens.insert(
+            "YFI".to_string(),
+            Some(erc20::TokenInfo::new(
+                "0x0bc529c00C6401aEF6D220BE8C6Ea1667F6Ad93e",
+                &1.0,
+                &8000.0,
+                &4.0,
+                "",
+            )),
+        );
+        let snapshot = Snapshot::new(
+            "0x000000000000000000000000000000000000dead",
+            18908894,
+            1704067199,
+            &erc20::TokenInfo::new("", &2.0, &2000.0, &1.0, ""),
+            &tokens,
+        );
+
+        assert_eq!(get_snapshot_value(&snapshot, None), 12000.0);
+        assert_eq!(get_snapshot_value(&snapshot, Some("yfi")), 8000.0);
+        assert_eq!(get_snapshot_value(&snapshot, Some("AAVE")), 0.0);
     }
 
     #[tokio::test]
     async fn fill_table_with_erc20_success() {
         let mut table = Table::new();
-        let eth_balance = 0.0;
-        let usd_balance = 0.0;
+        let eth_info = erc20::TokenInfo::new("", &0.0, &0.0, &1.0, "");
+        let columns = Columns {
+            liquidation: false,
+            consensus: false,
+            currencies: vec![fiat::DEFAULT_CURRENCY.to_string()],
+            quote: None,
+            cost_basis: None,
+        };
 
         let test_account_address: H160 =
             "000000000000000000000000000000000000dead".parse().unwrap();
@@ -262,7 +2546,7 @@ mod test {
 
         let list_erc20 = erc20::list_erc20_for_account(
             test_account_address,
-            &test_etherscan_api_key,
+            &chain::Explorer::etherscan(&test_etherscan_api_key),
             &test_ethplorer_api_key,
             list_config,
         )
@@ -271,12 +2555,14 @@ mod test {
 
         let mut data = vec![Data {
             label: "ETH".into(),
-            value: usd_balance as f32,
+            value: eth_info.usd_balance as f32,
             color: Some(Style::new().fg(random::get_color())),
             fill: random::get_char(),
         }];
 
-        fill_table_with_erc20(&mut table, eth_balance, usd_balance, list_erc20, &mut data);
+        fill_table_with_erc20(
+            &mut table, &eth_info, list_erc20, &mut data, "ETH", &columns,
+        );
         assert_eq!(table.len(), 2);
     }
 }